@@ -0,0 +1,512 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    backtest::TimedBar,
+    error::TaResult,
+    strategy::{Action, Expiry, Side, StrategyError, StrategyNode, strat::Strategy},
+    traits::Candle,
+};
+
+/// How an option that expires exactly at its strike price is settled.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Tie {
+    /// The stake is returned.
+    #[default]
+    Refund,
+    /// The stake is lost.
+    Loss,
+    /// The payout is paid as for a win.
+    Win,
+}
+
+/// Settings of a `BinaryBacktest`.
+///
+/// Every field has a default, so partial JSON objects deserialize into a complete
+/// configuration.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct BinaryConfig {
+    /// Amount risked on every option.
+    pub stake: f64,
+    /// Time until the options expire. `Expiry::Seconds` requires timestamped bars.
+    pub expiry: Expiry,
+    /// Profit of a winning option as a percentage of the stake.
+    pub payout: f64,
+    pub tie: Tie,
+    /// Maximum number of options open at the same time. Signals above the limit are skipped.
+    pub max_open: Option<usize>,
+}
+
+impl Default for BinaryConfig {
+    fn default() -> Self {
+        Self {
+            stake: 1.0,
+            expiry: Expiry::Bars(1),
+            payout: 80.0,
+            tie: Tie::default(),
+            max_open: None,
+        }
+    }
+}
+
+impl BinaryConfig {
+    pub fn new(stake: f64, expiry: Expiry, payout: f64) -> Self {
+        Self {
+            stake,
+            expiry,
+            payout,
+            ..Self::default()
+        }
+    }
+
+    pub fn with_tie(mut self, tie: Tie) -> Self {
+        self.tie = tie;
+        self
+    }
+
+    pub fn with_max_open(mut self, max_open: usize) -> Self {
+        self.max_open = Some(max_open);
+        self
+    }
+
+    /// Win rate, in percent, at which the options neither make nor lose money, ignoring ties.
+    pub fn break_even_win_rate(&self) -> f64 {
+        100.0 / (1.0 + self.payout / 100.0)
+    }
+
+    /// Checks that the stake is positive, the payout is not negative and the expiry is neither
+    /// zero nor longer than a series or a `TimeDelta` can be.
+    pub fn validate(&self) -> TaResult<()> {
+        if !(self.stake.is_finite() && self.stake > 0.0) {
+            return Err(StrategyError::Configuration(format!("stake {}", self.stake)).into());
+        }
+        if !(self.payout.is_finite() && self.payout >= 0.0) {
+            return Err(StrategyError::Configuration(format!("payout {}", self.payout)).into());
+        }
+        let valid = match self.expiry {
+            Expiry::Bars(bars) => bars > 0 && bars <= isize::MAX as usize,
+            Expiry::Seconds(seconds) => seconds > 0 && expiry_delta(seconds).is_some(),
+        };
+        if !valid {
+            return Err(StrategyError::Configuration(format!("expiry {:?}", self.expiry)).into());
+        }
+        Ok(())
+    }
+}
+
+fn expiry_delta(seconds: u64) -> Option<TimeDelta> {
+    i64::try_from(seconds).ok().and_then(TimeDelta::try_seconds)
+}
+
+/// Result of a settled option, before the tie policy is applied.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Win,
+    Loss,
+    Tie,
+}
+
+impl Outcome {
+    /// `Outcome::Win` or `Outcome::Loss` once ties are settled by `tie`, `None` for a refund.
+    pub fn settle(self, tie: Tie) -> Option<Outcome> {
+        match (self, tie) {
+            (Outcome::Win, _) | (Outcome::Tie, Tie::Win) => Some(Outcome::Win),
+            (Outcome::Loss, _) | (Outcome::Tie, Tie::Loss) => Some(Outcome::Loss),
+            (Outcome::Tie, Tie::Refund) => None,
+        }
+    }
+}
+
+/// A settled option. `Side::Long` is a call and `Side::Short` a put.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BinaryOption {
+    pub side: Side,
+    pub entry_index: usize,
+    pub entry_time: Option<DateTime<Utc>>,
+    /// Close of the bar that produced the signal.
+    pub strike: f64,
+    pub expiry_index: usize,
+    pub expiry_time: Option<DateTime<Utc>>,
+    /// Close of the bar the option was settled against.
+    pub settlement: f64,
+    pub outcome: Outcome,
+    /// Payout on a win, minus the stake on a loss and according to the tie policy on a tie.
+    pub profit: f64,
+}
+
+/// Summary of a set of settled options.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub struct BinaryStats {
+    pub trades: usize,
+    /// Options that made money, including ties settled as wins.
+    pub wins: usize,
+    /// Options that lost the stake, including ties settled as losses.
+    pub losses: usize,
+    /// Options whose stake was refunded.
+    pub refunds: usize,
+    /// Percentage of wins among the options that were not refunded, `0.0` when there are none.
+    pub win_rate: f64,
+    pub net_profit: f64,
+    /// Longest run of losses in entry order. Refunds neither extend nor break a run.
+    pub max_consecutive_losses: usize,
+    /// Win rate, in percent, needed to break even at the configured payout.
+    pub break_even_win_rate: f64,
+}
+
+impl BinaryStats {
+    /// Summarizes `options`, given in entry order.
+    pub fn from_options<'a>(
+        options: impl IntoIterator<Item = &'a BinaryOption>,
+        config: &BinaryConfig,
+    ) -> Self {
+        let mut stats = Self {
+            break_even_win_rate: config.break_even_win_rate(),
+            ..Self::default()
+        };
+        let mut streak = 0;
+        for option in options {
+            stats.trades += 1;
+            stats.net_profit += option.profit;
+            match option.outcome.settle(config.tie) {
+                Some(Outcome::Win) => {
+                    stats.wins += 1;
+                    streak = 0;
+                }
+                Some(_) => {
+                    stats.losses += 1;
+                    streak += 1;
+                    stats.max_consecutive_losses = stats.max_consecutive_losses.max(streak);
+                }
+                None => stats.refunds += 1,
+            }
+        }
+        let decided = stats.wins + stats.losses;
+        if decided > 0 {
+            stats.win_rate = stats.wins as f64 / decided as f64 * 100.0;
+        }
+        stats
+    }
+}
+
+/// Options traded on a single asset.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BinaryAssetReport {
+    /// Settled options in entry order.
+    pub options: Vec<BinaryOption>,
+    /// Options still open after the last bar, left out of the statistics.
+    pub unsettled: usize,
+    pub stats: BinaryStats,
+}
+
+/// Result of a `BinaryBacktest` over several assets.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BinaryReport {
+    pub assets: BTreeMap<String, BinaryAssetReport>,
+    /// Statistics of the options of every asset together. The loss streak is the longest
+    /// streak of any single asset.
+    pub total: BinaryStats,
+}
+
+/// Simulates fixed-stake binary options opened by the actions of a strategy.
+///
+/// Every Buy or StrongBuy opens a call and every Sell or StrongSell a put, struck at the close
+/// of the bar that produced the signal, regardless of the options already open. An option
+/// expiring after `Expiry::Bars(n)` is settled against the close `n` bars later; one expiring
+/// after `Expiry::Seconds(s)` against the close of the first bar stamped at or after the
+/// expiry time. Bars of the warm-up period produce no options.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct BinaryBacktest {
+    pub config: BinaryConfig,
+}
+
+/// Option waiting for its expiry.
+struct Pending {
+    side: Side,
+    index: usize,
+    time: Option<DateTime<Utc>>,
+    strike: f64,
+    expires: Expires,
+}
+
+enum Expires {
+    Index(usize),
+    Time(DateTime<Utc>),
+}
+
+impl BinaryBacktest {
+    pub fn new(config: BinaryConfig) -> Self {
+        Self { config }
+    }
+
+    /// Runs a fresh `Strategy` built from `strategy` over the bars of every asset.
+    pub fn run_assets(
+        &self,
+        strategy: &StrategyNode,
+        assets: &BTreeMap<String, Vec<TimedBar>>,
+    ) -> TaResult<BinaryReport> {
+        let mut reports = BTreeMap::new();
+        for (asset, bars) in assets {
+            let report = self.run(&mut Strategy::new(strategy.clone()), bars)?;
+            reports.insert(asset.clone(), report);
+        }
+        let mut total = BinaryStats::from_options(
+            reports.values().flat_map(|report| &report.options),
+            &self.config,
+        );
+        total.max_consecutive_losses = reports
+            .values()
+            .map(|report| report.stats.max_consecutive_losses)
+            .max()
+            .unwrap_or(0);
+        Ok(BinaryReport {
+            assets: reports,
+            total,
+        })
+    }
+
+    /// Runs `strategy` over the bars of a single asset.
+    pub fn run(&self, strategy: &mut Strategy, bars: &[TimedBar]) -> TaResult<BinaryAssetReport> {
+        self.config.validate()?;
+        let mut open: Vec<Pending> = Vec::new();
+        let mut options = Vec::new();
+        for (index, bar) in bars.iter().enumerate() {
+            let close = bar.data.close();
+            let mut remaining = Vec::with_capacity(open.len());
+            for pending in open {
+                let expired = match pending.expires {
+                    Expires::Index(expiry) => index >= expiry,
+                    Expires::Time(expiry) => bar.timestamp.is_some_and(|time| time >= expiry),
+                };
+                if expired {
+                    options.push(self.settle(pending, index, bar.timestamp, close));
+                } else {
+                    remaining.push(pending);
+                }
+            }
+            open = remaining;
+
+            let action = match bar.timestamp {
+                Some(timestamp) => strategy.evaluate_at(&bar.data, timestamp)?,
+                None => strategy.evaluate(&bar.data)?,
+            }
+            .unwrap_or(Action::Hold);
+            let side = Side::of(action);
+            let full = self.config.max_open.is_some_and(|max| open.len() >= max);
+            if side == Side::Flat || full {
+                continue;
+            }
+            let expires = match self.config.expiry {
+                Expiry::Bars(bars) => index.checked_add(bars).map(Expires::Index),
+                Expiry::Seconds(seconds) => {
+                    let time = bar.timestamp.ok_or(StrategyError::MissingTimestamp)?;
+                    expiry_delta(seconds)
+                        .and_then(|delta| time.checked_add_signed(delta))
+                        .map(Expires::Time)
+                }
+            }
+            .ok_or_else(|| {
+                StrategyError::Configuration(format!("expiry {:?}", self.config.expiry))
+            })?;
+            open.push(Pending {
+                side,
+                index,
+                time: bar.timestamp,
+                strike: close,
+                expires,
+            });
+        }
+        options.sort_by_key(|option: &BinaryOption| option.entry_index);
+        let stats = BinaryStats::from_options(&options, &self.config);
+        Ok(BinaryAssetReport {
+            options,
+            unsettled: open.len(),
+            stats,
+        })
+    }
+
+    fn settle(
+        &self,
+        pending: Pending,
+        index: usize,
+        time: Option<DateTime<Utc>>,
+        settlement: f64,
+    ) -> BinaryOption {
+        let moved = match pending.side {
+            Side::Short => pending.strike - settlement,
+            _ => settlement - pending.strike,
+        };
+        let outcome = if moved > 0.0 {
+            Outcome::Win
+        } else if moved < 0.0 {
+            Outcome::Loss
+        } else {
+            Outcome::Tie
+        };
+        let win = self.config.stake * self.config.payout / 100.0;
+        let profit = match outcome.settle(self.config.tie) {
+            Some(Outcome::Win) => win,
+            Some(_) => -self.config.stake,
+            None => 0.0,
+        };
+        BinaryOption {
+            side: pending.side,
+            entry_index: pending.index,
+            entry_time: pending.time,
+            strike: pending.strike,
+            expiry_index: index,
+            expiry_time: time,
+            settlement,
+            outcome,
+            profit,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(not(feature = "js"))]
+    use crate::Indicator;
+    #[cfg(feature = "js")]
+    use crate::indicators::indicator::Indicator;
+
+    use super::*;
+    use crate::error::TaError;
+    use crate::strategy::{Condition, MarketData};
+    use crate::types::OutputType;
+
+    /// Calls above 100 and puts below 90.
+    fn breakout() -> TaResult<StrategyNode> {
+        Ok(StrategyNode::If {
+            condition: Condition::greater_than(Indicator::sma(1)?, OutputType::Single(100.0)),
+            then_branch: Box::new(StrategyNode::Action(Action::Buy)),
+            else_branch: Some(Box::new(StrategyNode::If {
+                condition: Condition::less_than(Indicator::sma(1)?, OutputType::Single(90.0)),
+                then_branch: Box::new(StrategyNode::Action(Action::Sell)),
+                else_branch: Some(Box::new(StrategyNode::Action(Action::Hold))),
+            })),
+        })
+    }
+
+    fn bars(closes: &[f64]) -> Vec<TimedBar> {
+        closes
+            .iter()
+            .map(|&close| TimedBar::from(MarketData::Float(close)))
+            .collect()
+    }
+
+    #[test]
+    fn test_settle_after_bars() -> TaResult<()> {
+        let config = BinaryConfig::new(10.0, Expiry::Bars(2), 80.0);
+        let report = BinaryBacktest::new(config).run(
+            &mut Strategy::new(breakout()?),
+            &bars(&[95.0, 101.0, 95.0, 102.0, 99.0, 101.0, 85.0]),
+        )?;
+        // Calls at 101 (bar 1) and 102 (bar 3) expire at 102 and 101; the call at 101 (bar 5)
+        // and the put at 85 (bar 6) are still open.
+        assert_eq!(report.options.len(), 2);
+        assert_eq!(report.options[0].outcome, Outcome::Win);
+        assert_eq!(report.options[0].expiry_index, 3);
+        assert_eq!(report.options[1].outcome, Outcome::Loss);
+        assert_eq!(report.unsettled, 2);
+        assert_eq!(report.stats.net_profit, 8.0 - 10.0);
+        assert_eq!(report.stats.win_rate, 50.0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_ties_and_streaks() -> TaResult<()> {
+        let series = bars(&[95.0, 101.0, 101.0, 101.0, 100.0, 89.0, 90.0, 88.0, 88.0]);
+        let run = |tie| -> TaResult<BinaryStats> {
+            let config = BinaryConfig::default().with_tie(tie);
+            Ok(BinaryBacktest::new(config)
+                .run(&mut Strategy::new(breakout()?), &series)?
+                .stats)
+        };
+        // Calls at 101 tie twice and lose once, the put at 89 loses and the one at 88 ties.
+        let refund = run(Tie::Refund)?;
+        assert_eq!((refund.wins, refund.losses, refund.refunds), (0, 2, 3));
+        assert_eq!(refund.max_consecutive_losses, 2);
+        assert_eq!(refund.net_profit, -2.0);
+        let loss = run(Tie::Loss)?;
+        assert_eq!(loss.losses, 5);
+        assert_eq!(loss.max_consecutive_losses, 5);
+        let win = run(Tie::Win)?;
+        assert_eq!((win.wins, win.losses), (3, 2));
+        assert_eq!(win.win_rate, 60.0);
+        assert!((win.net_profit - (2.4 - 2.0)).abs() < 1e-9);
+
+        // Without a payout, wins make no money but still count as wins.
+        let config = BinaryConfig::new(1.0, Expiry::Bars(1), 0.0).with_tie(Tie::Win);
+        config.validate()?;
+        let free = BinaryBacktest::new(config)
+            .run(&mut Strategy::new(breakout()?), &series)?
+            .stats;
+        assert_eq!((free.wins, free.losses, free.refunds), (3, 2, 0));
+        assert_eq!(free.win_rate, 60.0);
+        assert_eq!(free.net_profit, -2.0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_expiry_in_seconds() -> TaResult<()> {
+        let start = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let series: Vec<TimedBar> = [95.0, 101.0, 99.0, 103.0, 104.0]
+            .iter()
+            .enumerate()
+            .map(|(i, &close)| {
+                TimedBar::at(
+                    start + TimeDelta::seconds(60 * i as i64),
+                    MarketData::Float(close),
+                )
+            })
+            .collect();
+        let config = BinaryConfig::new(1.0, Expiry::Seconds(90), 80.0);
+        let report = BinaryBacktest::new(config).run(&mut Strategy::new(breakout()?), &series)?;
+        // The call at 101 expires 90s later, settled at the bar two minutes later.
+        assert_eq!(report.options[0].expiry_index, 3);
+        assert_eq!(report.options[0].outcome, Outcome::Win);
+
+        let untimed =
+            BinaryBacktest::new(config).run(&mut Strategy::new(breakout()?), &bars(&[95.0, 101.0]));
+        assert!(untimed.is_err());
+
+        let config: BinaryConfig =
+            serde_json::from_str(r#"{"expiry":{"Seconds":100000000000000000}}"#)?;
+        assert!(config.validate().is_err());
+        let late = TimedBar::at(DateTime::<Utc>::MAX_UTC, MarketData::Float(101.0));
+        let config = BinaryConfig::new(1.0, Expiry::Seconds(90), 80.0);
+        let result = BinaryBacktest::new(config)
+            .run(&mut Strategy::new(breakout()?), &[series[0].clone(), late]);
+        assert!(matches!(
+            result,
+            Err(TaError::Strategy(StrategyError::Configuration(_)))
+        ));
+        assert!(
+            BinaryConfig::new(1.0, Expiry::Bars(usize::MAX), 80.0)
+                .validate()
+                .is_err()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_per_asset_report() -> TaResult<()> {
+        let assets = BTreeMap::from([
+            ("EURUSD".to_string(), bars(&[95.0, 101.0, 102.0, 103.0])),
+            ("GBPUSD".to_string(), bars(&[95.0, 89.0, 90.0, 91.0])),
+        ]);
+        let config = BinaryConfig::default().with_max_open(1);
+        let report = BinaryBacktest::new(config).run_assets(&breakout()?, &assets)?;
+        assert_eq!(report.assets["EURUSD"].stats.wins, 2);
+        assert_eq!(report.assets["GBPUSD"].stats.losses, 1);
+        assert_eq!(report.total.trades, 3);
+        assert_eq!(report.total.max_consecutive_losses, 1);
+        assert!((report.total.break_even_win_rate - 100.0 / 1.8).abs() < 1e-9);
+        let json = serde_json::to_string(&report)?;
+        assert_eq!(serde_json::from_str::<BinaryReport>(&json)?, report);
+        Ok(())
+    }
+}
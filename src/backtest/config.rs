@@ -0,0 +1,186 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{error::TaResult, strategy::StrategyError};
+
+/// Price at which an order produced by the strategy on a bar is filled.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EntryTiming {
+    /// Fill at the close of the bar that produced the signal.
+    #[default]
+    SameClose,
+    /// Fill at the open of the following bar. Signals on the last bar are never filled.
+    NextOpen,
+}
+
+/// Commission charged on every fill, entries and exits alike.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(default)]
+pub struct Fees {
+    /// Percentage of the notional value of the fill.
+    pub percent: f64,
+    /// Flat amount per fill.
+    pub fixed: f64,
+}
+
+impl Fees {
+    pub fn new(percent: f64, fixed: f64) -> Self {
+        Self { percent, fixed }
+    }
+
+    /// Fee charged on a fill of the given notional value.
+    pub fn charge(&self, notional: f64) -> f64 {
+        notional.abs() * self.percent / 100.0 + self.fixed
+    }
+}
+
+/// Difference between the reference price and the fill price, always applied against the
+/// trader: buys fill higher and sells fill lower.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum Slippage {
+    /// Percentage of the reference price.
+    Percent(f64),
+    /// Absolute price difference.
+    Fixed(f64),
+}
+
+impl Default for Slippage {
+    fn default() -> Self {
+        Slippage::Percent(0.0)
+    }
+}
+
+impl Slippage {
+    /// Returns the fill price of a buy (`buy == true`) or a sell at `price`.
+    pub fn apply(&self, price: f64, buy: bool) -> f64 {
+        let offset = match self {
+            Slippage::Percent(percent) => price * percent / 100.0,
+            Slippage::Fixed(amount) => *amount,
+        };
+        if buy { price + offset } else { price - offset }
+    }
+}
+
+/// Quantity opened by every entry.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum Sizing {
+    /// A fixed number of units.
+    Units(f64),
+    /// Units worth this much cash at the fill price.
+    Cash(f64),
+    /// Units worth this percentage of the equity at the time of the fill.
+    PercentOfEquity(f64),
+}
+
+impl Default for Sizing {
+    fn default() -> Self {
+        Sizing::PercentOfEquity(100.0)
+    }
+}
+
+impl Sizing {
+    /// Returns the quantity opened at `price` with the given equity.
+    pub fn quantity(&self, price: f64, equity: f64) -> f64 {
+        match self {
+            Sizing::Units(units) => *units,
+            Sizing::Cash(cash) => cash / price,
+            Sizing::PercentOfEquity(percent) => equity * percent / 100.0 / price,
+        }
+    }
+}
+
+/// Settings of a `Backtest`.
+///
+/// Every field has a default, so a partial JSON object such as `{ "fees": { "percent": 0.1 } }`
+/// deserializes into a complete configuration.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct BacktestConfig {
+    /// Cash available before the first bar.
+    pub initial_capital: f64,
+    pub entry: EntryTiming,
+    pub fees: Fees,
+    pub slippage: Slippage,
+    pub sizing: Sizing,
+    /// Whether sell signals open short positions when flat. When disabled they only close
+    /// long positions.
+    pub allow_short: bool,
+    /// Whether a position still open after the last bar is closed at its close. When disabled
+    /// it is left out of the trade list and only shows up in the equity curve.
+    pub close_at_end: bool,
+}
+
+impl Default for BacktestConfig {
+    fn default() -> Self {
+        Self {
+            initial_capital: 10_000.0,
+            entry: EntryTiming::default(),
+            fees: Fees::default(),
+            slippage: Slippage::default(),
+            sizing: Sizing::default(),
+            allow_short: true,
+            close_at_end: true,
+        }
+    }
+}
+
+impl BacktestConfig {
+    pub fn new(initial_capital: f64) -> Self {
+        Self {
+            initial_capital,
+            ..Self::default()
+        }
+    }
+
+    pub fn with_entry(mut self, entry: EntryTiming) -> Self {
+        self.entry = entry;
+        self
+    }
+
+    pub fn with_fees(mut self, fees: Fees) -> Self {
+        self.fees = fees;
+        self
+    }
+
+    pub fn with_slippage(mut self, slippage: Slippage) -> Self {
+        self.slippage = slippage;
+        self
+    }
+
+    pub fn with_sizing(mut self, sizing: Sizing) -> Self {
+        self.sizing = sizing;
+        self
+    }
+
+    pub fn with_short(mut self, allow_short: bool) -> Self {
+        self.allow_short = allow_short;
+        self
+    }
+
+    pub fn with_close_at_end(mut self, close_at_end: bool) -> Self {
+        self.close_at_end = close_at_end;
+        self
+    }
+
+    /// Checks that capital, fees, slippage and sizing are finite and not negative, and that
+    /// the sizing opens a non-empty position.
+    pub fn validate(&self) -> TaResult<()> {
+        let invalid = |message: String| Err(StrategyError::Configuration(message).into());
+        let valid = |value: f64| value.is_finite() && value >= 0.0;
+        if !valid(self.initial_capital) || self.initial_capital == 0.0 {
+            return invalid(format!("initial capital {}", self.initial_capital));
+        }
+        if !valid(self.fees.percent) || !valid(self.fees.fixed) {
+            return invalid(format!("fees {:?}", self.fees));
+        }
+        let (Slippage::Percent(slippage) | Slippage::Fixed(slippage)) = self.slippage;
+        if !valid(slippage) {
+            return invalid(format!("slippage {:?}", self.slippage));
+        }
+        let (Sizing::Units(size) | Sizing::Cash(size) | Sizing::PercentOfEquity(size)) =
+            self.sizing;
+        if !valid(size) || size == 0.0 {
+            return invalid(format!("sizing {:?}", self.sizing));
+        }
+        Ok(())
+    }
+}
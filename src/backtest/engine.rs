@@ -0,0 +1,323 @@
+use chrono::{DateTime, Utc};
+
+use crate::{
+    backtest::{
+        TimedBar,
+        config::{BacktestConfig, EntryTiming},
+        report::{BacktestReport, Trade, TradeExit},
+    },
+    error::TaResult,
+    strategy::{Action, Side, StrategyNode, strat::Strategy},
+    traits::Candle,
+};
+
+/// Simulates the positions opened by a strategy over a series of bars.
+///
+/// Every bar is fed through `Strategy::evaluate` (or `evaluate_at` when it carries a
+/// timestamp) and the resulting actions are turned into positions the same way
+/// `ManagedStrategy` does: a non-Hold action opens a position when flat, an action pointing
+/// the other way closes it and actions in the direction of the open position are ignored.
+/// Bars of the warm-up period count as `Hold`. Conditions on the position are not supported,
+/// the strategy always sees a flat context.
+///
+/// Runs are deterministic: the same strategy, bars and configuration always give the same
+/// report.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Backtest {
+    pub config: BacktestConfig,
+}
+
+impl Backtest {
+    pub fn new(config: BacktestConfig) -> Self {
+        Self { config }
+    }
+
+    /// Runs a fresh `Strategy` built from `strategy` over `bars`.
+    pub fn run_node(&self, strategy: StrategyNode, bars: &[TimedBar]) -> TaResult<BacktestReport> {
+        self.run(&mut Strategy::new(strategy), bars)
+    }
+
+    /// Runs `strategy` over `bars`, continuing from whatever state it is in.
+    pub fn run(&self, strategy: &mut Strategy, bars: &[TimedBar]) -> TaResult<BacktestReport> {
+        self.config.validate()?;
+        let mut account = Account::new(self.config);
+        let mut equity = Vec::with_capacity(bars.len());
+        let mut pending = None;
+        for (index, bar) in bars.iter().enumerate() {
+            if let Some(action) = pending.take() {
+                account.fill(action, bar.data.open(), index, bar.timestamp);
+            }
+            let action = match bar.timestamp {
+                Some(timestamp) => strategy.evaluate_at(&bar.data, timestamp)?,
+                None => strategy.evaluate(&bar.data)?,
+            }
+            .unwrap_or(Action::Hold);
+            if action != Action::Hold {
+                match self.config.entry {
+                    EntryTiming::SameClose => {
+                        account.fill(action, bar.data.close(), index, bar.timestamp)
+                    }
+                    EntryTiming::NextOpen => pending = Some(action),
+                }
+            }
+            equity.push(account.equity(bar.data.close()));
+        }
+
+        let open_at_end = self.config.close_at_end && account.open.is_some();
+        if let Some(last) = bars.last().filter(|_| open_at_end) {
+            account.close(
+                last.data.close(),
+                bars.len() - 1,
+                last.timestamp,
+                TradeExit::EndOfData,
+            );
+            if let Some(point) = equity.last_mut() {
+                *point = account.cash;
+            }
+        }
+        let final_equity = match bars.last() {
+            Some(last) => account.equity(last.data.close()),
+            None => account.cash,
+        };
+        Ok(BacktestReport {
+            initial_capital: self.config.initial_capital,
+            final_equity,
+            total_fees: account.fees,
+            trades: account.trades,
+            equity,
+        })
+    }
+}
+
+/// Position opened by the simulation and not closed yet.
+struct Open {
+    side: Side,
+    index: usize,
+    time: Option<DateTime<Utc>>,
+    price: f64,
+    quantity: f64,
+    fee: f64,
+}
+
+struct Account {
+    config: BacktestConfig,
+    cash: f64,
+    fees: f64,
+    open: Option<Open>,
+    trades: Vec<Trade>,
+}
+
+impl Account {
+    fn new(config: BacktestConfig) -> Self {
+        Self {
+            config,
+            cash: config.initial_capital,
+            fees: 0.0,
+            open: None,
+            trades: Vec::new(),
+        }
+    }
+
+    /// Equity with the open position marked to `price`.
+    fn equity(&self, price: f64) -> f64 {
+        match &self.open {
+            Some(open) if open.side == Side::Short => self.cash - open.quantity * price,
+            Some(open) => self.cash + open.quantity * price,
+            None => self.cash,
+        }
+    }
+
+    /// Applies `action` at the reference `price`.
+    fn fill(&mut self, action: Action, price: f64, index: usize, time: Option<DateTime<Utc>>) {
+        let side = Side::of(action);
+        match &self.open {
+            Some(open) if side != Side::Flat && side != open.side => {
+                self.close(price, index, time, TradeExit::Signal)
+            }
+            None if side == Side::Long || (side == Side::Short && self.config.allow_short) => {
+                self.enter(side, price, index, time)
+            }
+            _ => {}
+        }
+    }
+
+    fn enter(&mut self, side: Side, price: f64, index: usize, time: Option<DateTime<Utc>>) {
+        let buy = side == Side::Long;
+        let price = self.config.slippage.apply(price, buy);
+        let quantity = self.config.sizing.quantity(price, self.cash);
+        if !(quantity.is_finite() && quantity > 0.0) {
+            return;
+        }
+        let fee = self.config.fees.charge(price * quantity);
+        self.fees += fee;
+        if buy {
+            self.cash -= price * quantity + fee;
+        } else {
+            self.cash += price * quantity - fee;
+        }
+        self.open = Some(Open {
+            side,
+            index,
+            time,
+            price,
+            quantity,
+            fee,
+        });
+    }
+
+    fn close(&mut self, price: f64, index: usize, time: Option<DateTime<Utc>>, exit: TradeExit) {
+        let Some(open) = self.open.take() else {
+            return;
+        };
+        let buy = open.side == Side::Short;
+        let price = self.config.slippage.apply(price, buy);
+        let fee = self.config.fees.charge(price * open.quantity);
+        self.fees += fee;
+        if buy {
+            self.cash -= price * open.quantity + fee;
+        } else {
+            self.cash += price * open.quantity - fee;
+        }
+        let mut trade = Trade {
+            side: open.side,
+            entry_index: open.index,
+            entry_time: open.time,
+            entry_price: open.price,
+            exit_index: index,
+            exit_time: time,
+            exit_price: price,
+            quantity: open.quantity,
+            fees: open.fee + fee,
+            pnl: 0.0,
+            exit,
+        };
+        trade.pnl = trade.gross_pnl() - trade.fees;
+        self.trades.push(trade);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(not(feature = "js"))]
+    use crate::Indicator;
+    #[cfg(feature = "js")]
+    use crate::indicators::indicator::Indicator;
+
+    use super::*;
+    use crate::backtest::{Fees, Sizing, Slippage};
+    use crate::error::TaError;
+    use crate::helper_types::Bar;
+    use crate::strategy::{Condition, MarketData, StrategyError};
+    use crate::types::OutputType;
+
+    /// Buys above 100 and sells below 90.
+    fn breakout() -> TaResult<StrategyNode> {
+        Ok(StrategyNode::If {
+            condition: Condition::greater_than(Indicator::sma(1)?, OutputType::Single(100.0)),
+            then_branch: Box::new(StrategyNode::Action(Action::Buy)),
+            else_branch: Some(Box::new(StrategyNode::If {
+                condition: Condition::less_than(Indicator::sma(1)?, OutputType::Single(90.0)),
+                then_branch: Box::new(StrategyNode::Action(Action::Sell)),
+                else_branch: Some(Box::new(StrategyNode::Action(Action::Hold))),
+            })),
+        })
+    }
+
+    /// Bars opening one point above the previous close.
+    fn bars(closes: &[f64]) -> Vec<TimedBar> {
+        let mut previous = closes[0];
+        closes
+            .iter()
+            .map(|&close| {
+                let bar = Bar::new()
+                    .set_open(previous + 1.0)
+                    .set_high(close.max(previous + 1.0))
+                    .set_low(close.min(previous + 1.0))
+                    .set_close(close);
+                previous = close;
+                TimedBar::from(MarketData::Bar(bar))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_same_close_entry() -> TaResult<()> {
+        let report =
+            Backtest::default().run_node(breakout()?, &bars(&[95.0, 101.0, 105.0, 89.0, 92.0]))?;
+        assert_eq!(report.trades.len(), 1);
+        let trade = &report.trades[0];
+        assert_eq!(trade.side, Side::Long);
+        assert_eq!((trade.entry_index, trade.exit_index), (1, 3));
+        assert_eq!((trade.entry_price, trade.exit_price), (101.0, 89.0));
+        assert_eq!(trade.exit, TradeExit::Signal);
+        assert_eq!(report.equity.len(), 5);
+        assert!((report.equity[2] - 10_000.0 * 105.0 / 101.0).abs() < 1e-9);
+        assert!((report.final_equity - 10_000.0 * 89.0 / 101.0).abs() < 1e-9);
+        assert_eq!(report.equity[4], report.final_equity);
+        Ok(())
+    }
+
+    #[test]
+    fn test_next_open_entry() -> TaResult<()> {
+        let config = BacktestConfig::default().with_entry(EntryTiming::NextOpen);
+        let report = Backtest::new(config)
+            .run_node(breakout()?, &bars(&[95.0, 101.0, 105.0, 89.0, 92.0]))?;
+        let trade = &report.trades[0];
+        assert_eq!((trade.entry_index, trade.exit_index), (2, 4));
+        assert_eq!((trade.entry_price, trade.exit_price), (102.0, 90.0));
+        // The signal bar itself is still flat.
+        assert_eq!(report.equity[1], 10_000.0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_fees_slippage_and_sizing() -> TaResult<()> {
+        let config = BacktestConfig::default()
+            .with_sizing(Sizing::Units(10.0))
+            .with_fees(Fees::new(0.1, 1.0))
+            .with_slippage(Slippage::Fixed(0.5));
+        let report = Backtest::new(config).run_node(breakout()?, &bars(&[95.0, 101.0, 89.0]))?;
+        let trade = &report.trades[0];
+        assert_eq!((trade.entry_price, trade.exit_price), (101.5, 88.5));
+        let fees = 1015.0 * 0.001 + 1.0 + 885.0 * 0.001 + 1.0;
+        assert!((trade.fees - fees).abs() < 1e-9);
+        assert!((trade.pnl - (-130.0 - fees)).abs() < 1e-9);
+        assert!((report.total_fees - fees).abs() < 1e-9);
+        assert!((report.final_equity - (10_000.0 + trade.pnl)).abs() < 1e-9);
+        Ok(())
+    }
+
+    #[test]
+    fn test_short_closed_at_end() -> TaResult<()> {
+        let series = bars(&[95.0, 89.0, 85.0]);
+        let report = Backtest::default().run_node(breakout()?, &series)?;
+        let trade = &report.trades[0];
+        assert_eq!(trade.side, Side::Short);
+        assert_eq!(trade.exit, TradeExit::EndOfData);
+        assert!((trade.pnl - 4.0 * 10_000.0 / 89.0).abs() < 1e-9);
+        assert!((report.final_equity - 10_000.0 - trade.pnl).abs() < 1e-9);
+
+        let long_only = BacktestConfig::default().with_short(false);
+        let report = Backtest::new(long_only).run_node(breakout()?, &series)?;
+        assert!(report.trades.is_empty());
+        assert_eq!(report.equity, vec![10_000.0; 3]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_deterministic_and_validated() -> TaResult<()> {
+        let series = bars(&[95.0, 101.0, 85.0, 103.0, 99.0, 88.0, 104.0]);
+        let config = BacktestConfig::new(1_000.0).with_fees(Fees::new(0.05, 0.0));
+        let first = Backtest::new(config).run_node(breakout()?, &series)?;
+        let second = Backtest::new(config).run_node(breakout()?, &series)?;
+        assert_eq!(first, second);
+        assert_eq!(first.trades.len(), 3);
+
+        let invalid = BacktestConfig::default().with_sizing(Sizing::Cash(-1.0));
+        assert!(matches!(
+            Backtest::new(invalid).run_node(breakout()?, &series),
+            Err(TaError::Strategy(StrategyError::Configuration(_)))
+        ));
+        Ok(())
+    }
+}
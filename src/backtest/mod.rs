@@ -0,0 +1,259 @@
+//! Offline simulation of strategies over historical bars.
+//!
+//! A `Backtest` feeds a series of `TimedBar`s through a `Strategy`, turns its actions into
+//! simulated positions according to a `BacktestConfig` and returns a `BacktestReport` with the
+//! closed trades and the equity curve. `BinaryBacktest` instead settles fixed-stake binary
+//! options opened by the strategy at a fixed expiry. `MonteCarlo` resamples the trades of a
+//! report to show how much of its result is down to luck.
+
+pub mod binary;
+pub mod config;
+pub mod engine;
+pub mod monte_carlo;
+pub mod report;
+
+use chrono::{DateTime, Utc};
+
+use crate::{
+    error::TaResult,
+    helper_types::Bar,
+    strategy::{MarketData, StrategyError},
+    traits::Candle,
+};
+
+pub use binary::{
+    BinaryAssetReport, BinaryBacktest, BinaryConfig, BinaryOption, BinaryReport, BinaryStats,
+    Outcome, Tie,
+};
+pub use config::{BacktestConfig, EntryTiming, Fees, Sizing, Slippage};
+pub use engine::Backtest;
+pub use monte_carlo::{
+    Interval, MonteCarlo, MonteCarloConfig, MonteCarloReport, Resampling, Simulation,
+};
+pub use report::{BacktestReport, Trade, TradeExit};
+
+/// A bar of market data with the time it closed at, if known.
+///
+/// Time based conditions of the strategy need the timestamp; bars without one are evaluated
+/// with `Strategy::evaluate`.
+#[derive(Debug, Clone)]
+pub struct TimedBar {
+    pub timestamp: Option<DateTime<Utc>>,
+    pub data: MarketData,
+}
+
+impl TimedBar {
+    pub fn new(data: MarketData) -> Self {
+        Self {
+            timestamp: None,
+            data,
+        }
+    }
+
+    /// Creates a bar closed at `timestamp`.
+    pub fn at(timestamp: DateTime<Utc>, data: MarketData) -> Self {
+        Self {
+            timestamp: Some(timestamp),
+            data,
+        }
+    }
+
+    /// Copies the OHLCV values of any `Candle`.
+    pub fn from_candle<C: Candle>(candle: &C) -> Self {
+        Self::new(MarketData::Bar(
+            Bar::new()
+                .set_open(candle.open())
+                .set_high(candle.high())
+                .set_low(candle.low())
+                .set_close(candle.close())
+                .set_price(candle.price())
+                .set_volume(candle.volume()),
+        ))
+    }
+
+    /// Builds a series from candles and optional Unix timestamps in seconds, one per candle.
+    pub fn series<C: Candle>(candles: &[C], timestamps: Option<&[i64]>) -> TaResult<Vec<Self>> {
+        let Some(timestamps) = timestamps else {
+            return Ok(candles.iter().map(Self::from_candle).collect());
+        };
+        if timestamps.len() != candles.len() {
+            return Err(StrategyError::Configuration(format!(
+                "{} timestamps for {} candles",
+                timestamps.len(),
+                candles.len()
+            ))
+            .into());
+        }
+        candles
+            .iter()
+            .zip(timestamps)
+            .map(|(candle, &seconds)| {
+                let timestamp = DateTime::from_timestamp(seconds, 0).ok_or_else(|| {
+                    StrategyError::Configuration(format!("timestamp {seconds} out of range"))
+                })?;
+                Ok(Self {
+                    timestamp: Some(timestamp),
+                    ..Self::from_candle(candle)
+                })
+            })
+            .collect()
+    }
+}
+
+impl From<MarketData> for TimedBar {
+    fn from(data: MarketData) -> Self {
+        Self::new(data)
+    }
+}
+
+#[cfg(feature = "js")]
+pub mod js {
+    use std::collections::{BTreeMap, HashMap};
+
+    use napi::bindgen_prelude::*;
+    use napi_derive::napi;
+
+    use super::{Backtest, BacktestConfig, BinaryBacktest, BinaryConfig, TimedBar};
+    use crate::{indicators::js::Candle, strategy::StrategyFile};
+
+    /// Runs a strategy over a series of candles and returns the backtest report.
+    ///
+    /// # Arguments
+    /// * `strategy` - Strategy tree, bare or wrapped in a versioned strategy file
+    /// * `candles` - Bars in chronological order
+    /// * `config` - Backtest settings, missing fields take their defaults
+    /// * `timestamps` - Unix timestamps in seconds, one per candle
+    ///
+    /// # Example
+    /// ```javascript
+    /// const report = backtest(strategy, candles, { fees: { percent: 0.1 } });
+    /// console.log(report.final_equity, report.trades.length);
+    /// ```
+    #[napi]
+    pub fn backtest<'env>(
+        env: Env,
+        strategy: Unknown<'env>,
+        candles: Vec<&Candle>,
+        config: Option<Unknown<'env>>,
+        timestamps: Option<Vec<i64>>,
+    ) -> napi::Result<Unknown<'env>> {
+        let strategy = StrategyFile::from_value(env.from_js_value(strategy)?)?;
+        let config: BacktestConfig = match config {
+            Some(config) => env.from_js_value(config)?,
+            None => BacktestConfig::default(),
+        };
+        let candles: Vec<Candle> = candles.into_iter().cloned().collect();
+        let bars = TimedBar::series(&candles, timestamps.as_deref())?;
+        let report = Backtest::new(config).run_node(strategy.into_strategy(), &bars)?;
+        env.to_js_value(&report)
+    }
+
+    /// Simulates fixed-stake binary options on several assets and returns the report.
+    ///
+    /// # Arguments
+    /// * `strategy` - Strategy tree, bare or wrapped in a versioned strategy file
+    /// * `assets` - Object mapping every asset to its candles in chronological order
+    /// * `config` - Binary options settings, missing fields take their defaults
+    /// * `timestamps` - Object mapping assets to Unix timestamps in seconds, one per candle
+    ///
+    /// # Example
+    /// ```javascript
+    /// const report = backtestBinary(strategy, { EURUSD: candles }, { payout: 85, expiry: { Bars: 3 } });
+    /// console.log(report.assets.EURUSD.stats.win_rate);
+    /// ```
+    #[napi]
+    pub fn backtest_binary<'env>(
+        env: Env,
+        strategy: Unknown<'env>,
+        assets: Unknown<'env>,
+        config: Option<Unknown<'env>>,
+        timestamps: Option<HashMap<String, Vec<i64>>>,
+    ) -> napi::Result<Unknown<'env>> {
+        let strategy = StrategyFile::from_value(env.from_js_value(strategy)?)?;
+        let assets: BTreeMap<String, Vec<Candle>> = env.from_js_value(assets)?;
+        let config: BinaryConfig = match config {
+            Some(config) => env.from_js_value(config)?,
+            None => BinaryConfig::default(),
+        };
+        let mut series = BTreeMap::new();
+        for (asset, candles) in assets {
+            let times = timestamps
+                .as_ref()
+                .and_then(|timestamps| timestamps.get(&asset));
+            let bars = TimedBar::series(&candles, times.map(Vec::as_slice))?;
+            series.insert(asset, bars);
+        }
+        let report = BinaryBacktest::new(config).run_assets(&strategy.into_strategy(), &series)?;
+        env.to_js_value(&report)
+    }
+}
+
+#[cfg(feature = "py")]
+pub mod py {
+    use std::collections::{BTreeMap, HashMap};
+
+    use pyo3::{PyResult, exceptions::PyValueError, pyfunction};
+    use pyo3_stub_gen::derive::gen_stub_pyfunction;
+
+    use super::{Backtest, BacktestConfig, BinaryBacktest, BinaryConfig, TimedBar};
+    use crate::{indicators::py::Candle, strategy::StrategyFile};
+
+    /// Runs the strategy given as JSON over `candles` and returns the report as JSON.
+    ///
+    /// The strategy may be a bare tree or a versioned strategy file. `config` is a JSON
+    /// `BacktestConfig` whose missing fields take their defaults and `timestamps` are Unix
+    /// timestamps in seconds, one per candle.
+    #[gen_stub_pyfunction]
+    #[pyfunction]
+    #[pyo3(signature = (strategy, candles, config=None, timestamps=None))]
+    pub fn backtest(
+        strategy: String,
+        candles: Vec<Candle>,
+        config: Option<String>,
+        timestamps: Option<Vec<i64>>,
+    ) -> PyResult<String> {
+        let strategy = StrategyFile::from_json(&strategy)?;
+        let config: BacktestConfig = match config {
+            Some(config) => {
+                serde_json::from_str(&config).map_err(|e| PyValueError::new_err(e.to_string()))?
+            }
+            None => BacktestConfig::default(),
+        };
+        let bars = TimedBar::series(&candles, timestamps.as_deref())?;
+        let report = Backtest::new(config).run_node(strategy.into_strategy(), &bars)?;
+        serde_json::to_string(&report).map_err(|e| PyValueError::new_err(e.to_string()))
+    }
+
+    /// Simulates fixed-stake binary options on several assets and returns the report as JSON.
+    ///
+    /// `assets` maps every asset to its candles and `timestamps` optionally maps assets to Unix
+    /// timestamps in seconds, one per candle. `config` is a JSON `BinaryConfig` whose missing
+    /// fields take their defaults.
+    #[gen_stub_pyfunction]
+    #[pyfunction]
+    #[pyo3(signature = (strategy, assets, config=None, timestamps=None))]
+    pub fn backtest_binary(
+        strategy: String,
+        assets: HashMap<String, Vec<Candle>>,
+        config: Option<String>,
+        timestamps: Option<HashMap<String, Vec<i64>>>,
+    ) -> PyResult<String> {
+        let strategy = StrategyFile::from_json(&strategy)?;
+        let config: BinaryConfig = match config {
+            Some(config) => {
+                serde_json::from_str(&config).map_err(|e| PyValueError::new_err(e.to_string()))?
+            }
+            None => BinaryConfig::default(),
+        };
+        let mut series = BTreeMap::new();
+        for (asset, candles) in assets {
+            let times = timestamps
+                .as_ref()
+                .and_then(|timestamps| timestamps.get(&asset));
+            let bars = TimedBar::series(&candles, times.map(Vec::as_slice))?;
+            series.insert(asset, bars);
+        }
+        let report = BinaryBacktest::new(config).run_assets(&strategy.into_strategy(), &series)?;
+        serde_json::to_string(&report).map_err(|e| PyValueError::new_err(e.to_string()))
+    }
+}
@@ -0,0 +1,423 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    backtest::{BacktestReport, Trade},
+    error::TaResult,
+    metrics::max_drawdown,
+    random::Rng,
+    strategy::{Side, StrategyError},
+};
+
+/// How the trades of every simulation are drawn from the trades of the backtest.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Resampling {
+    /// Keep the original order, so only skipping and slippage noise change the runs.
+    Keep,
+    /// Shuffle the order of the trades. The final equity only changes through skipping and
+    /// slippage noise, but drawdowns depend on the order.
+    #[default]
+    Shuffle,
+    /// Draw as many trades as the backtest made, with replacement.
+    Bootstrap,
+}
+
+/// Settings of a `MonteCarlo` analysis.
+///
+/// Every field has a default, so partial JSON objects deserialize into a complete
+/// configuration.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct MonteCarloConfig {
+    pub simulations: usize,
+    pub resampling: Resampling,
+    /// Percentage of chance that each trade is skipped, as if the signal had been missed.
+    pub skip: f64,
+    /// Standard deviation of the entry slippage noise, as a percentage of the entry price.
+    /// The noise is always applied against the trader.
+    pub slippage: f64,
+    /// Percentage of the initial capital whose loss counts as ruin.
+    pub ruin: f64,
+    /// Probability covered by the confidence intervals, such as `0.95`.
+    pub confidence: f64,
+    pub seed: u64,
+}
+
+impl Default for MonteCarloConfig {
+    fn default() -> Self {
+        Self {
+            simulations: 1000,
+            resampling: Resampling::default(),
+            skip: 0.0,
+            slippage: 0.0,
+            ruin: 50.0,
+            confidence: 0.95,
+            seed: 0,
+        }
+    }
+}
+
+impl MonteCarloConfig {
+    pub fn new(simulations: usize, seed: u64) -> Self {
+        Self {
+            simulations,
+            seed,
+            ..Self::default()
+        }
+    }
+
+    pub fn with_resampling(mut self, resampling: Resampling) -> Self {
+        self.resampling = resampling;
+        self
+    }
+
+    pub fn with_skip(mut self, skip: f64) -> Self {
+        self.skip = skip;
+        self
+    }
+
+    pub fn with_slippage(mut self, slippage: f64) -> Self {
+        self.slippage = slippage;
+        self
+    }
+
+    pub fn with_ruin(mut self, ruin: f64) -> Self {
+        self.ruin = ruin;
+        self
+    }
+
+    pub fn with_confidence(mut self, confidence: f64) -> Self {
+        self.confidence = confidence;
+        self
+    }
+
+    pub fn validate(&self) -> TaResult<()> {
+        if self.simulations == 0 {
+            return Err(StrategyError::Configuration("no simulations".into()).into());
+        }
+        if !(0.0..=100.0).contains(&self.skip) {
+            return Err(StrategyError::Configuration(format!("skip of {}%", self.skip)).into());
+        }
+        if !(self.slippage.is_finite() && self.slippage >= 0.0) {
+            return Err(StrategyError::Configuration(format!(
+                "slippage noise of {}%",
+                self.slippage
+            ))
+            .into());
+        }
+        if !(self.ruin > 0.0 && self.ruin <= 100.0) {
+            return Err(StrategyError::Configuration(format!("ruin at {}%", self.ruin)).into());
+        }
+        if !(self.confidence > 0.0 && self.confidence < 1.0) {
+            return Err(
+                StrategyError::Configuration(format!("confidence of {}", self.confidence)).into(),
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Outcome of one simulated sequence of trades.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct Simulation {
+    pub final_equity: f64,
+    /// Deepest decline of the equity from a previous peak, as a fraction, measured between
+    /// trades.
+    pub max_drawdown: f64,
+    /// Whether the equity fell to the ruin level at any point.
+    pub ruined: bool,
+    /// Number of trades taken, after skipping.
+    pub trades: usize,
+}
+
+/// Distribution of a value over the simulations.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct Interval {
+    pub mean: f64,
+    /// Lower bound of the confidence interval.
+    pub lower: f64,
+    pub median: f64,
+    /// Upper bound of the confidence interval.
+    pub upper: f64,
+}
+
+impl Interval {
+    /// Summarizes `values`, the central `confidence` of which lies between `lower` and
+    /// `upper`. Percentiles are interpolated linearly.
+    pub fn new(values: &[f64], confidence: f64) -> Self {
+        let mut sorted = values.to_vec();
+        sorted.sort_by(f64::total_cmp);
+        let tail = (1.0 - confidence) / 2.0;
+        Self {
+            mean: sorted.iter().sum::<f64>() / sorted.len() as f64,
+            lower: percentile(&sorted, tail),
+            median: percentile(&sorted, 0.5),
+            upper: percentile(&sorted, 1.0 - tail),
+        }
+    }
+
+    pub fn contains(&self, value: f64) -> bool {
+        (self.lower..=self.upper).contains(&value)
+    }
+}
+
+/// Percentile `p`, between 0 and 1, of sorted values. `NaN` without values.
+fn percentile(sorted: &[f64], p: f64) -> f64 {
+    if sorted.is_empty() {
+        return f64::NAN;
+    }
+    let rank = p * (sorted.len() - 1) as f64;
+    let (below, above) = (rank.floor() as usize, rank.ceil() as usize);
+    sorted[below] + (sorted[above] - sorted[below]) * (rank - below as f64)
+}
+
+/// Result of a `MonteCarlo` analysis.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MonteCarloReport {
+    pub initial_capital: f64,
+    pub confidence: f64,
+    pub final_equity: Interval,
+    pub max_drawdown: Interval,
+    /// Fraction of the simulations that were ruined.
+    pub risk_of_ruin: f64,
+    /// Every simulation, in the order they were run.
+    pub simulations: Vec<Simulation>,
+}
+
+/// Monte Carlo analysis of the trades of a backtest.
+///
+/// Every trade is turned into a return on the equity it was opened with, so that resampled
+/// sequences compound like the original run. Each simulation then draws its trades according
+/// to the resampling, skips some of them and worsens their entry price by slippage noise
+/// before replaying them from the initial capital. Equity is floored at zero.
+///
+/// Simulations draw their randomness from generators forked from the seed in order, so the
+/// same configuration always gives the same report.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct MonteCarlo {
+    pub config: MonteCarloConfig,
+}
+
+impl MonteCarlo {
+    pub fn new(config: MonteCarloConfig) -> Self {
+        Self { config }
+    }
+
+    /// Simulates the trades of a backtest report.
+    pub fn run(&self, report: &BacktestReport) -> TaResult<MonteCarloReport> {
+        self.run_trades(report.initial_capital, &report.trades)
+    }
+
+    /// Simulates `trades`, closed one after the other by a run that started with
+    /// `initial_capital`.
+    pub fn run_trades(&self, initial_capital: f64, trades: &[Trade]) -> TaResult<MonteCarloReport> {
+        self.config.validate()?;
+        if !(initial_capital.is_finite() && initial_capital > 0.0) {
+            return Err(StrategyError::Configuration(format!(
+                "initial capital of {initial_capital}"
+            ))
+            .into());
+        }
+        let mut equity = initial_capital;
+        let samples: Vec<Sample> = trades
+            .iter()
+            .map(|trade| {
+                let sample = Sample::new(trade, equity);
+                equity += trade.pnl;
+                sample
+            })
+            .collect();
+
+        let mut rng = Rng::new(self.config.seed);
+        let simulations: Vec<Simulation> = (0..self.config.simulations)
+            .map(|_| self.simulate(initial_capital, &samples, &mut rng.fork()))
+            .collect();
+        let values =
+            |value: fn(&Simulation) -> f64| -> Vec<f64> { simulations.iter().map(value).collect() };
+        let confidence = self.config.confidence;
+        Ok(MonteCarloReport {
+            initial_capital,
+            confidence,
+            final_equity: Interval::new(&values(|s| s.final_equity), confidence),
+            max_drawdown: Interval::new(&values(|s| s.max_drawdown), confidence),
+            risk_of_ruin: simulations.iter().filter(|s| s.ruined).count() as f64
+                / simulations.len() as f64,
+            simulations,
+        })
+    }
+
+    fn simulate(&self, initial_capital: f64, samples: &[Sample], rng: &mut Rng) -> Simulation {
+        let order: Vec<usize> = match self.config.resampling {
+            Resampling::Keep => (0..samples.len()).collect(),
+            Resampling::Shuffle => {
+                let mut order: Vec<usize> = (0..samples.len()).collect();
+                rng.shuffle(&mut order);
+                order
+            }
+            Resampling::Bootstrap => (0..samples.len())
+                .map(|_| rng.below(samples.len()))
+                .collect(),
+        };
+        let ruin = initial_capital * (1.0 - self.config.ruin / 100.0);
+        let mut curve = vec![initial_capital];
+        let mut equity = initial_capital;
+        let mut ruined = false;
+        for index in order {
+            if rng.chance(self.config.skip / 100.0) {
+                continue;
+            }
+            let noise = (rng.normal() * self.config.slippage).abs();
+            equity = (equity * (1.0 + samples[index].ret(noise))).max(0.0);
+            ruined |= equity <= ruin;
+            curve.push(equity);
+        }
+        Simulation {
+            final_equity: equity,
+            max_drawdown: max_drawdown(&curve).depth,
+            ruined,
+            trades: curve.len() - 1,
+        }
+    }
+}
+
+/// A trade as a return on the equity it was opened with.
+#[derive(Debug, Clone, Copy)]
+struct Sample {
+    ret: f64,
+    /// Return lost for every percent of adverse entry slippage.
+    slippage_cost: f64,
+}
+
+impl Sample {
+    fn new(trade: &Trade, equity: f64) -> Self {
+        let notional = (trade.entry_price * trade.quantity).abs();
+        let cost = match trade.side {
+            Side::Flat => 0.0,
+            _ => notional / 100.0 / equity,
+        };
+        Self {
+            ret: trade.pnl / equity,
+            slippage_cost: cost,
+        }
+    }
+
+    /// Return of the trade with `noise` percent of extra entry slippage.
+    fn ret(&self, noise: f64) -> f64 {
+        self.ret - self.slippage_cost * noise
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backtest::TradeExit;
+
+    fn trade(pnl: f64) -> Trade {
+        Trade {
+            side: Side::Long,
+            entry_index: 0,
+            entry_time: None,
+            entry_price: 100.0,
+            exit_index: 1,
+            exit_time: None,
+            exit_price: 100.0 + pnl / 10.0,
+            quantity: 10.0,
+            fees: 0.0,
+            pnl,
+            exit: TradeExit::Signal,
+        }
+    }
+
+    fn trades() -> Vec<Trade> {
+        [
+            120.0, -80.0, 200.0, -150.0, 60.0, -40.0, 90.0, -110.0, 150.0, 30.0,
+        ]
+        .into_iter()
+        .map(trade)
+        .collect()
+    }
+
+    #[test]
+    fn test_shuffle_keeps_final_equity() -> TaResult<()> {
+        let trades = trades();
+        let original = 1000.0 + trades.iter().map(|t| t.pnl).sum::<f64>();
+        let report = MonteCarlo::new(MonteCarloConfig::new(200, 3)).run_trades(1000.0, &trades)?;
+        assert_eq!(report.simulations.len(), 200);
+        assert!(
+            report
+                .simulations
+                .iter()
+                .all(|s| (s.final_equity - original).abs() < 1e-9 && s.trades == 10)
+        );
+        // The order of the trades changes the drawdowns.
+        assert!(report.max_drawdown.lower < report.max_drawdown.upper);
+        assert!(report.max_drawdown.lower <= report.max_drawdown.median);
+
+        let kept = MonteCarlo::new(MonteCarloConfig::new(5, 3).with_resampling(Resampling::Keep))
+            .run_trades(1000.0, &trades)?;
+        let mut curve = vec![1000.0];
+        for trade in &trades {
+            curve.push(curve.last().unwrap() + trade.pnl);
+        }
+        assert!((kept.max_drawdown.mean - max_drawdown(&curve).depth).abs() < 1e-12);
+        assert_eq!(kept.max_drawdown.lower, kept.max_drawdown.upper);
+        Ok(())
+    }
+
+    #[test]
+    fn test_reproducible() -> TaResult<()> {
+        let trades = trades();
+        let config = MonteCarloConfig::new(100, 9)
+            .with_resampling(Resampling::Bootstrap)
+            .with_skip(20.0)
+            .with_slippage(0.5);
+        let first = MonteCarlo::new(config).run_trades(1000.0, &trades)?;
+        assert_eq!(MonteCarlo::new(config).run_trades(1000.0, &trades)?, first);
+        let other = MonteCarlo::new(MonteCarloConfig { seed: 10, ..config });
+        assert_ne!(other.run_trades(1000.0, &trades)?, first);
+        assert!(first.final_equity.lower < first.final_equity.upper);
+        Ok(())
+    }
+
+    #[test]
+    fn test_skip_slippage_and_ruin() -> TaResult<()> {
+        let trades = trades();
+        let skipped = MonteCarlo::new(MonteCarloConfig::new(10, 1).with_skip(100.0))
+            .run_trades(1000.0, &trades)?;
+        assert_eq!(skipped.final_equity.upper, 1000.0);
+        assert!(skipped.simulations.iter().all(|s| s.trades == 0));
+
+        let clean = MonteCarlo::new(MonteCarloConfig::new(50, 1)).run_trades(1000.0, &trades)?;
+        let noisy = MonteCarlo::new(MonteCarloConfig::new(50, 1).with_slippage(1.0))
+            .run_trades(1000.0, &trades)?;
+        assert!(noisy.final_equity.mean < clean.final_equity.mean);
+        assert!(
+            noisy
+                .simulations
+                .iter()
+                .all(|s| s.final_equity <= clean.final_equity.upper + 1e-9)
+        );
+
+        assert_eq!(clean.risk_of_ruin, 0.0);
+        let losers: Vec<Trade> = (0..5).map(|_| trade(-100.0)).collect();
+        let ruined = MonteCarlo::new(MonteCarloConfig::new(20, 1).with_ruin(40.0))
+            .run_trades(1000.0, &losers)?;
+        assert_eq!(ruined.risk_of_ruin, 1.0);
+        assert!((ruined.max_drawdown.median - 0.5).abs() < 1e-12);
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_config() {
+        let trades = trades();
+        for config in [
+            MonteCarloConfig::new(0, 1),
+            MonteCarloConfig::default().with_skip(120.0),
+            MonteCarloConfig::default().with_slippage(-1.0),
+            MonteCarloConfig::default().with_ruin(0.0),
+            MonteCarloConfig::default().with_confidence(1.0),
+        ] {
+            assert!(MonteCarlo::new(config).run_trades(1000.0, &trades).is_err());
+        }
+        assert!(MonteCarlo::default().run_trades(0.0, &trades).is_err());
+    }
+}
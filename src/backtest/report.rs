@@ -0,0 +1,102 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    metrics::{Metrics, TradeRecord},
+    strategy::Side,
+};
+
+/// Why a simulated position was closed.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TradeExit {
+    /// The strategy produced an action on the opposite side.
+    Signal,
+    /// The position was still open after the last bar and was closed at its close.
+    EndOfData,
+}
+
+/// A closed simulated position.
+///
+/// Indices refer to the bars given to the backtest, prices are fill prices after slippage and
+/// `pnl` is net of the fees of both fills.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Trade {
+    pub side: Side,
+    pub entry_index: usize,
+    pub entry_time: Option<DateTime<Utc>>,
+    pub entry_price: f64,
+    pub exit_index: usize,
+    pub exit_time: Option<DateTime<Utc>>,
+    pub exit_price: f64,
+    pub quantity: f64,
+    /// Fees paid on entry and exit.
+    pub fees: f64,
+    pub pnl: f64,
+    pub exit: TradeExit,
+}
+
+impl Trade {
+    /// Profit before fees.
+    pub fn gross_pnl(&self) -> f64 {
+        match self.side {
+            Side::Short => (self.entry_price - self.exit_price) * self.quantity,
+            _ => (self.exit_price - self.entry_price) * self.quantity,
+        }
+    }
+
+    /// Net profit as a percentage of the notional value at entry.
+    pub fn return_pct(&self) -> f64 {
+        self.pnl / (self.entry_price * self.quantity).abs() * 100.0
+    }
+
+    /// Number of bars between entry and exit.
+    pub fn bars_held(&self) -> usize {
+        self.exit_index - self.entry_index
+    }
+
+    pub fn is_win(&self) -> bool {
+        self.pnl > 0.0
+    }
+}
+
+impl From<&Trade> for TradeRecord {
+    fn from(trade: &Trade) -> Self {
+        TradeRecord::new(trade.pnl, trade.bars_held())
+    }
+}
+
+/// Result of a `Backtest` run.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BacktestReport {
+    pub initial_capital: f64,
+    /// Equity after the last bar, including the close of any position left open.
+    pub final_equity: f64,
+    /// Fees paid on every fill, including those of a position left open.
+    pub total_fees: f64,
+    /// Closed positions in the order they were opened.
+    pub trades: Vec<Trade>,
+    /// Equity marked to the close of every bar, one entry per input bar.
+    pub equity: Vec<f64>,
+}
+
+impl BacktestReport {
+    /// Net profit of the run.
+    pub fn net_profit(&self) -> f64 {
+        self.final_equity - self.initial_capital
+    }
+
+    /// Net profit as a percentage of the initial capital.
+    pub fn return_pct(&self) -> f64 {
+        self.net_profit() / self.initial_capital * 100.0
+    }
+
+    /// Trades as seen by the metrics.
+    pub fn trade_records(&self) -> Vec<TradeRecord> {
+        self.trades.iter().map(TradeRecord::from).collect()
+    }
+
+    /// Computes the metrics of the run, given the number of bars in a year.
+    pub fn metrics(&self, periods_per_year: f64) -> Metrics {
+        Metrics::compute(&self.equity, &self.trade_records(), periods_per_year)
+    }
+}
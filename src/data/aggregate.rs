@@ -0,0 +1,533 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    data::{TimedCandle, invalid_data},
+    error::TaResult,
+    strategy::StrategyError,
+    traits::Reset,
+};
+
+/// Bricks a single tick may complete, beyond which the tick is rejected as a price gap.
+const MAX_BRICKS: f64 = 10_000.0;
+
+/// A trade, or a price update, of a live feed.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct Tick {
+    pub timestamp: DateTime<Utc>,
+    pub price: f64,
+    pub volume: f64,
+}
+
+impl Tick {
+    pub fn new(timestamp: DateTime<Utc>, price: f64, volume: f64) -> Self {
+        Self {
+            timestamp,
+            price,
+            volume,
+        }
+    }
+}
+
+/// When an `Aggregator` completes a bar.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum BarKind {
+    /// Bars covering this many seconds, aligned on the Unix epoch. The bar of a period without
+    /// ticks is missing, and a bar only completes when a tick of a later period arrives.
+    Time(u64),
+    /// Bars of this many ticks.
+    Ticks(usize),
+    /// Bars completed by the tick that brings their volume to at least this much.
+    Volume(f64),
+    /// Bars completed by the tick that widens their high-low range to at least this much.
+    Range(f64),
+    /// Renko bricks of this size. A brick in the direction of the previous one needs the price
+    /// to move one brick beyond it, and a reversal two bricks. A tick moving several bricks
+    /// completes all of them; the volume traded since the previous brick goes to the first.
+    /// Renko bricks have no forming bar. A tick moving more than 10000 bricks, or a size too
+    /// small to change the price, is rejected.
+    Renko(f64),
+}
+
+/// A bar produced by an `Aggregator`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum BarEvent {
+    Completed(TimedCandle),
+    /// The bar being built, as of the last tick. It is replaced by the next `Forming` or
+    /// `Completed` event.
+    Forming(TimedCandle),
+}
+
+impl BarEvent {
+    pub fn candle(&self) -> &TimedCandle {
+        match self {
+            BarEvent::Completed(candle) | BarEvent::Forming(candle) => candle,
+        }
+    }
+
+    pub fn is_completed(&self) -> bool {
+        matches!(self, BarEvent::Completed(_))
+    }
+}
+
+/// Turns candles into Heikin-Ashi candles, one at a time.
+///
+/// The close is the mean of the open, high, low and close, the open is the midpoint of the
+/// previous Heikin-Ashi candle, or of the open and close of the first candle, and the high
+/// and low are widened to enclose them. Timestamps and volumes are kept.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub struct HeikinAshi {
+    /// Open and close of the previous Heikin-Ashi candle.
+    previous: Option<(f64, f64)>,
+}
+
+impl HeikinAshi {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn next(&mut self, candle: &TimedCandle) -> TimedCandle {
+        let smoothed = self.peek(candle);
+        self.previous = Some((smoothed.open, smoothed.close));
+        smoothed
+    }
+
+    /// The Heikin-Ashi candle of `candle`, without moving on to the next candle.
+    pub fn peek(&self, candle: &TimedCandle) -> TimedCandle {
+        let close = (candle.open + candle.high + candle.low + candle.close) / 4.0;
+        let open = match self.previous {
+            Some((open, close)) => (open + close) / 2.0,
+            None => (candle.open + candle.close) / 2.0,
+        };
+        TimedCandle::new(
+            candle.timestamp,
+            open,
+            candle.high.max(open).max(close),
+            candle.low.min(open).min(close),
+            close,
+            candle.volume,
+        )
+    }
+}
+
+impl Reset for HeikinAshi {
+    fn reset(&mut self) {
+        self.previous = None;
+    }
+}
+
+/// Streaming aggregation of ticks into candles.
+///
+/// Every call to `next` returns the bars completed by the tick and, when `with_forming` is
+/// set, the bar being built after it. Candles carry the timestamp of their first tick, or of
+/// the start of their period for time bars, and implement `Candle`, so they can be fed to
+/// `Indicator::next` as is and to `Strategy::evaluate` through `MarketData::from`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Aggregator {
+    kind: BarKind,
+    forming: bool,
+    heikin_ashi: Option<HeikinAshi>,
+    bar: Option<TimedCandle>,
+    /// Ticks in `bar`.
+    ticks: usize,
+    /// Low and high of the last Renko brick, or the first price before the first brick.
+    brick: Option<(f64, f64)>,
+    /// Volume traded since the last Renko brick.
+    volume: f64,
+    /// Number of ticks received and timestamp of the last one.
+    received: usize,
+    last: Option<DateTime<Utc>>,
+}
+
+impl Aggregator {
+    pub fn new(kind: BarKind) -> TaResult<Self> {
+        let valid = match kind {
+            BarKind::Time(seconds) => {
+                seconds > 0 && i64::try_from(seconds).is_ok_and(|s| s.checked_mul(1000).is_some())
+            }
+            BarKind::Ticks(ticks) => ticks > 0,
+            BarKind::Volume(size) | BarKind::Range(size) | BarKind::Renko(size) => {
+                size.is_finite() && size > 0.0
+            }
+        };
+        if !valid {
+            return Err(StrategyError::Configuration(format!("bar kind {kind:?}")).into());
+        }
+        Ok(Self {
+            kind,
+            forming: false,
+            heikin_ashi: None,
+            bar: None,
+            ticks: 0,
+            brick: None,
+            volume: 0.0,
+            received: 0,
+            last: None,
+        })
+    }
+
+    /// Also emits the forming bar after every tick.
+    pub fn with_forming(mut self, forming: bool) -> Self {
+        self.forming = forming;
+        self
+    }
+
+    /// Emits Heikin-Ashi candles of the bars instead of the bars themselves.
+    pub fn with_heikin_ashi(mut self) -> Self {
+        self.heikin_ashi = Some(HeikinAshi::new());
+        self
+    }
+
+    pub fn kind(&self) -> BarKind {
+        self.kind
+    }
+
+    /// Adds a tick. Fails on ticks older than the previous one, with a non-finite price or
+    /// with a negative volume; the `line` of the error is the position of the tick in the
+    /// stream, counting from 1.
+    pub fn next(&mut self, tick: Tick) -> TaResult<Vec<BarEvent>> {
+        self.received += 1;
+        if !tick.price.is_finite() || tick.volume.is_nan() || tick.volume < 0.0 {
+            return Err(invalid_data(
+                self.received,
+                format!("invalid tick {tick:?}"),
+            ));
+        }
+        if self.last.is_some_and(|last| tick.timestamp < last) {
+            return Err(invalid_data(self.received, "tick out of order"));
+        }
+        self.last = Some(tick.timestamp);
+
+        let mut completed = Vec::new();
+        match self.kind {
+            BarKind::Time(seconds) => {
+                let period = seconds as i64 * 1000;
+                let millis = tick.timestamp.timestamp_millis();
+                let start = DateTime::from_timestamp_millis(millis - millis.rem_euclid(period))
+                    .unwrap_or(tick.timestamp);
+                if self.bar.is_some_and(|bar| bar.timestamp != start) {
+                    completed.extend(self.take());
+                }
+                self.add(tick, start);
+            }
+            BarKind::Ticks(ticks) => {
+                self.add(tick, tick.timestamp);
+                if self.ticks >= ticks {
+                    completed.extend(self.take());
+                }
+            }
+            BarKind::Volume(volume) => {
+                self.add(tick, tick.timestamp);
+                if self.bar.is_some_and(|bar| bar.volume >= volume) {
+                    completed.extend(self.take());
+                }
+            }
+            BarKind::Range(range) => {
+                self.add(tick, tick.timestamp);
+                if self.bar.is_some_and(|bar| bar.high - bar.low >= range) {
+                    completed.extend(self.take());
+                }
+            }
+            BarKind::Renko(size) => self.renko(tick, size, &mut completed)?,
+        }
+
+        let mut events: Vec<BarEvent> = completed
+            .into_iter()
+            .map(|bar| BarEvent::Completed(self.smooth(bar)))
+            .collect();
+        if let Some(bar) = self.forming_bar().filter(|_| self.forming) {
+            events.push(BarEvent::Forming(bar));
+        }
+        Ok(events)
+    }
+
+    /// The bar being built, if any.
+    pub fn forming_bar(&self) -> Option<TimedCandle> {
+        let bar = self.bar?;
+        Some(match &self.heikin_ashi {
+            Some(heikin_ashi) => heikin_ashi.peek(&bar),
+            None => bar,
+        })
+    }
+
+    /// Completes the bar being built, such as at the end of the data. Renko bricks are never
+    /// completed early.
+    pub fn flush(&mut self) -> Option<TimedCandle> {
+        let bar = self.take()?;
+        Some(self.smooth(bar))
+    }
+
+    /// Aggregates a whole series of ticks, including the last, incomplete, bar.
+    pub fn aggregate(
+        &mut self,
+        ticks: impl IntoIterator<Item = Tick>,
+    ) -> TaResult<Vec<TimedCandle>> {
+        let mut bars = Vec::new();
+        for tick in ticks {
+            bars.extend(
+                self.next(tick)?
+                    .into_iter()
+                    .filter(BarEvent::is_completed)
+                    .map(|event| *event.candle()),
+            );
+        }
+        bars.extend(self.flush());
+        Ok(bars)
+    }
+
+    fn add(&mut self, tick: Tick, start: DateTime<Utc>) {
+        self.ticks += 1;
+        let bar = self
+            .bar
+            .get_or_insert_with(|| TimedCandle::flat(start, tick.price));
+        bar.high = bar.high.max(tick.price);
+        bar.low = bar.low.min(tick.price);
+        bar.close = tick.price;
+        bar.volume += tick.volume;
+    }
+
+    fn take(&mut self) -> Option<TimedCandle> {
+        self.ticks = 0;
+        self.bar.take()
+    }
+
+    fn smooth(&mut self, bar: TimedCandle) -> TimedCandle {
+        match &mut self.heikin_ashi {
+            Some(heikin_ashi) => heikin_ashi.next(&bar),
+            None => bar,
+        }
+    }
+
+    fn renko(&mut self, tick: Tick, size: f64, completed: &mut Vec<TimedCandle>) -> TaResult<()> {
+        let (mut low, mut high) = self.brick.unwrap_or((tick.price, tick.price));
+        if high + size == high || low - size == low {
+            return Err(invalid_data(
+                self.received,
+                format!("renko size {size} is below the precision of price {high}"),
+            ));
+        }
+        if (tick.price - high).max(low - tick.price) / size > MAX_BRICKS {
+            return Err(invalid_data(
+                self.received,
+                format!("price {} is more than {MAX_BRICKS} bricks away", tick.price),
+            ));
+        }
+        self.volume += tick.volume;
+        self.brick = Some((low, high));
+        loop {
+            let (open, close) = if tick.price >= high + size {
+                (high, high + size)
+            } else if tick.price <= low - size {
+                (low, low - size)
+            } else {
+                break;
+            };
+            if open == close {
+                break;
+            }
+            (low, high) = (open.min(close), open.max(close));
+            let volume = std::mem::take(&mut self.volume);
+            completed.push(TimedCandle::new(
+                tick.timestamp,
+                open,
+                high,
+                low,
+                close,
+                volume,
+            ));
+        }
+        self.brick = Some((low, high));
+        Ok(())
+    }
+}
+
+impl Reset for Aggregator {
+    fn reset(&mut self) {
+        if let Some(heikin_ashi) = &mut self.heikin_ashi {
+            heikin_ashi.reset();
+        }
+        self.bar = None;
+        self.ticks = 0;
+        self.brick = None;
+        self.volume = 0.0;
+        self.received = 0;
+        self.last = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(not(feature = "js"))]
+    use crate::Indicator;
+    #[cfg(feature = "js")]
+    use crate::indicators::indicator::Indicator;
+
+    use super::*;
+    use crate::{
+        Next,
+        strategy::{Action, Condition, MarketData, StrategyNode, strat::Strategy},
+        types::OutputType,
+    };
+
+    fn tick(seconds: i64, price: f64, volume: f64) -> Tick {
+        Tick::new(DateTime::from_timestamp(seconds, 0).unwrap(), price, volume)
+    }
+
+    fn ohlcv(candle: &TimedCandle) -> (f64, f64, f64, f64, f64) {
+        (
+            candle.open,
+            candle.high,
+            candle.low,
+            candle.close,
+            candle.volume,
+        )
+    }
+
+    #[test]
+    fn test_time_bars() -> TaResult<()> {
+        let mut aggregator = Aggregator::new(BarKind::Time(60))?.with_forming(true);
+        let events = aggregator.next(tick(65, 10.0, 1.0))?;
+        assert_eq!(events.len(), 1);
+        assert!(!events[0].is_completed());
+        assert_eq!(events[0].candle().timestamp.timestamp(), 60);
+        aggregator.next(tick(90, 12.0, 2.0))?;
+        aggregator.next(tick(119, 9.0, 1.0))?;
+
+        let events = aggregator.next(tick(200, 11.0, 4.0))?;
+        assert_eq!(events.len(), 2);
+        assert_eq!(
+            events[0],
+            BarEvent::Completed(TimedCandle::new(
+                DateTime::from_timestamp(60, 0).unwrap(),
+                10.0,
+                12.0,
+                9.0,
+                9.0,
+                4.0
+            ))
+        );
+        assert_eq!(events[1].candle().timestamp.timestamp(), 180);
+        assert_eq!(
+            ohlcv(&aggregator.flush().unwrap()),
+            (11.0, 11.0, 11.0, 11.0, 4.0)
+        );
+        assert_eq!(aggregator.flush(), None);
+
+        assert!(aggregator.next(tick(100, 1.0, 1.0)).is_err());
+        assert!(aggregator.next(tick(300, f64::NAN, 1.0)).is_err());
+        assert!(Aggregator::new(BarKind::Time(0)).is_err());
+        assert!(Aggregator::new(BarKind::Renko(-1.0)).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_tick_volume_and_range_bars() -> TaResult<()> {
+        let ticks: Vec<Tick> = [10.0, 11.0, 12.5, 11.5, 10.0, 10.5, 13.0]
+            .into_iter()
+            .enumerate()
+            .map(|(i, price)| tick(i as i64, price, 1.0 + i as f64))
+            .collect();
+
+        let bars = Aggregator::new(BarKind::Ticks(3))?.aggregate(ticks.clone())?;
+        assert_eq!(bars.len(), 3);
+        assert_eq!(ohlcv(&bars[0]), (10.0, 12.5, 10.0, 12.5, 6.0));
+        assert_eq!(ohlcv(&bars[2]), (13.0, 13.0, 13.0, 13.0, 7.0));
+
+        // Volumes 1, 2, 3, 4, 5, 6, 7.
+        let bars = Aggregator::new(BarKind::Volume(6.0))?.aggregate(ticks.clone())?;
+        let volumes: Vec<f64> = bars.iter().map(|bar| bar.volume).collect();
+        assert_eq!(volumes, vec![6.0, 9.0, 6.0, 7.0]);
+
+        let bars = Aggregator::new(BarKind::Range(2.0))?.aggregate(ticks)?;
+        assert_eq!(ohlcv(&bars[0]), (10.0, 12.5, 10.0, 12.5, 6.0));
+        assert_eq!(ohlcv(&bars[1]), (11.5, 13.0, 10.0, 13.0, 22.0));
+        assert_eq!(bars.len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_renko() -> TaResult<()> {
+        let mut aggregator = Aggregator::new(BarKind::Renko(1.0))?.with_forming(true);
+        assert!(aggregator.next(tick(0, 100.0, 1.0))?.is_empty());
+        let bricks = aggregator.next(tick(1, 102.4, 2.0))?;
+        assert_eq!(bricks.len(), 2);
+        assert_eq!(ohlcv(bricks[0].candle()), (100.0, 101.0, 100.0, 101.0, 3.0));
+        assert_eq!(ohlcv(bricks[1].candle()), (101.0, 102.0, 101.0, 102.0, 0.0));
+        // A reversal needs two bricks.
+        assert!(aggregator.next(tick(2, 100.5, 1.0))?.is_empty());
+        let bricks = aggregator.next(tick(3, 99.9, 1.0))?;
+        assert_eq!(bricks.len(), 1);
+        assert_eq!(ohlcv(bricks[0].candle()), (101.0, 101.0, 100.0, 100.0, 2.0));
+        assert_eq!(aggregator.flush(), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_renko_bounds() -> TaResult<()> {
+        let mut aggregator = Aggregator::new(BarKind::Renko(1e-9))?;
+        assert!(aggregator.next(tick(0, 1e8, 1.0)).is_err());
+
+        let mut aggregator = Aggregator::new(BarKind::Renko(0.01))?;
+        aggregator.next(tick(0, 100.0, 1.0))?;
+        assert!(aggregator.next(tick(1, 1e6, 1.0)).is_err());
+        // The rejected tick is not counted.
+        let bricks = aggregator.next(tick(2, 100.025, 1.0))?;
+        assert_eq!(bricks.len(), 2);
+        assert_eq!(bricks[0].candle().volume, 2.0);
+
+        assert!(Aggregator::new(BarKind::Time(u64::MAX)).is_err());
+        assert!(Aggregator::new(BarKind::Time(i64::MAX as u64 / 1000)).is_ok());
+        Ok(())
+    }
+
+    #[test]
+    fn test_heikin_ashi() -> TaResult<()> {
+        let at = DateTime::from_timestamp(0, 0).unwrap();
+        let mut heikin_ashi = HeikinAshi::new();
+        let first = heikin_ashi.next(&TimedCandle::new(at, 10.0, 14.0, 8.0, 12.0, 5.0));
+        assert_eq!(ohlcv(&first), (11.0, 14.0, 8.0, 11.0, 5.0));
+        let second = heikin_ashi.next(&TimedCandle::new(at, 12.0, 13.0, 11.5, 12.5, 1.0));
+        assert_eq!(ohlcv(&second), (11.0, 13.0, 11.0, 12.25, 1.0));
+
+        let ticks = [
+            tick(0, 10.0, 1.0),
+            tick(1, 14.0, 1.0),
+            tick(2, 8.0, 1.0),
+            tick(3, 12.0, 2.0),
+        ];
+        let mut aggregator = Aggregator::new(BarKind::Ticks(4))?
+            .with_heikin_ashi()
+            .with_forming(true);
+        let mut events = Vec::new();
+        for tick in ticks {
+            events.extend(aggregator.next(tick)?);
+        }
+        assert_eq!(events.len(), 4);
+        assert_eq!(ohlcv(events[3].candle()), (11.0, 14.0, 8.0, 11.0, 5.0));
+        assert!(events[3].is_completed());
+        Ok(())
+    }
+
+    #[test]
+    fn test_feeds_indicators_and_strategies() -> TaResult<()> {
+        let ticks: Vec<Tick> = (0..40)
+            .map(|i| tick(i * 20, 100.0 + (i as f64 / 3.0).sin() * 5.0, 1.0))
+            .collect();
+        let bars = Aggregator::new(BarKind::Time(60))?.aggregate(ticks)?;
+        assert_eq!(bars.len(), 14);
+
+        let mut sma = Indicator::sma(3)?;
+        let mut strategy = Strategy::new(StrategyNode::If {
+            condition: Condition::greater_than(Indicator::sma(3)?, OutputType::Single(100.0)),
+            then_branch: Box::new(StrategyNode::Action(Action::Buy)),
+            else_branch: Some(Box::new(StrategyNode::Action(Action::Sell))),
+        });
+        let mut actions = Vec::new();
+        for bar in &bars {
+            sma.next(bar)?;
+            actions.extend(strategy.evaluate_at(&MarketData::from(bar), bar.timestamp)?);
+        }
+        assert_eq!(actions.len(), bars.len() - 3);
+        Ok(())
+    }
+}
@@ -0,0 +1,290 @@
+use std::{
+    fs::File,
+    io::{BufRead, BufReader},
+    path::Path,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    data::{Column, ColumnMapping, Row, TimestampFormat, candle, invalid_data, io_error},
+    error::TaResult,
+    strategy::StrategyError,
+};
+
+/// Layout of a CSV file.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct CsvConfig {
+    pub delimiter: char,
+    /// Whether the first line holds the column names. Columns can only be mapped by name
+    /// with a header.
+    pub header: bool,
+    pub columns: ColumnMapping,
+    pub timestamp: TimestampFormat,
+}
+
+impl Default for CsvConfig {
+    fn default() -> Self {
+        Self {
+            delimiter: ',',
+            header: true,
+            columns: ColumnMapping::default(),
+            timestamp: TimestampFormat::default(),
+        }
+    }
+}
+
+impl CsvConfig {
+    pub fn with_delimiter(mut self, delimiter: char) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+
+    pub fn with_header(mut self, header: bool) -> Self {
+        self.header = header;
+        self
+    }
+
+    pub fn with_columns(mut self, columns: ColumnMapping) -> Self {
+        self.columns = columns;
+        self
+    }
+
+    pub fn with_timestamp(mut self, timestamp: TimestampFormat) -> Self {
+        self.timestamp = timestamp;
+        self
+    }
+}
+
+/// Streams the candles of a CSV file, one line at a time.
+///
+/// Fields may be quoted with `"`, in which case they can hold the delimiter and `""` stands
+/// for a quote. Fields are trimmed and blank lines are ignored. Rows that can not be parsed
+/// yield a `StrategyError::InvalidData` and reading goes on with the next line.
+#[derive(Debug)]
+pub struct CsvReader<R> {
+    lines: std::io::Lines<R>,
+    config: CsvConfig,
+    /// Positions of the fields of the candle, in the order of `ColumnMapping::columns`.
+    positions: [Option<usize>; 6],
+    line: usize,
+    done: bool,
+}
+
+impl CsvReader<BufReader<File>> {
+    pub fn open(path: impl AsRef<Path>, config: CsvConfig) -> TaResult<Self> {
+        let path = path.as_ref();
+        let file = File::open(path).map_err(|error| {
+            StrategyError::IO(format!("can not open {}: {error}", path.display()))
+        })?;
+        Self::new(BufReader::new(file), config)
+    }
+}
+
+impl<R: BufRead> CsvReader<R> {
+    /// Reads the header, if any, and maps the columns.
+    pub fn new(reader: R, config: CsvConfig) -> TaResult<Self> {
+        let mut lines = reader.lines();
+        let mut line = 0;
+        let mut header = None;
+        if config.header {
+            for text in lines.by_ref() {
+                line += 1;
+                let text = text.map_err(|error| io_error(line, error))?;
+                if !text.trim().is_empty() {
+                    header = Some(split(&text, config.delimiter));
+                    break;
+                }
+            }
+        }
+        let mut positions = [None; 6];
+        for (position, column) in positions.iter_mut().zip(config.columns.columns()) {
+            *position = match (column, &header) {
+                (None, _) => None,
+                (Some(Column::Index(index)), _) => Some(*index),
+                (Some(Column::Name(name)), Some(header)) => Some(
+                    header
+                        .iter()
+                        .position(|field| field.eq_ignore_ascii_case(name))
+                        .ok_or_else(|| {
+                            StrategyError::Configuration(format!("no column named '{name}'"))
+                        })?,
+                ),
+                (Some(Column::Name(name)), None) => {
+                    return Err(StrategyError::Configuration(format!(
+                        "column '{name}' needs a header"
+                    ))
+                    .into());
+                }
+            };
+        }
+        Ok(Self {
+            lines,
+            config,
+            positions,
+            line,
+            done: false,
+        })
+    }
+
+    fn parse(&self, text: &str) -> TaResult<Row> {
+        let fields = split(text, self.config.delimiter);
+        let field = |position: usize| {
+            fields.get(position).map(String::as_str).ok_or_else(|| {
+                invalid_data(
+                    self.line,
+                    format!("{} fields, no field {position}", fields.len()),
+                )
+            })
+        };
+        let [timestamp, prices @ ..] = self.positions;
+        let text = field(timestamp.unwrap_or_default())?;
+        let timestamp = self
+            .config
+            .timestamp
+            .parse(text)
+            .ok_or_else(|| invalid_data(self.line, format!("invalid timestamp '{text}'")))?;
+        let mut values = [None; 5];
+        for (value, position) in values.iter_mut().zip(prices) {
+            if let Some(position) = position {
+                *value = Some(field(position)?.parse().unwrap_or(f64::NAN));
+            }
+        }
+        Ok(Row {
+            line: self.line,
+            candle: candle(self.line, timestamp, values)?,
+        })
+    }
+}
+
+impl<R: BufRead> Iterator for CsvReader<R> {
+    type Item = TaResult<Row>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done {
+            self.line += 1;
+            match self.lines.next()? {
+                Ok(text) if text.trim().is_empty() => continue,
+                Ok(text) => return Some(self.parse(&text)),
+                Err(error) => {
+                    self.done = true;
+                    return Some(Err(io_error(self.line, error)));
+                }
+            }
+        }
+        None
+    }
+}
+
+/// Splits a line into trimmed fields, honouring double quotes.
+fn split(line: &str, delimiter: char) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            c if c == delimiter && !quoted => {
+                fields.push(field.trim().to_string());
+                field.clear();
+            }
+            c => field.push(c),
+        }
+    }
+    fields.push(field.trim().to_string());
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{Policy, Validation, load_csv};
+    use crate::error::TaError;
+
+    #[test]
+    fn test_split() {
+        assert_eq!(split("a, b ,c", ','), vec!["a", "b", "c"]);
+        assert_eq!(
+            split("\"1,5\";\"say \"\"hi\"\"\";", ';'),
+            vec!["1,5", "say \"hi\"", ""]
+        );
+    }
+
+    #[test]
+    fn test_read_with_header() -> TaResult<()> {
+        let data = "Date,Open,High,Low,Close,Adj Close,Volume\n\
+                    2024-01-01,10,12,9,11,11,100\n\
+                    \n\
+                    2024-01-02,11,13,10,12.5,12.5,150\n";
+        let rows = CsvReader::new(
+            data.as_bytes(),
+            CsvConfig::default().with_columns(ColumnMapping::default().with_timestamp("date")),
+        )?
+        .collect::<TaResult<Vec<_>>>()?;
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[1].line, 4);
+        assert_eq!(rows[1].candle.close, 12.5);
+        assert_eq!(rows[1].candle.volume, 150.0);
+        assert_eq!(
+            rows[1].candle.timestamp.to_rfc3339(),
+            "2024-01-02T00:00:00+00:00"
+        );
+
+        let missing = CsvReader::new(data.as_bytes(), CsvConfig::default());
+        assert!(matches!(
+            missing,
+            Err(TaError::Strategy(StrategyError::Configuration(_)))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_by_index() -> TaResult<()> {
+        let data = "1700000000000;1;2;0.5;1.5\n1700000060000;1.5;x;1;2\n1700000120000;2;3\n";
+        let config = CsvConfig::default()
+            .with_header(false)
+            .with_delimiter(';')
+            .with_timestamp(TimestampFormat::Milliseconds)
+            .with_columns(ColumnMapping::indices().with_volume(None));
+        let rows: Vec<_> = CsvReader::new(data.as_bytes(), config.clone())?.collect();
+        assert_eq!(rows.len(), 3);
+        let first = rows[0].as_ref().unwrap();
+        assert_eq!(first.candle.timestamp.timestamp(), 1_700_000_000);
+        assert_eq!(first.candle.volume, 0.0);
+        assert_eq!(rows[1], Err(invalid_data(2, "invalid high")));
+        assert_eq!(rows[2], Err(invalid_data(3, "3 fields, no field 3")));
+
+        let dataset = load_csv(data.as_bytes(), &config, &Validation::all(Policy::Skip))?;
+        assert_eq!(dataset.candles.len(), 1);
+        assert_eq!(dataset.issues.len(), 2);
+        assert!(load_csv(data.as_bytes(), &config, &Validation::default()).is_err());
+
+        let named = config.with_columns(ColumnMapping::default());
+        assert!(CsvReader::new(data.as_bytes(), named).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_io_errors() -> TaResult<()> {
+        let missing = CsvReader::open("tests/no_such_file.csv", CsvConfig::default());
+        assert!(matches!(
+            missing,
+            Err(TaError::Strategy(StrategyError::IO(_)))
+        ));
+        let config = CsvConfig::default()
+            .with_header(false)
+            .with_columns(ColumnMapping::indices());
+        let rows: Vec<_> = CsvReader::new(&b"\xff\n"[..], config)?.collect();
+        assert!(matches!(
+            rows[..],
+            [Err(TaError::Strategy(StrategyError::IO(_)))]
+        ));
+        Ok(())
+    }
+}
@@ -0,0 +1,167 @@
+use std::{
+    fs::File,
+    io::{BufRead, BufReader},
+    path::Path,
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    data::{Column, ColumnMapping, Row, TimestampFormat, candle, invalid_data, io_error},
+    error::TaResult,
+    strategy::StrategyError,
+};
+
+/// Layout of a JSON Lines file.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(default)]
+pub struct JsonlConfig {
+    /// Keys of the fields in JSON objects, or their positions in JSON arrays.
+    pub columns: ColumnMapping,
+    pub timestamp: TimestampFormat,
+}
+
+impl JsonlConfig {
+    pub fn with_columns(mut self, columns: ColumnMapping) -> Self {
+        self.columns = columns;
+        self
+    }
+
+    pub fn with_timestamp(mut self, timestamp: TimestampFormat) -> Self {
+        self.timestamp = timestamp;
+        self
+    }
+}
+
+/// Streams the candles of a JSON Lines file, one line at a time.
+///
+/// Every line holds a JSON object or array. Prices and volumes may be numbers or strings
+/// holding numbers, and timestamps numbers or strings in the configured format. Lines that
+/// can not be parsed yield a `StrategyError::InvalidData` and reading goes on with the next
+/// line; blank lines are ignored.
+#[derive(Debug)]
+pub struct JsonlReader<R> {
+    lines: std::io::Lines<R>,
+    config: JsonlConfig,
+    line: usize,
+    done: bool,
+}
+
+impl JsonlReader<BufReader<File>> {
+    pub fn open(path: impl AsRef<Path>, config: JsonlConfig) -> TaResult<Self> {
+        let path = path.as_ref();
+        let file = File::open(path).map_err(|error| {
+            StrategyError::IO(format!("can not open {}: {error}", path.display()))
+        })?;
+        Ok(Self::new(BufReader::new(file), config))
+    }
+}
+
+impl<R: BufRead> JsonlReader<R> {
+    pub fn new(reader: R, config: JsonlConfig) -> Self {
+        Self {
+            lines: reader.lines(),
+            config,
+            line: 0,
+            done: false,
+        }
+    }
+
+    fn parse(&self, text: &str) -> TaResult<Row> {
+        let document: Value = serde_json::from_str(text)
+            .map_err(|error| invalid_data(self.line, error.to_string()))?;
+        let field = |column: &Column| {
+            let value = match column {
+                Column::Name(name) => document.get(name.as_str()),
+                Column::Index(index) => document.get(index),
+            };
+            value.ok_or_else(|| invalid_data(self.line, format!("missing field {column:?}")))
+        };
+        let [timestamp, prices @ ..] = self.config.columns.columns();
+        let value = field(timestamp.unwrap_or(&Column::Index(0)))?;
+        let timestamp = match value {
+            Value::Number(number) => number
+                .as_f64()
+                .and_then(|number| self.config.timestamp.from_number(number)),
+            Value::String(text) => self.config.timestamp.parse(text),
+            _ => None,
+        }
+        .ok_or_else(|| invalid_data(self.line, format!("invalid timestamp {value}")))?;
+        let mut values = [None; 5];
+        for (value, column) in values.iter_mut().zip(prices) {
+            if let Some(column) = column {
+                *value = Some(match field(column)? {
+                    Value::Number(number) => number.as_f64().unwrap_or(f64::NAN),
+                    Value::String(text) => text.trim().parse().unwrap_or(f64::NAN),
+                    _ => f64::NAN,
+                });
+            }
+        }
+        Ok(Row {
+            line: self.line,
+            candle: candle(self.line, timestamp, values)?,
+        })
+    }
+}
+
+impl<R: BufRead> Iterator for JsonlReader<R> {
+    type Item = TaResult<Row>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done {
+            self.line += 1;
+            match self.lines.next()? {
+                Ok(text) if text.trim().is_empty() => continue,
+                Ok(text) => return Some(self.parse(&text)),
+                Err(error) => {
+                    self.done = true;
+                    return Some(Err(io_error(self.line, error)));
+                }
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{Policy, Validation, load_jsonl};
+
+    #[test]
+    fn test_read_objects_and_arrays() -> TaResult<()> {
+        let objects = r#"{"timestamp":"2024-01-01T00:00:00Z","open":1,"high":2,"low":0.5,"close":1.5,"volume":"10"}
+{"timestamp":1704067260,"open":"1.5","high":2.5,"low":1,"close":2,"volume":12}
+{"timestamp":1704067320,"open":2,"high":3,"low":1.5}
+not json
+"#;
+        let rows: Vec<_> = JsonlReader::new(objects.as_bytes(), JsonlConfig::default()).collect();
+        assert_eq!(rows.len(), 4);
+        let second = rows[1].as_ref().unwrap();
+        assert_eq!(second.candle.timestamp.timestamp(), 1_704_067_260);
+        assert_eq!(second.candle.open, 1.5);
+        assert_eq!(rows[0].as_ref().unwrap().candle.volume, 10.0);
+        assert!(rows[2].is_err() && rows[3].is_err());
+
+        let dataset = load_jsonl(
+            objects.as_bytes(),
+            &JsonlConfig::default(),
+            &Validation::all(Policy::Skip),
+        )?;
+        assert_eq!(dataset.candles.len(), 2);
+        assert_eq!(
+            dataset.issues.iter().map(|i| i.line).collect::<Vec<_>>(),
+            vec![3, 4]
+        );
+
+        let arrays = "[1704067200000,\"1\",\"2\",\"0.5\",\"1.5\",\"7\"]\n";
+        let config = JsonlConfig::default()
+            .with_columns(ColumnMapping::indices())
+            .with_timestamp(TimestampFormat::Milliseconds);
+        let dataset = load_jsonl(arrays.as_bytes(), &config, &Validation::default())?;
+        assert_eq!(dataset.candles[0].timestamp.timestamp(), 1_704_067_200);
+        assert_eq!(dataset.candles[0].volume, 7.0);
+        Ok(())
+    }
+}
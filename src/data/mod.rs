@@ -0,0 +1,768 @@
+//! Loading of OHLCV candles from files.
+//!
+//! `CsvReader` and `JsonlReader` stream `Row`s out of any `BufRead`, one line at a time, and
+//! `Validation` checks them into a `Dataset`: it rejects, skips or repairs inconsistent
+//! candles, duplicated and out-of-order timestamps and gaps in the series, and records every
+//! problem it met as an `Issue`. `load_csv` and `load_jsonl` do both at once.
+//!
+//! `Aggregator` builds candles out of a live stream of `Tick`s instead.
+
+pub mod aggregate;
+pub mod csv;
+pub mod jsonl;
+
+use std::io::{BufReader, Read};
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime, TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    backtest::TimedBar,
+    error::{TaError, TaResult},
+    helper_types::Bar,
+    strategy::{MarketData, StrategyError},
+    traits::Candle,
+};
+
+pub use aggregate::{Aggregator, BarEvent, BarKind, HeikinAshi, Tick};
+pub use csv::{CsvConfig, CsvReader};
+pub use jsonl::{JsonlConfig, JsonlReader};
+
+/// Most candles a single gap may be repaired with.
+const MAX_FILL: usize = 100_000;
+
+/// An OHLCV candle and the time it opened at.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct TimedCandle {
+    pub timestamp: DateTime<Utc>,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    pub volume: f64,
+}
+
+impl TimedCandle {
+    pub fn new(
+        timestamp: DateTime<Utc>,
+        open: f64,
+        high: f64,
+        low: f64,
+        close: f64,
+        volume: f64,
+    ) -> Self {
+        Self {
+            timestamp,
+            open,
+            high,
+            low,
+            close,
+            volume,
+        }
+    }
+
+    /// A candle without trades, flat at `price`.
+    pub fn flat(timestamp: DateTime<Utc>, price: f64) -> Self {
+        Self::new(timestamp, price, price, price, price, 0.0)
+    }
+
+    /// Whether `high` and `low` enclose the open and the close.
+    pub fn has_valid_prices(&self) -> bool {
+        self.high >= self.open.max(self.close) && self.low <= self.open.min(self.close)
+    }
+
+    /// The candle as a bar closed `interval` seconds after it opened.
+    pub fn bar(&self, interval: u64) -> TaResult<TimedBar> {
+        let close = i64::try_from(interval)
+            .ok()
+            .and_then(TimeDelta::try_seconds)
+            .and_then(|interval| self.timestamp.checked_add_signed(interval))
+            .ok_or_else(|| {
+                StrategyError::Configuration(format!("interval of {interval} seconds"))
+            })?;
+        Ok(TimedBar::at(close, self.into()))
+    }
+
+    /// Widens `high` and `low` to enclose every price and raises a negative volume to zero.
+    pub fn repair(&mut self) {
+        let prices = [self.open, self.high, self.low, self.close];
+        self.high = prices.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        self.low = prices.iter().copied().fold(f64::INFINITY, f64::min);
+        self.volume = self.volume.max(0.0);
+    }
+}
+
+impl Candle for TimedCandle {
+    fn close(&self) -> f64 {
+        self.close
+    }
+
+    fn high(&self) -> f64 {
+        self.high
+    }
+
+    fn low(&self) -> f64 {
+        self.low
+    }
+
+    fn open(&self) -> f64 {
+        self.open
+    }
+
+    fn price(&self) -> f64 {
+        self.close
+    }
+
+    fn volume(&self) -> f64 {
+        self.volume
+    }
+}
+
+impl From<&TimedCandle> for MarketData {
+    fn from(candle: &TimedCandle) -> Self {
+        MarketData::Bar(
+            Bar::new()
+                .set_open(candle.open)
+                .set_high(candle.high)
+                .set_low(candle.low)
+                .set_close(candle.close)
+                .set_price(candle.close)
+                .set_volume(candle.volume),
+        )
+    }
+}
+
+/// A candle read from the line `line` of a file, counting from 1.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Row {
+    pub line: usize,
+    pub candle: TimedCandle,
+}
+
+/// Where a field of the candle is read from.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum Column {
+    /// A CSV header or a JSON object key. CSV headers are matched ignoring case.
+    Name(String),
+    /// A position in a CSV row or a JSON array, counting from 0.
+    Index(usize),
+}
+
+impl From<&str> for Column {
+    fn from(name: &str) -> Self {
+        Column::Name(name.to_string())
+    }
+}
+
+impl From<usize> for Column {
+    fn from(index: usize) -> Self {
+        Column::Index(index)
+    }
+}
+
+/// Columns of the candle fields. Without a volume column every candle has a volume of zero.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ColumnMapping {
+    pub timestamp: Column,
+    pub open: Column,
+    pub high: Column,
+    pub low: Column,
+    pub close: Column,
+    pub volume: Option<Column>,
+}
+
+impl Default for ColumnMapping {
+    /// Columns named `timestamp`, `open`, `high`, `low`, `close` and `volume`.
+    fn default() -> Self {
+        Self {
+            timestamp: "timestamp".into(),
+            open: "open".into(),
+            high: "high".into(),
+            low: "low".into(),
+            close: "close".into(),
+            volume: Some("volume".into()),
+        }
+    }
+}
+
+impl ColumnMapping {
+    /// Columns in the order timestamp, open, high, low, close and volume.
+    pub fn indices() -> Self {
+        Self {
+            timestamp: 0.into(),
+            open: 1.into(),
+            high: 2.into(),
+            low: 3.into(),
+            close: 4.into(),
+            volume: Some(5.into()),
+        }
+    }
+
+    pub fn with_timestamp(mut self, column: impl Into<Column>) -> Self {
+        self.timestamp = column.into();
+        self
+    }
+
+    pub fn with_volume(mut self, column: Option<Column>) -> Self {
+        self.volume = column;
+        self
+    }
+
+    /// Columns in the order of the fields of `TimedCandle`.
+    fn columns(&self) -> [Option<&Column>; 6] {
+        [
+            Some(&self.timestamp),
+            Some(&self.open),
+            Some(&self.high),
+            Some(&self.low),
+            Some(&self.close),
+            self.volume.as_ref(),
+        ]
+    }
+}
+
+/// How timestamps are written.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+pub enum TimestampFormat {
+    /// Unix seconds or milliseconds, told apart by their magnitude, RFC 3339, or
+    /// `%Y-%m-%d %H:%M:%S` and `%Y-%m-%d` in UTC.
+    #[default]
+    Auto,
+    /// Unix seconds, possibly fractional.
+    Seconds,
+    /// Unix milliseconds.
+    Milliseconds,
+    Rfc3339,
+    /// A `chrono` format string. Formats without an offset are read in UTC and formats
+    /// without a time at midnight.
+    Custom(String),
+}
+
+impl TimestampFormat {
+    /// Unix timestamps from 1e11 on are taken as milliseconds by `Auto`, which covers
+    /// seconds until the year 5138.
+    const AUTO_MILLISECONDS: f64 = 1e11;
+
+    pub fn parse(&self, text: &str) -> Option<DateTime<Utc>> {
+        let text = text.trim();
+        match self {
+            TimestampFormat::Auto => text
+                .parse::<f64>()
+                .ok()
+                .and_then(|number| self.from_number(number))
+                .or_else(|| DateTime::parse_from_rfc3339(text).ok().map(|t| t.to_utc()))
+                .or_else(|| naive(text, "%Y-%m-%d %H:%M:%S"))
+                .or_else(|| naive(text, "%Y-%m-%d")),
+            TimestampFormat::Seconds | TimestampFormat::Milliseconds => {
+                self.from_number(text.parse().ok()?)
+            }
+            TimestampFormat::Rfc3339 => DateTime::parse_from_rfc3339(text).ok().map(|t| t.to_utc()),
+            TimestampFormat::Custom(format) => DateTime::parse_from_str(text, format)
+                .ok()
+                .map(|t| t.to_utc())
+                .or_else(|| naive(text, format)),
+        }
+    }
+
+    /// Reads a Unix timestamp. Only `Auto`, `Seconds` and `Milliseconds` accept numbers.
+    pub fn from_number(&self, number: f64) -> Option<DateTime<Utc>> {
+        if !number.is_finite() {
+            return None;
+        }
+        let milliseconds = match self {
+            TimestampFormat::Auto if number.abs() >= Self::AUTO_MILLISECONDS => number,
+            TimestampFormat::Auto | TimestampFormat::Seconds => number * 1000.0,
+            TimestampFormat::Milliseconds => number,
+            _ => return None,
+        };
+        DateTime::from_timestamp_millis(milliseconds.round() as i64)
+    }
+}
+
+fn naive(text: &str, format: &str) -> Option<DateTime<Utc>> {
+    NaiveDateTime::parse_from_str(text, format)
+        .ok()
+        .or_else(|| {
+            NaiveDate::parse_from_str(text, format)
+                .ok()
+                .and_then(|date| date.and_hms_opt(0, 0, 0))
+        })
+        .map(|time| time.and_utc())
+}
+
+/// What to do with a problem found in the data.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Policy {
+    /// Stop loading with a `StrategyError::InvalidData`.
+    #[default]
+    Error,
+    /// Leave the offending row out, or leave a gap as it is.
+    Skip,
+    /// Fix the data, as described by the fields of `Validation`.
+    Repair,
+}
+
+/// Problem found in the data.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum IssueKind {
+    /// The row could not be parsed.
+    Malformed(String),
+    /// The high is below the open or the close, or the low above them.
+    InvalidPrices,
+    NegativeVolume,
+    /// The row has the same timestamp as another one.
+    Duplicate,
+    /// The row is older than the one before it.
+    OutOfOrder,
+    /// `missing` candles are missing before the row.
+    Gap {
+        missing: usize,
+    },
+}
+
+/// A problem found at a line of the data and what was done about it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Issue {
+    pub line: usize,
+    pub kind: IssueKind,
+    pub policy: Policy,
+}
+
+/// Candles that passed a `Validation`, in time order, and the problems it met.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct Dataset {
+    pub candles: Vec<TimedCandle>,
+    pub issues: Vec<Issue>,
+}
+
+impl Dataset {
+    /// The candles as backtest input, each closed `interval` seconds after it opened.
+    pub fn bars(&self, interval: u64) -> TaResult<Vec<TimedBar>> {
+        self.candles
+            .iter()
+            .map(|candle| candle.bar(interval))
+            .collect()
+    }
+}
+
+/// Checks applied to loaded rows, and what to do about each kind of problem.
+///
+/// - `invalid` covers rows that can not be parsed, prices outside of the high-low range and
+///   negative volumes. Repair widens the range and raises the volume to zero; rows that can
+///   not be parsed are skipped.
+/// - `duplicates` covers rows with the timestamp of another row. Skip keeps the first of them
+///   and repair the last.
+/// - `out_of_order` covers rows older than the row before them. Repair sorts the rows by
+///   timestamp, keeping the order of equal ones.
+/// - `gaps` covers missing candles, when `interval` is set. Two candles less than one and a
+///   half intervals apart leave no gap, so slightly irregular steps are accepted. Repair
+///   fills them with flat candles at the previous close and no volume, and fails on a gap of
+///   more than 100000 candles.
+///
+/// Every problem is recorded as an `Issue`, whatever the policy.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(default)]
+pub struct Validation {
+    pub invalid: Policy,
+    pub duplicates: Policy,
+    pub out_of_order: Policy,
+    pub gaps: Policy,
+    /// Expected time between two candles, in seconds. Gaps are not looked for without it.
+    pub interval: Option<u64>,
+}
+
+impl Validation {
+    /// The same policy for every problem.
+    pub fn all(policy: Policy) -> Self {
+        Self {
+            invalid: policy,
+            duplicates: policy,
+            out_of_order: policy,
+            gaps: policy,
+            interval: None,
+        }
+    }
+
+    pub fn with_invalid(mut self, policy: Policy) -> Self {
+        self.invalid = policy;
+        self
+    }
+
+    pub fn with_duplicates(mut self, policy: Policy) -> Self {
+        self.duplicates = policy;
+        self
+    }
+
+    pub fn with_out_of_order(mut self, policy: Policy) -> Self {
+        self.out_of_order = policy;
+        self
+    }
+
+    /// Looks for gaps in a series of candles `interval` seconds apart.
+    pub fn with_gaps(mut self, policy: Policy, interval: u64) -> Self {
+        self.gaps = policy;
+        self.interval = Some(interval);
+        self
+    }
+
+    /// Validates rows as they are read. Fails with the first error of the reader or the first
+    /// problem whose policy is `Policy::Error`.
+    pub fn apply(&self, rows: impl IntoIterator<Item = TaResult<Row>>) -> TaResult<Dataset> {
+        if self.interval == Some(0) {
+            return Err(StrategyError::Configuration("interval of 0 seconds".into()).into());
+        }
+        let mut issues = Issues::default();
+        let mut rows_kept: Vec<Row> = Vec::new();
+        let mut unsorted = false;
+        for row in rows {
+            let mut row = match row {
+                Ok(row) => row,
+                Err(TaError::Strategy(StrategyError::InvalidData { line, reason }))
+                    if self.invalid != Policy::Error =>
+                {
+                    issues.push(line, IssueKind::Malformed(reason), Policy::Skip)?;
+                    continue;
+                }
+                Err(error) => return Err(error),
+            };
+            let kind = if !row.candle.has_valid_prices() {
+                Some(IssueKind::InvalidPrices)
+            } else if row.candle.volume < 0.0 {
+                Some(IssueKind::NegativeVolume)
+            } else {
+                None
+            };
+            if let Some(kind) = kind {
+                issues.push(row.line, kind, self.invalid)?;
+                match self.invalid {
+                    Policy::Repair => row.candle.repair(),
+                    _ => continue,
+                }
+            }
+            let last = rows_kept.last().map(|last| last.candle.timestamp);
+            if last == Some(row.candle.timestamp) {
+                issues.push(row.line, IssueKind::Duplicate, self.duplicates)?;
+                if self.duplicates == Policy::Repair {
+                    rows_kept.pop();
+                    rows_kept.push(row);
+                }
+                continue;
+            }
+            if last.is_some_and(|last| row.candle.timestamp < last) {
+                issues.push(row.line, IssueKind::OutOfOrder, self.out_of_order)?;
+                if self.out_of_order == Policy::Skip {
+                    continue;
+                }
+                unsorted = true;
+            }
+            rows_kept.push(row);
+        }
+
+        if unsorted {
+            rows_kept.sort_by_key(|row| row.candle.timestamp);
+            let mut deduplicated: Vec<Row> = Vec::with_capacity(rows_kept.len());
+            for row in rows_kept {
+                let last = deduplicated.last().map(|last| last.candle.timestamp);
+                if last != Some(row.candle.timestamp) {
+                    deduplicated.push(row);
+                    continue;
+                }
+                issues.push(row.line, IssueKind::Duplicate, self.duplicates)?;
+                if self.duplicates == Policy::Repair {
+                    deduplicated.pop();
+                    deduplicated.push(row);
+                }
+            }
+            rows_kept = deduplicated;
+        }
+
+        let mut candles: Vec<TimedCandle> = Vec::with_capacity(rows_kept.len());
+        for row in rows_kept {
+            if let (Some(previous), Some(interval)) = (candles.last().copied(), self.interval) {
+                let elapsed = (row.candle.timestamp - previous.timestamp).num_seconds();
+                let elapsed = u64::try_from(elapsed).unwrap_or(0);
+                let intervals = elapsed.saturating_add(interval / 2) / interval;
+                let missing = usize::try_from(intervals.saturating_sub(1)).unwrap_or(usize::MAX);
+                if missing > 0 {
+                    issues.push(row.line, IssueKind::Gap { missing }, self.gaps)?;
+                }
+                if self.gaps == Policy::Repair && missing > MAX_FILL {
+                    return Err(invalid_data(
+                        row.line,
+                        format!("{missing} missing candles before is too many to fill"),
+                    ));
+                }
+                if self.gaps == Policy::Repair {
+                    candles.extend((1..=missing).map(|slot| {
+                        let offset = TimeDelta::seconds((slot as u64 * interval) as i64);
+                        TimedCandle::flat(previous.timestamp + offset, previous.close)
+                    }));
+                }
+            }
+            candles.push(row.candle);
+        }
+        Ok(Dataset {
+            candles,
+            issues: issues.0,
+        })
+    }
+}
+
+#[derive(Default)]
+struct Issues(Vec<Issue>);
+
+impl Issues {
+    /// Records an issue, or fails if its policy is `Policy::Error`.
+    fn push(&mut self, line: usize, kind: IssueKind, policy: Policy) -> TaResult<()> {
+        if policy == Policy::Error {
+            let reason = match kind {
+                IssueKind::Malformed(reason) => reason,
+                IssueKind::InvalidPrices => "high and low do not enclose open and close".into(),
+                IssueKind::NegativeVolume => "negative volume".into(),
+                IssueKind::Duplicate => "duplicate timestamp".into(),
+                IssueKind::OutOfOrder => "timestamp out of order".into(),
+                IssueKind::Gap { missing } => format!("{missing} missing candles before"),
+            };
+            return Err(StrategyError::InvalidData { line, reason }.into());
+        }
+        self.0.push(Issue { line, kind, policy });
+        Ok(())
+    }
+}
+
+/// Reads and validates CSV candles.
+pub fn load_csv(
+    reader: impl Read,
+    config: &CsvConfig,
+    validation: &Validation,
+) -> TaResult<Dataset> {
+    validation.apply(CsvReader::new(BufReader::new(reader), config.clone())?)
+}
+
+/// Reads and validates JSON Lines candles.
+pub fn load_jsonl(
+    reader: impl Read,
+    config: &JsonlConfig,
+    validation: &Validation,
+) -> TaResult<Dataset> {
+    validation.apply(JsonlReader::new(BufReader::new(reader), config.clone()))
+}
+
+/// Wraps an I/O error of a reader.
+fn io_error(line: usize, error: std::io::Error) -> TaError {
+    StrategyError::IO(format!("after line {line}: {error}")).into()
+}
+
+fn invalid_data(line: usize, reason: impl Into<String>) -> TaError {
+    StrategyError::InvalidData {
+        line,
+        reason: reason.into(),
+    }
+    .into()
+}
+
+/// Builds a candle from its six fields, in the order of `ColumnMapping::columns`. A missing
+/// volume is zero.
+fn candle(
+    line: usize,
+    timestamp: DateTime<Utc>,
+    values: [Option<f64>; 5],
+) -> TaResult<TimedCandle> {
+    let field = |index: usize, name: &str| {
+        values[index]
+            .filter(|value| value.is_finite())
+            .ok_or_else(|| invalid_data(line, format!("invalid {name}")))
+    };
+    Ok(TimedCandle::new(
+        timestamp,
+        field(0, "open")?,
+        field(1, "high")?,
+        field(2, "low")?,
+        field(3, "close")?,
+        match values[4] {
+            Some(_) => field(4, "volume")?,
+            None => 0.0,
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(seconds: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp(seconds, 0).unwrap()
+    }
+
+    fn row(line: usize, seconds: i64, close: f64) -> TaResult<Row> {
+        Ok(Row {
+            line,
+            candle: TimedCandle::new(at(seconds), close, close + 1.0, close - 1.0, close, 10.0),
+        })
+    }
+
+    #[test]
+    fn test_timestamp_formats() {
+        let expected = Some(at(1_700_000_000));
+        assert_eq!(TimestampFormat::Auto.parse("1700000000"), expected);
+        assert_eq!(TimestampFormat::Auto.parse("1700000000000"), expected);
+        assert_eq!(
+            TimestampFormat::Auto.parse("2023-11-14T22:13:20Z"),
+            expected
+        );
+        assert_eq!(TimestampFormat::Auto.parse("2023-11-14 22:13:20"), expected);
+        assert_eq!(
+            TimestampFormat::Auto.parse("2023-11-14"),
+            Some(at(1_699_920_000))
+        );
+        assert_eq!(
+            TimestampFormat::Milliseconds.parse("1700000000000"),
+            expected
+        );
+        assert_eq!(TimestampFormat::Seconds.parse("1700000000.0"), expected);
+        assert_eq!(
+            TimestampFormat::Custom("%d/%m/%Y %H:%M".into()).parse("14/11/2023 22:13"),
+            Some(at(1_700_000_000 - 20))
+        );
+        assert_eq!(TimestampFormat::Rfc3339.parse("1700000000"), None);
+        assert_eq!(TimestampFormat::Auto.parse("yesterday"), None);
+    }
+
+    #[test]
+    fn test_invalid_candles() -> TaResult<()> {
+        let broken = || {
+            vec![
+                row(1, 0, 10.0),
+                Ok(Row {
+                    line: 2,
+                    candle: TimedCandle::new(at(60), 10.0, 9.0, 8.0, 11.0, -1.0),
+                }),
+                Err(invalid_data(3, "invalid close")),
+                row(4, 120, 12.0),
+            ]
+        };
+        assert_eq!(
+            Validation::default().apply(broken()),
+            Err(invalid_data(
+                2,
+                "high and low do not enclose open and close"
+            ))
+        );
+
+        let skipped = Validation::all(Policy::Skip).apply(broken())?;
+        assert_eq!(skipped.candles.len(), 2);
+        assert_eq!(skipped.issues.len(), 2);
+        assert_eq!(
+            skipped.issues[1].kind,
+            IssueKind::Malformed("invalid close".into())
+        );
+
+        let repaired = Validation::all(Policy::Repair).apply(broken())?;
+        assert_eq!(repaired.candles.len(), 3);
+        assert_eq!(
+            repaired.candles[1],
+            TimedCandle::new(at(60), 10.0, 11.0, 8.0, 11.0, 0.0)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_duplicates_and_order() -> TaResult<()> {
+        let rows = || {
+            vec![
+                row(1, 0, 1.0),
+                row(2, 60, 2.0),
+                row(3, 60, 3.0),
+                row(4, 180, 4.0),
+                row(5, 120, 5.0),
+                row(6, 60, 6.0),
+            ]
+        };
+        assert!(matches!(
+            Validation::default().apply(rows()),
+            Err(TaError::Strategy(StrategyError::InvalidData {
+                line: 3,
+                ..
+            }))
+        ));
+
+        let closes = |dataset: &Dataset| -> Vec<f64> {
+            dataset.candles.iter().map(|candle| candle.close).collect()
+        };
+        let skipped = Validation::all(Policy::Skip).apply(rows())?;
+        assert_eq!(closes(&skipped), vec![1.0, 2.0, 4.0]);
+        assert_eq!(
+            skipped.issues.iter().map(|i| &i.kind).collect::<Vec<_>>(),
+            vec![
+                &IssueKind::Duplicate,
+                &IssueKind::OutOfOrder,
+                &IssueKind::OutOfOrder
+            ]
+        );
+
+        let repaired = Validation::all(Policy::Repair).apply(rows())?;
+        assert_eq!(closes(&repaired), vec![1.0, 6.0, 5.0, 4.0]);
+        assert!(
+            repaired
+                .candles
+                .windows(2)
+                .all(|pair| pair[0].timestamp < pair[1].timestamp)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_gaps() -> TaResult<()> {
+        let rows = || vec![row(1, 0, 1.0), row(2, 60, 2.0), row(3, 240, 3.0)];
+        assert!(Validation::default().apply(rows())?.issues.is_empty());
+        assert!(
+            Validation::default()
+                .with_gaps(Policy::Error, 60)
+                .apply(rows())
+                .is_err()
+        );
+
+        let kept = Validation::default()
+            .with_gaps(Policy::Skip, 60)
+            .apply(rows())?;
+        assert_eq!(kept.candles.len(), 3);
+        assert_eq!(kept.issues[0].kind, IssueKind::Gap { missing: 2 });
+
+        let filled = Validation::default()
+            .with_gaps(Policy::Repair, 60)
+            .apply(rows())?;
+        assert_eq!(filled.candles.len(), 5);
+        assert_eq!(filled.candles[2], TimedCandle::flat(at(120), 2.0));
+        assert_eq!(filled.candles[3], TimedCandle::flat(at(180), 2.0));
+        let bars = filled.bars(60)?;
+        assert_eq!(bars.len(), 5);
+        assert_eq!(bars[0].timestamp, Some(at(60)));
+        assert!(filled.bars(u64::MAX).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_gap_tolerance_and_limit() -> TaResult<()> {
+        let irregular = Validation::default()
+            .with_gaps(Policy::Error, 60)
+            .apply(vec![row(1, 0, 1.0), row(2, 61, 2.0), row(3, 110, 3.0)])?;
+        assert!(irregular.issues.is_empty());
+        let late = Validation::default()
+            .with_gaps(Policy::Skip, 60)
+            .apply(vec![row(1, 0, 1.0), row(2, 95, 2.0)])?;
+        assert_eq!(late.issues[0].kind, IssueKind::Gap { missing: 1 });
+
+        let outlier = || vec![row(1, 0, 1.0), row(2, 60 * 1_000_000_000, 2.0)];
+        assert!(
+            Validation::default()
+                .with_gaps(Policy::Repair, 60)
+                .apply(outlier())
+                .is_err()
+        );
+        let kept = Validation::default()
+            .with_gaps(Policy::Skip, 60)
+            .apply(outlier())?;
+        assert_eq!(kept.candles.len(), 2);
+        Ok(())
+    }
+}
@@ -1,10 +1,19 @@
+#[cfg(feature = "strategy")]
+pub mod backtest;
+#[cfg(feature = "strategy")]
+pub mod data;
 pub mod error;
 pub mod helper;
 /// This is a Technical analysis crate based on [`ta-rs`](https://github.com/greyblake/ta-rs) and [`rust_ti`](https://github.com/0100101001010000/RustTI)
 pub mod indicators;
+pub mod metrics;
+#[cfg(feature = "strategy")]
+pub mod optimize;
 pub mod preprocessing;
+pub mod random;
 #[cfg(feature = "strategy")]
 pub mod strategy;
+pub mod synthetic;
 
 pub mod traits;
 pub mod types;
@@ -30,7 +39,7 @@ mod py {
     use pyo3::{
         pymodule,
         types::{PyModule, PyModuleMethods},
-        Bound, PyResult,
+        wrap_pyfunction, Bound, PyResult,
     };
     use pyo3_stub_gen::define_stub_info_gatherer;
 
@@ -41,6 +50,10 @@ mod py {
     fn chipa_ta(m: &Bound<'_, PyModule>) -> PyResult<()> {
         m.add_class::<Indicator>()?;
         m.add_class::<Candle>()?;
+        #[cfg(feature = "strategy")]
+        m.add_function(wrap_pyfunction!(crate::backtest::py::backtest, m)?)?;
+        #[cfg(feature = "strategy")]
+        m.add_function(wrap_pyfunction!(crate::backtest::py::backtest_binary, m)?)?;
 
         Ok(())
     }
@@ -0,0 +1,396 @@
+//! Performance metrics of equity curves and trade lists.
+//!
+//! Every function works on plain slices so the metrics can be computed for any source of
+//! equity values and trades, not only for the reports of the backtest engine. Returns and
+//! drawdowns are fractions (`0.05` is 5%), durations are counted in bars and ratios are
+//! computed with a risk free rate of zero.
+
+use serde::{Deserialize, Serialize};
+
+/// A closed trade as seen by the metrics.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub struct TradeRecord {
+    /// Net profit of the trade.
+    pub pnl: f64,
+    /// Number of bars the position was held.
+    pub bars_held: usize,
+}
+
+impl TradeRecord {
+    pub fn new(pnl: f64, bars_held: usize) -> Self {
+        Self { pnl, bars_held }
+    }
+}
+
+/// Deepest decline of an equity curve from a previous peak.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub struct Drawdown {
+    /// Decline from the peak to the trough as a fraction of the peak.
+    pub depth: f64,
+    pub peak_index: usize,
+    pub trough_index: usize,
+    /// Index at which the curve got back to the peak, if it did.
+    pub recovery_index: Option<usize>,
+}
+
+/// Every metric of an equity curve and the trades that produced it.
+///
+/// Returns and ratios are `None` when they are not finite, such as the annualized return of
+/// a short curve that grows fast or the ratios of a curve starting at zero, so that the
+/// metrics serialize back and forth.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub struct Metrics {
+    pub total_return: Option<f64>,
+    pub annualized_return: Option<f64>,
+    pub sharpe: Option<f64>,
+    pub sortino: Option<f64>,
+    pub calmar: Option<f64>,
+    pub max_drawdown: Option<f64>,
+    /// Longest number of bars spent below a previous peak.
+    pub max_drawdown_duration: usize,
+    pub trades: usize,
+    /// Fraction of trades with a positive profit.
+    pub win_rate: f64,
+    /// `None` when there are profits but no losses, which makes it infinite.
+    pub profit_factor: Option<f64>,
+    pub expectancy: Option<f64>,
+    pub max_consecutive_wins: usize,
+    pub max_consecutive_losses: usize,
+    /// Fraction of the bars spent in a position.
+    pub exposure: f64,
+    /// Average number of bars a position was held.
+    pub average_holding: f64,
+}
+
+impl Metrics {
+    /// Computes every metric. `periods_per_year` is the number of bars in a year, such as
+    /// `252.0` for daily bars of a stock or `365.0 * 24.0` for hourly bars of a crypto asset.
+    pub fn compute(equity: &[f64], trades: &[TradeRecord], periods_per_year: f64) -> Self {
+        let returns = returns(equity);
+        let (max_consecutive_wins, max_consecutive_losses) = streaks(trades);
+        Self {
+            total_return: finite(total_return(equity)),
+            annualized_return: finite(annualized_return(equity, periods_per_year)),
+            sharpe: finite(sharpe_ratio(&returns, periods_per_year)),
+            sortino: finite(sortino_ratio(&returns, periods_per_year)),
+            calmar: finite(calmar_ratio(equity, periods_per_year)),
+            max_drawdown: finite(max_drawdown(equity).depth),
+            max_drawdown_duration: max_drawdown_duration(equity),
+            trades: trades.len(),
+            win_rate: win_rate(trades),
+            profit_factor: finite(profit_factor(trades)),
+            expectancy: finite(expectancy(trades)),
+            max_consecutive_wins,
+            max_consecutive_losses,
+            exposure: exposure(trades, equity.len()),
+            average_holding: average_holding(trades),
+        }
+    }
+}
+
+/// `value` if it is finite.
+pub(crate) fn finite(value: f64) -> Option<f64> {
+    Some(value).filter(|value| value.is_finite())
+}
+
+fn mean(values: &[f64]) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    values.iter().sum::<f64>() / values.len() as f64
+}
+
+/// Simple returns between consecutive equity values.
+pub fn returns(equity: &[f64]) -> Vec<f64> {
+    equity
+        .windows(2)
+        .map(|pair| pair[1] / pair[0] - 1.0)
+        .collect()
+}
+
+/// Change from the first to the last equity value.
+pub fn total_return(equity: &[f64]) -> f64 {
+    match (equity.first(), equity.last()) {
+        (Some(first), Some(last)) if *first != 0.0 => last / first - 1.0,
+        _ => 0.0,
+    }
+}
+
+/// Compound yearly return of the curve, given the number of bars in a year.
+pub fn annualized_return(equity: &[f64], periods_per_year: f64) -> f64 {
+    let periods = equity.len().saturating_sub(1);
+    if periods == 0 {
+        return 0.0;
+    }
+    let growth = 1.0 + total_return(equity);
+    if growth <= 0.0 {
+        return -1.0;
+    }
+    growth.powf(periods_per_year / periods as f64) - 1.0
+}
+
+/// Annualized mean return over the sample standard deviation of the returns. `0.0` when the
+/// returns do not vary.
+pub fn sharpe_ratio(returns: &[f64], periods_per_year: f64) -> f64 {
+    if returns.len() < 2 {
+        return 0.0;
+    }
+    let mean = mean(returns);
+    let variance =
+        returns.iter().map(|r| (r - mean).powi(2)).sum::<f64>() / (returns.len() - 1) as f64;
+    if variance == 0.0 {
+        return 0.0;
+    }
+    mean / variance.sqrt() * periods_per_year.sqrt()
+}
+
+/// Annualized mean return over the downside deviation, which only penalizes negative
+/// returns. `0.0` when no return is negative.
+pub fn sortino_ratio(returns: &[f64], periods_per_year: f64) -> f64 {
+    if returns.is_empty() {
+        return 0.0;
+    }
+    let downside = returns.iter().map(|r| r.min(0.0).powi(2)).sum::<f64>() / returns.len() as f64;
+    if downside == 0.0 {
+        return 0.0;
+    }
+    mean(returns) / downside.sqrt() * periods_per_year.sqrt()
+}
+
+/// Annualized return over the maximum drawdown. `0.0` without drawdown.
+pub fn calmar_ratio(equity: &[f64], periods_per_year: f64) -> f64 {
+    let depth = max_drawdown(equity).depth;
+    if depth == 0.0 {
+        return 0.0;
+    }
+    annualized_return(equity, periods_per_year) / depth
+}
+
+/// Deepest decline of the curve from a previous peak.
+pub fn max_drawdown(equity: &[f64]) -> Drawdown {
+    let mut worst = Drawdown::default();
+    let mut peak = 0;
+    for (index, &value) in equity.iter().enumerate() {
+        if value >= equity[peak] {
+            peak = index;
+            continue;
+        }
+        let depth = 1.0 - value / equity[peak];
+        if depth > worst.depth {
+            worst = Drawdown {
+                depth,
+                peak_index: peak,
+                trough_index: index,
+                recovery_index: None,
+            };
+        }
+    }
+    if worst.depth > 0.0 {
+        let peak = equity[worst.peak_index];
+        worst.recovery_index = equity
+            .iter()
+            .skip(worst.trough_index)
+            .position(|&value| value >= peak)
+            .map(|offset| worst.trough_index + offset);
+    }
+    worst
+}
+
+/// Longest number of bars the curve spent below a previous peak, including a drawdown that
+/// has not recovered by the end of the curve.
+pub fn max_drawdown_duration(equity: &[f64]) -> usize {
+    let mut longest = 0;
+    let mut peak = f64::NEG_INFINITY;
+    let mut below = 0;
+    for &value in equity {
+        if value >= peak {
+            peak = value;
+            below = 0;
+        } else {
+            below += 1;
+            longest = longest.max(below);
+        }
+    }
+    longest
+}
+
+/// Fraction of trades with a positive profit.
+pub fn win_rate(trades: &[TradeRecord]) -> f64 {
+    if trades.is_empty() {
+        return 0.0;
+    }
+    trades.iter().filter(|trade| trade.pnl > 0.0).count() as f64 / trades.len() as f64
+}
+
+/// Gross profit over gross loss. Infinite when there are profits but no losses and `0.0`
+/// without profits.
+pub fn profit_factor(trades: &[TradeRecord]) -> f64 {
+    let profit: f64 = trades.iter().map(|trade| trade.pnl.max(0.0)).sum();
+    let loss: f64 = trades.iter().map(|trade| -trade.pnl.min(0.0)).sum();
+    if profit == 0.0 {
+        0.0
+    } else if loss == 0.0 {
+        f64::INFINITY
+    } else {
+        profit / loss
+    }
+}
+
+/// Average profit per trade.
+pub fn expectancy(trades: &[TradeRecord]) -> f64 {
+    if trades.is_empty() {
+        return 0.0;
+    }
+    trades.iter().map(|trade| trade.pnl).sum::<f64>() / trades.len() as f64
+}
+
+/// Longest runs of winning and losing trades. Break-even trades end both runs.
+pub fn streaks(trades: &[TradeRecord]) -> (usize, usize) {
+    let (mut wins, mut losses) = (0, 0);
+    let (mut max_wins, mut max_losses) = (0, 0);
+    for trade in trades {
+        if trade.pnl > 0.0 {
+            wins += 1;
+            losses = 0;
+        } else if trade.pnl < 0.0 {
+            losses += 1;
+            wins = 0;
+        } else {
+            wins = 0;
+            losses = 0;
+        }
+        max_wins = usize::max(max_wins, wins);
+        max_losses = usize::max(max_losses, losses);
+    }
+    (max_wins, max_losses)
+}
+
+/// Fraction of `bars` spent in a position, capped at `1.0` for overlapping trades.
+pub fn exposure(trades: &[TradeRecord], bars: usize) -> f64 {
+    if bars == 0 {
+        return 0.0;
+    }
+    let held: usize = trades.iter().map(|trade| trade.bars_held).sum();
+    (held as f64 / bars as f64).min(1.0)
+}
+
+/// Average number of bars a position was held.
+pub fn average_holding(trades: &[TradeRecord]) -> f64 {
+    if trades.is_empty() {
+        return 0.0;
+    }
+    trades.iter().map(|trade| trade.bars_held).sum::<usize>() as f64 / trades.len() as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn test_returns() {
+        let equity = [100.0, 110.0, 99.0, 121.0];
+        assert!(close(total_return(&equity), 0.21));
+        assert!(close(
+            annualized_return(&equity, 2.0),
+            1.21f64.powf(2.0 / 3.0) - 1.0
+        ));
+        assert!(close(annualized_return(&[100.0, 110.0, 121.0], 1.0), 0.1));
+        let returns = returns(&equity);
+        assert_eq!(returns.len(), 3);
+        assert!(close(returns[1], -0.1));
+        assert_eq!(total_return(&[]), 0.0);
+        assert_eq!(annualized_return(&[100.0], 252.0), 0.0);
+    }
+
+    #[test]
+    fn test_ratios() {
+        let returns = [0.01, -0.02, 0.03, 0.0];
+        let mean = 0.005;
+        let std = ((0.005f64.powi(2) + 0.025f64.powi(2) + 0.025f64.powi(2) + 0.005f64.powi(2))
+            / 3.0)
+            .sqrt();
+        assert!(close(sharpe_ratio(&returns, 4.0), mean / std * 2.0));
+        let downside = (0.0004f64 / 4.0).sqrt();
+        assert!(close(sortino_ratio(&returns, 4.0), mean / downside * 2.0));
+        assert_eq!(sharpe_ratio(&[0.01, 0.01], 252.0), 0.0);
+        assert_eq!(sortino_ratio(&[0.01, 0.02], 252.0), 0.0);
+
+        let equity = [100.0, 120.0, 90.0, 130.0];
+        assert!(close(
+            calmar_ratio(&equity, 3.0),
+            annualized_return(&equity, 3.0) / 0.25
+        ));
+    }
+
+    #[test]
+    fn test_drawdown() {
+        let equity = [100.0, 120.0, 90.0, 100.0, 125.0, 110.0, 115.0];
+        let drawdown = max_drawdown(&equity);
+        assert!(close(drawdown.depth, 0.25));
+        assert_eq!((drawdown.peak_index, drawdown.trough_index), (1, 2));
+        assert_eq!(drawdown.recovery_index, Some(4));
+        // Two bars below 120, then two bars below 125 that never recover.
+        assert_eq!(max_drawdown_duration(&equity), 2);
+        assert_eq!(max_drawdown_duration(&[100.0, 90.0, 95.0, 99.0]), 3);
+        assert_eq!(max_drawdown(&[100.0, 101.0]), Drawdown::default());
+    }
+
+    #[test]
+    fn test_trade_metrics() {
+        let trades = [
+            TradeRecord::new(10.0, 2),
+            TradeRecord::new(-5.0, 1),
+            TradeRecord::new(-5.0, 3),
+            TradeRecord::new(20.0, 4),
+            TradeRecord::new(15.0, 2),
+        ];
+        assert!(close(win_rate(&trades), 0.6));
+        assert!(close(profit_factor(&trades), 4.5));
+        assert!(close(expectancy(&trades), 7.0));
+        assert_eq!(streaks(&trades), (2, 2));
+        assert!(close(exposure(&trades, 24), 0.5));
+        assert!(close(average_holding(&trades), 2.4));
+        assert_eq!(profit_factor(&trades[..1]), f64::INFINITY);
+        assert_eq!(profit_factor(&[]), 0.0);
+    }
+
+    #[test]
+    fn test_compute_serializes() -> Result<(), serde_json::Error> {
+        let equity = [100.0, 110.0, 105.0, 120.0];
+        let trades = [TradeRecord::new(10.0, 1), TradeRecord::new(10.0, 2)];
+        let metrics = Metrics::compute(&equity, &trades, 252.0);
+        assert!(close(metrics.total_return.unwrap(), 0.2));
+        assert_eq!(metrics.max_drawdown_duration, 1);
+        assert_eq!(metrics.max_consecutive_wins, 2);
+        // Only winning trades: the infinite profit factor is written as null.
+        assert_eq!(metrics.profit_factor, None);
+        let json = serde_json::to_string(&metrics)?;
+        assert!(json.contains("\"profit_factor\":null"));
+        assert_eq!(serde_json::from_str::<Metrics>(&json)?, metrics);
+
+        let metrics = Metrics::compute(&equity, &trades[..0], 252.0);
+        assert_eq!(metrics.profit_factor, Some(0.0));
+        let json = serde_json::to_string(&metrics)?;
+        assert_eq!(serde_json::from_str::<Metrics>(&json)?, metrics);
+
+        // A single two minute bar of growth overflows once annualized.
+        let metrics = Metrics::compute(&[100.0, 150.0], &[], 525_600.0);
+        assert_eq!(
+            (metrics.annualized_return, metrics.calmar),
+            (None, Some(0.0))
+        );
+        let json = serde_json::to_string(&metrics)?;
+        assert_eq!(serde_json::from_str::<Metrics>(&json)?, metrics);
+
+        // Returns from a zero equity are infinite.
+        let metrics = Metrics::compute(&[0.0, 10.0, 5.0], &[], 252.0);
+        assert_eq!((metrics.sharpe, metrics.sortino), (None, None));
+        let json = serde_json::to_string(&metrics)?;
+        assert_eq!(serde_json::from_str::<Metrics>(&json)?, metrics);
+        Ok(())
+    }
+}
@@ -0,0 +1,954 @@
+use std::{num::NonZeroUsize, thread};
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::{
+    backtest::{Backtest, TimedBar},
+    error::TaResult,
+    metrics::{Metrics, finite},
+    optimize::search::{Objective, parallel_map},
+    random::Rng,
+    strategy::{
+        Action, Condition, StrategyError, StrategyNode, condition::Operator,
+        wrapper::IndicatorState,
+    },
+    types::OutputType,
+};
+
+/// Random trees tried per individual of the initial population before giving up on the palette.
+const MAX_ATTEMPTS: usize = 100;
+
+/// An indicator the genetic search may put in its conditions.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Gene {
+    /// Type of the indicator as serialized, such as `"Rsi"` or `"Sma"`. The indicator must be
+    /// fully described by its type and a `period`.
+    pub indicator: String,
+    /// Smallest and largest period, both included.
+    pub periods: (usize, usize),
+    /// Range of the values the indicator is compared to in `Condition::Value`. `None` to
+    /// never compare the indicator to a value.
+    pub thresholds: Option<(f64, f64)>,
+    /// Whether the indicator may be compared to itself with another period in
+    /// `Condition::Indicator`, as in moving average crossovers.
+    pub compare: bool,
+}
+
+impl Gene {
+    pub fn new(indicator: impl Into<String>, periods: (usize, usize)) -> Self {
+        Self {
+            indicator: indicator.into(),
+            periods,
+            thresholds: None,
+            compare: false,
+        }
+    }
+
+    pub fn with_thresholds(mut self, low: f64, high: f64) -> Self {
+        self.thresholds = Some((low, high));
+        self
+    }
+
+    pub fn with_compare(mut self) -> Self {
+        self.compare = true;
+        self
+    }
+
+    fn build(&self, period: usize) -> TaResult<IndicatorState> {
+        Ok(serde_json::from_value(
+            json!({ "type": self.indicator, "period": period }),
+        )?)
+    }
+
+    /// Condition comparing the indicator with its smallest and largest periods, as
+    /// `random_leaf` would build it.
+    fn sample(&self, operator: Operator) -> TaResult<Condition> {
+        let (low, high) = self.periods;
+        if self.compare {
+            return Ok(Condition::Indicator {
+                left: Box::new(self.build(low)?),
+                right: Box::new(self.build(high)?),
+                operator,
+            });
+        }
+        Ok(Condition::Value {
+            indicator: Box::new(self.build(high)?),
+            value: OutputType::Single(self.thresholds.map_or(0.0, |(low, _)| low)),
+            operator,
+        })
+    }
+
+    fn period(&self, rng: &mut Rng) -> usize {
+        self.periods.0 + rng.below(self.periods.1 - self.periods.0 + 1)
+    }
+
+    fn threshold(&self, rng: &mut Rng) -> Option<f64> {
+        self.thresholds
+            .map(|(low, high)| round(rng.range(low, high)))
+    }
+}
+
+/// Building blocks of the trees generated by the genetic search.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Palette {
+    pub genes: Vec<Gene>,
+    pub operators: Vec<Operator>,
+    /// Actions at the leaves of the trees.
+    pub actions: Vec<Action>,
+}
+
+impl Default for Palette {
+    /// RSI levels and simple moving average crossovers.
+    fn default() -> Self {
+        Self {
+            genes: vec![
+                Gene::new("Rsi", (5, 30)).with_thresholds(20.0, 80.0),
+                Gene::new("Sma", (3, 50)).with_compare(),
+            ],
+            operators: vec![
+                Operator::GreaterThan,
+                Operator::LessThan,
+                Operator::CrossOver(None),
+                Operator::CrossUnder(None),
+            ],
+            actions: vec![Action::Buy, Action::Sell, Action::Hold],
+        }
+    }
+}
+
+impl Palette {
+    /// Checks that the palette can build a tree: every list is non-empty and every gene has a
+    /// valid period range, a sorted threshold range, a way to be compared and builds a valid
+    /// condition.
+    pub fn validate(&self) -> TaResult<()> {
+        let invalid = |message: String| Err(StrategyError::Configuration(message).into());
+        if self.genes.is_empty() || self.operators.is_empty() || self.actions.is_empty() {
+            return invalid("palette needs genes, operators and actions".into());
+        }
+        for gene in &self.genes {
+            let (low, high) = gene.periods;
+            if low == 0 || low > high {
+                return invalid(format!(
+                    "periods {:?} of gene {}",
+                    gene.periods, gene.indicator
+                ));
+            }
+            if gene
+                .thresholds
+                .is_some_and(|(low, high)| low.is_nan() || high.is_nan() || low > high)
+            {
+                return invalid(format!("thresholds of gene {}", gene.indicator));
+            }
+            if gene.thresholds.is_none() && !gene.compare {
+                return invalid(format!("gene {} can not be compared", gene.indicator));
+            }
+            gene.sample(self.operators[0].clone())?.validate()?;
+        }
+        Ok(())
+    }
+
+    fn gene_of(&self, indicator: &IndicatorState) -> TaResult<Option<&Gene>> {
+        let value = serde_json::to_value(indicator)?;
+        Ok(self
+            .genes
+            .iter()
+            .find(|gene| value["type"] == gene.indicator.as_str()))
+    }
+}
+
+/// Settings of a `GeneticSearch`.
+///
+/// Every field has a default, so partial JSON objects deserialize into a complete
+/// configuration.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct GeneticConfig {
+    pub population: usize,
+    pub generations: usize,
+    /// Number of individuals competing in every tournament selection.
+    pub tournament: usize,
+    /// Number of best individuals copied unchanged into the next generation.
+    pub elitism: usize,
+    /// Probability that a child is bred by crossover rather than copied from a parent.
+    pub crossover_rate: f64,
+    /// Probability that a child is mutated.
+    pub mutation_rate: f64,
+    /// Maximum nesting of `If` nodes.
+    pub max_depth: usize,
+    /// Maximum nesting of `And`, `Or` and `Not` conditions.
+    pub max_condition_depth: usize,
+    /// Fitness subtracted for every node and condition of a tree.
+    pub complexity_penalty: f64,
+    pub seed: u64,
+}
+
+impl Default for GeneticConfig {
+    fn default() -> Self {
+        Self {
+            population: 40,
+            generations: 20,
+            tournament: 3,
+            elitism: 2,
+            crossover_rate: 0.7,
+            mutation_rate: 0.3,
+            max_depth: 3,
+            max_condition_depth: 2,
+            complexity_penalty: 0.0,
+            seed: 0,
+        }
+    }
+}
+
+impl GeneticConfig {
+    pub fn with_population(mut self, population: usize) -> Self {
+        self.population = population;
+        self
+    }
+
+    pub fn with_generations(mut self, generations: usize) -> Self {
+        self.generations = generations;
+        self
+    }
+
+    pub fn with_complexity_penalty(mut self, complexity_penalty: f64) -> Self {
+        self.complexity_penalty = complexity_penalty;
+        self
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    pub fn validate(&self) -> TaResult<()> {
+        let rate = |rate: f64| (0.0..=1.0).contains(&rate);
+        if self.population == 0
+            || self.tournament == 0
+            || self.max_depth == 0
+            || self.elitism > self.population
+            || !rate(self.crossover_rate)
+            || !rate(self.mutation_rate)
+            || self.complexity_penalty.is_nan()
+            || self.complexity_penalty < 0.0
+        {
+            return Err(StrategyError::Configuration(format!("genetic search {self:?}")).into());
+        }
+        Ok(())
+    }
+}
+
+/// A tree of the population and its fitness.
+///
+/// Scores and fitnesses are `None` when they are infinite or undefined. The population is
+/// sorted before they are stored, so a profit factor without losses still ranks first.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Individual {
+    pub strategy: StrategyNode,
+    /// Score of the objective minus the complexity penalty.
+    pub fitness: Option<f64>,
+    /// Score of the objective.
+    pub score: Option<f64>,
+    /// Number of nodes and conditions of the tree.
+    pub complexity: usize,
+    pub metrics: Metrics,
+}
+
+/// Fitness of a generation.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct GenerationStats {
+    pub generation: usize,
+    /// Fitness of the fittest individual.
+    pub best: Option<f64>,
+    /// Mean fitness of the individuals with a finite fitness, `None` when there are none.
+    pub mean: Option<f64>,
+}
+
+/// Result of a `GeneticSearch`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct GeneticResult {
+    /// Last generation, fittest first.
+    pub population: Vec<Individual>,
+    pub history: Vec<GenerationStats>,
+}
+
+impl GeneticResult {
+    pub fn best(&self) -> Option<&Individual> {
+        self.population.first()
+    }
+}
+
+/// Evolutionary search over strategy trees.
+///
+/// The trees are `If` nodes with `Action` leaves, whose conditions combine comparisons of the
+/// palette's indicators with `And`, `Or` and `Not`. Every generation is bred from the previous
+/// one by tournament selection, subtree crossover and mutation of periods, thresholds,
+/// operators, actions and whole subtrees. Every tree passes `validate()`: offspring that do
+/// not, or that exceed the depth limits, are replaced by their parent.
+///
+/// All randomness comes from the seed of the configuration; fitness is evaluated in parallel
+/// on `threads` threads without affecting the result.
+#[derive(Debug, Clone)]
+pub struct GeneticSearch {
+    pub palette: Palette,
+    pub config: GeneticConfig,
+    pub backtest: Backtest,
+    pub objective: Objective,
+    /// Number of bars in a year, used by the annualized metrics.
+    pub periods_per_year: f64,
+    pub threads: usize,
+}
+
+impl GeneticSearch {
+    pub fn new(palette: Palette, config: GeneticConfig) -> Self {
+        Self {
+            palette,
+            config,
+            backtest: Backtest::default(),
+            objective: Objective::default(),
+            periods_per_year: 252.0,
+            threads: thread::available_parallelism().map_or(1, NonZeroUsize::get),
+        }
+    }
+
+    pub fn with_backtest(mut self, backtest: Backtest) -> Self {
+        self.backtest = backtest;
+        self
+    }
+
+    pub fn with_objective(mut self, objective: Objective) -> Self {
+        self.objective = objective;
+        self
+    }
+
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    pub fn run(&self, bars: &[TimedBar]) -> TaResult<GeneticResult> {
+        self.palette.validate()?;
+        self.config.validate()?;
+        let mut rng = Rng::new(self.config.seed);
+        let mut trees = Vec::with_capacity(self.config.population);
+        let attempts = self.config.population.saturating_mul(MAX_ATTEMPTS);
+        for _ in 0..attempts {
+            if trees.len() == self.config.population {
+                break;
+            }
+            let tree = self.random_node(&mut rng, 0)?;
+            if tree.validate().is_ok() {
+                trees.push(tree);
+            }
+        }
+        if trees.len() < self.config.population {
+            return Err(StrategyError::Configuration(format!(
+                "palette built {} valid trees out of {attempts} attempts",
+                trees.len()
+            ))
+            .into());
+        }
+        let mut population = self.evaluate(trees, bars)?;
+        let mut history = vec![stats(0, &population)];
+        for generation in 1..=self.config.generations {
+            let mut children: Vec<StrategyNode> = population
+                .iter()
+                .take(self.config.elitism)
+                .map(|individual| individual.strategy.clone())
+                .collect();
+            while children.len() < self.config.population {
+                let parent = self.select(&population, &mut rng);
+                let mut child = parent.clone();
+                if rng.chance(self.config.crossover_rate) {
+                    let other = self.select(&population, &mut rng);
+                    child = self.crossover(parent, other, &mut rng);
+                }
+                if rng.chance(self.config.mutation_rate) {
+                    child = self.mutate(&child, &mut rng)?;
+                }
+                children.push(child);
+            }
+            population = self.evaluate(children, bars)?;
+            history.push(stats(generation, &population));
+        }
+        Ok(GeneticResult {
+            population,
+            history,
+        })
+    }
+
+    /// Backtests the trees and sorts them fittest first, keeping the order of equally fit
+    /// trees.
+    fn evaluate(&self, trees: Vec<StrategyNode>, bars: &[TimedBar]) -> TaResult<Vec<Individual>> {
+        let evaluated = parallel_map(&trees, self.threads, |tree| -> TaResult<_> {
+            let report = self.backtest.run_node(tree.clone(), bars)?;
+            let metrics = report.metrics(self.periods_per_year);
+            Ok((self.objective.score(&report, &metrics), metrics))
+        });
+        let mut population = trees
+            .into_iter()
+            .zip(evaluated)
+            .map(|(strategy, evaluated)| {
+                let (score, metrics) = evaluated?;
+                let complexity = complexity(&strategy);
+                let fitness = score - self.config.complexity_penalty * complexity as f64;
+                let individual = Individual {
+                    fitness: finite(fitness),
+                    strategy,
+                    score: finite(score),
+                    complexity,
+                    metrics,
+                };
+                Ok((fitness, individual))
+            })
+            .collect::<TaResult<Vec<_>>>()?;
+        population.sort_by(|(a, _), (b, _)| b.total_cmp(a));
+        Ok(population
+            .into_iter()
+            .map(|(_, individual)| individual)
+            .collect())
+    }
+
+    fn select<'a>(&self, population: &'a [Individual], rng: &mut Rng) -> &'a StrategyNode {
+        let mut best = rng.below(population.len());
+        for _ in 1..self.config.tournament {
+            // The population is sorted, so the lower index is the fitter one.
+            best = best.min(rng.below(population.len()));
+        }
+        &population[best].strategy
+    }
+
+    fn random_node(&self, rng: &mut Rng, depth: usize) -> TaResult<StrategyNode> {
+        if depth > 0 && (depth >= self.config.max_depth || rng.chance(0.4)) {
+            return Ok(StrategyNode::Action(self.random_action(rng)));
+        }
+        Ok(StrategyNode::If {
+            condition: self.random_condition(rng, 0)?,
+            then_branch: Box::new(self.random_node(rng, depth + 1)?),
+            else_branch: Some(Box::new(self.random_node(rng, depth + 1)?)),
+        })
+    }
+
+    fn random_action(&self, rng: &mut Rng) -> Action {
+        self.palette.actions[rng.below(self.palette.actions.len())]
+    }
+
+    fn random_operator(&self, rng: &mut Rng) -> Operator {
+        self.palette.operators[rng.below(self.palette.operators.len())].clone()
+    }
+
+    fn random_condition(&self, rng: &mut Rng, depth: usize) -> TaResult<Condition> {
+        if depth >= self.config.max_condition_depth || rng.chance(0.6) {
+            return self.random_leaf(rng);
+        }
+        Ok(match rng.below(5) {
+            0 => Condition::Not(Box::new(self.random_condition(rng, depth + 1)?)),
+            1 | 2 => Condition::And(vec![
+                self.random_condition(rng, depth + 1)?,
+                self.random_condition(rng, depth + 1)?,
+            ]),
+            _ => Condition::Or(vec![
+                self.random_condition(rng, depth + 1)?,
+                self.random_condition(rng, depth + 1)?,
+            ]),
+        })
+    }
+
+    fn random_leaf(&self, rng: &mut Rng) -> TaResult<Condition> {
+        let gene = &self.palette.genes[rng.below(self.palette.genes.len())];
+        let operator = self.random_operator(rng);
+        let compare = gene.compare && (gene.thresholds.is_none() || rng.chance(0.5));
+        if compare {
+            return Ok(Condition::Indicator {
+                left: Box::new(gene.build(gene.period(rng))?),
+                right: Box::new(gene.build(gene.period(rng))?),
+                operator,
+            });
+        }
+        Ok(Condition::Value {
+            indicator: Box::new(gene.build(gene.period(rng))?),
+            value: OutputType::Single(gene.threshold(rng).unwrap_or_default()),
+            operator,
+        })
+    }
+
+    /// Replaces a random node or condition of `parent` with a random one of `other`.
+    fn crossover(
+        &self,
+        parent: &StrategyNode,
+        other: &StrategyNode,
+        rng: &mut Rng,
+    ) -> StrategyNode {
+        let mut child = parent.clone();
+        if rng.chance(0.5) {
+            let donor = node_at(other, rng.below(count_nodes(other))).cloned();
+            let target = node_at_mut(&mut child, rng.below(count_nodes(parent)));
+            if let (Some(target), Some(donor)) = (target, donor) {
+                *target = donor;
+            }
+        } else {
+            let (count, donors) = (count_conditions(parent), count_conditions(other));
+            if count > 0 && donors > 0 {
+                let donor = condition_at(other, rng.below(donors)).cloned();
+                let target = condition_at_mut(&mut child, rng.below(count));
+                if let (Some(target), Some(donor)) = (target, donor) {
+                    *target = donor;
+                }
+            }
+        }
+        self.or_parent(child, parent)
+    }
+
+    fn mutate(&self, parent: &StrategyNode, rng: &mut Rng) -> TaResult<StrategyNode> {
+        let mut child = parent.clone();
+        match rng.below(4) {
+            0 => {
+                let index = rng.below(count_nodes(&child));
+                let depth = node_depth(&child, index);
+                if let Some(node) = node_at_mut(&mut child, index) {
+                    *node = self.random_node(rng, depth.max(1))?;
+                }
+            }
+            1 => {
+                let mut actions = Vec::new();
+                collect_actions(&mut child, &mut actions);
+                if !actions.is_empty() {
+                    let index = rng.below(actions.len());
+                    *actions[index] = self.random_action(rng);
+                }
+            }
+            _ => {
+                let mut leaves = Vec::new();
+                collect_leaves(&mut child, &mut leaves);
+                if !leaves.is_empty() {
+                    let index = rng.below(leaves.len());
+                    self.mutate_leaf(leaves.swap_remove(index), rng)?;
+                }
+            }
+        }
+        Ok(self.or_parent(child, parent))
+    }
+
+    /// Changes the period, threshold or operator of a comparison.
+    fn mutate_leaf(&self, leaf: &mut Condition, rng: &mut Rng) -> TaResult<()> {
+        let choice = rng.below(3);
+        match leaf {
+            Condition::Value {
+                indicator,
+                value,
+                operator,
+            } => match choice {
+                0 => self.mutate_period(indicator, rng)?,
+                1 => {
+                    let thresholds = self
+                        .palette
+                        .gene_of(indicator)?
+                        .and_then(|gene| gene.thresholds);
+                    if let (Some((low, high)), OutputType::Single(current)) = (thresholds, &value) {
+                        let step = rng.normal() * (high - low) * 0.1;
+                        *value = OutputType::Single(round((current + step).clamp(low, high)));
+                    }
+                }
+                _ => *operator = self.random_operator(rng),
+            },
+            Condition::Indicator {
+                left,
+                right,
+                operator,
+            } => match choice {
+                0 => self.mutate_period(left, rng)?,
+                1 => self.mutate_period(right, rng)?,
+                _ => *operator = self.random_operator(rng),
+            },
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn mutate_period(&self, indicator: &mut IndicatorState, rng: &mut Rng) -> TaResult<()> {
+        if let Some(gene) = self.palette.gene_of(indicator)? {
+            *indicator = gene.build(gene.period(rng))?;
+        }
+        Ok(())
+    }
+
+    /// Keeps `child` if it fits the depth limits and validates, and `parent` otherwise.
+    fn or_parent(&self, child: StrategyNode, parent: &StrategyNode) -> StrategyNode {
+        let fits = depth(&child) <= self.config.max_depth
+            && max_condition_depth(&child) <= self.config.max_condition_depth
+            && child.validate().is_ok();
+        if fits { child } else { parent.clone() }
+    }
+}
+
+fn round(value: f64) -> f64 {
+    (value * 100.0).round() / 100.0
+}
+
+fn stats(generation: usize, population: &[Individual]) -> GenerationStats {
+    let fitnesses: Vec<f64> = population
+        .iter()
+        .filter_map(|individual| individual.fitness)
+        .collect();
+    GenerationStats {
+        generation,
+        best: population.first().and_then(|individual| individual.fitness),
+        mean: if fitnesses.is_empty() {
+            None
+        } else {
+            Some(fitnesses.iter().sum::<f64>() / fitnesses.len() as f64)
+        },
+    }
+}
+
+/// Branches of a node, for the `If` and `Action` trees built by the search.
+fn children(node: &StrategyNode) -> Vec<&StrategyNode> {
+    match node {
+        StrategyNode::If {
+            then_branch,
+            else_branch,
+            ..
+        } => std::iter::once(then_branch.as_ref())
+            .chain(else_branch.as_deref())
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn count_nodes(node: &StrategyNode) -> usize {
+    1 + children(node).into_iter().map(count_nodes).sum::<usize>()
+}
+
+/// Nesting of `If` nodes.
+fn depth(node: &StrategyNode) -> usize {
+    match node {
+        StrategyNode::If { .. } => 1 + children(node).into_iter().map(depth).max().unwrap_or(0),
+        _ => 0,
+    }
+}
+
+/// Depth at which the pre-order `index`-th node sits.
+fn node_depth(node: &StrategyNode, index: usize) -> usize {
+    fn walk(node: &StrategyNode, index: &mut usize, depth: usize) -> Option<usize> {
+        if *index == 0 {
+            return Some(depth);
+        }
+        *index -= 1;
+        children(node)
+            .into_iter()
+            .find_map(|child| walk(child, index, depth + 1))
+    }
+    walk(node, &mut { index }, 0).unwrap_or(0)
+}
+
+fn node_at(node: &StrategyNode, index: usize) -> Option<&StrategyNode> {
+    fn walk<'a>(node: &'a StrategyNode, index: &mut usize) -> Option<&'a StrategyNode> {
+        if *index == 0 {
+            return Some(node);
+        }
+        *index -= 1;
+        children(node)
+            .into_iter()
+            .find_map(|child| walk(child, index))
+    }
+    walk(node, &mut { index })
+}
+
+fn node_at_mut(node: &mut StrategyNode, index: usize) -> Option<&mut StrategyNode> {
+    fn walk<'a>(node: &'a mut StrategyNode, index: &mut usize) -> Option<&'a mut StrategyNode> {
+        if *index == 0 {
+            return Some(node);
+        }
+        *index -= 1;
+        match node {
+            StrategyNode::If {
+                then_branch,
+                else_branch,
+                ..
+            } => {
+                if let Some(found) = walk(then_branch, index) {
+                    return Some(found);
+                }
+                else_branch
+                    .as_deref_mut()
+                    .and_then(|node| walk(node, index))
+            }
+            _ => None,
+        }
+    }
+    walk(node, &mut { index })
+}
+
+/// Members of a junction or negation.
+fn operands(condition: &Condition) -> Vec<&Condition> {
+    match condition {
+        Condition::And(members) | Condition::Or(members) => members.iter().collect(),
+        Condition::Not(inner) => vec![inner.as_ref()],
+        _ => Vec::new(),
+    }
+}
+
+fn condition_size(condition: &Condition) -> usize {
+    1 + operands(condition)
+        .into_iter()
+        .map(condition_size)
+        .sum::<usize>()
+}
+
+fn condition_depth(condition: &Condition) -> usize {
+    operands(condition)
+        .into_iter()
+        .map(|operand| 1 + condition_depth(operand))
+        .max()
+        .unwrap_or(0)
+}
+
+/// Conditions of the `If` nodes of a tree, in pre-order.
+fn conditions(node: &StrategyNode) -> Vec<&Condition> {
+    let mut found = Vec::new();
+    if let StrategyNode::If { condition, .. } = node {
+        found.push(condition);
+    }
+    for child in children(node) {
+        found.extend(conditions(child));
+    }
+    found
+}
+
+fn count_conditions(node: &StrategyNode) -> usize {
+    conditions(node).into_iter().map(condition_size).sum()
+}
+
+fn max_condition_depth(node: &StrategyNode) -> usize {
+    conditions(node)
+        .into_iter()
+        .map(condition_depth)
+        .max()
+        .unwrap_or(0)
+}
+
+/// Total number of nodes and conditions of a tree.
+fn complexity(node: &StrategyNode) -> usize {
+    count_nodes(node) + count_conditions(node)
+}
+
+fn condition_at(node: &StrategyNode, index: usize) -> Option<&Condition> {
+    fn walk<'a>(condition: &'a Condition, index: &mut usize) -> Option<&'a Condition> {
+        if *index == 0 {
+            return Some(condition);
+        }
+        *index -= 1;
+        operands(condition)
+            .into_iter()
+            .find_map(|operand| walk(operand, index))
+    }
+    let mut index = index;
+    conditions(node)
+        .into_iter()
+        .find_map(|condition| walk(condition, &mut index))
+}
+
+fn condition_at_mut(node: &mut StrategyNode, index: usize) -> Option<&mut Condition> {
+    fn walk<'a>(condition: &'a mut Condition, index: &mut usize) -> Option<&'a mut Condition> {
+        if *index == 0 {
+            return Some(condition);
+        }
+        *index -= 1;
+        match condition {
+            Condition::And(members) | Condition::Or(members) => {
+                members.iter_mut().find_map(|member| walk(member, index))
+            }
+            Condition::Not(inner) => walk(inner, index),
+            _ => None,
+        }
+    }
+    fn nodes<'a>(node: &'a mut StrategyNode, index: &mut usize) -> Option<&'a mut Condition> {
+        match node {
+            StrategyNode::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                if let Some(found) = walk(condition, index) {
+                    return Some(found);
+                }
+                if let Some(found) = nodes(then_branch, index) {
+                    return Some(found);
+                }
+                else_branch
+                    .as_deref_mut()
+                    .and_then(|node| nodes(node, index))
+            }
+            _ => None,
+        }
+    }
+    nodes(node, &mut { index })
+}
+
+fn collect_actions<'a>(node: &'a mut StrategyNode, actions: &mut Vec<&'a mut Action>) {
+    match node {
+        StrategyNode::Action(action) => actions.push(action),
+        StrategyNode::If {
+            then_branch,
+            else_branch,
+            ..
+        } => {
+            collect_actions(then_branch, actions);
+            if let Some(node) = else_branch {
+                collect_actions(node, actions);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn collect_leaves<'a>(node: &'a mut StrategyNode, leaves: &mut Vec<&'a mut Condition>) {
+    fn walk<'a>(condition: &'a mut Condition, leaves: &mut Vec<&'a mut Condition>) {
+        match condition {
+            Condition::And(members) | Condition::Or(members) => {
+                members.iter_mut().for_each(|member| walk(member, leaves))
+            }
+            Condition::Not(inner) => walk(inner, leaves),
+            leaf => leaves.push(leaf),
+        }
+    }
+    if let StrategyNode::If {
+        condition,
+        then_branch,
+        else_branch,
+    } = node
+    {
+        walk(condition, leaves);
+        collect_leaves(then_branch, leaves);
+        if let Some(node) = else_branch {
+            collect_leaves(node, leaves);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::strategy::MarketData;
+
+    fn bars() -> Vec<TimedBar> {
+        let mut rng = Rng::new(5);
+        let mut close: f64 = 100.0;
+        (0..250)
+            .map(|i| {
+                close += (i as f64 / 11.0).sin() * 0.8 + rng.normal() * 0.5;
+                TimedBar::from(MarketData::Float(close))
+            })
+            .collect()
+    }
+
+    fn search(seed: u64) -> GeneticSearch {
+        let config = GeneticConfig::default()
+            .with_population(16)
+            .with_generations(4)
+            .with_seed(seed);
+        GeneticSearch::new(Palette::default(), config)
+    }
+
+    #[test]
+    fn test_random_trees_are_valid() -> TaResult<()> {
+        let search = search(1);
+        let mut rng = Rng::new(9);
+        for _ in 0..200 {
+            let tree = search.random_node(&mut rng, 0)?;
+            tree.validate()?;
+            assert!(depth(&tree) <= search.config.max_depth);
+            assert!(max_condition_depth(&tree) <= search.config.max_condition_depth);
+            let mutated = search.mutate(&tree, &mut rng)?;
+            mutated.validate()?;
+            let other = search.random_node(&mut rng, 0)?;
+            let child = search.crossover(&tree, &other, &mut rng);
+            child.validate()?;
+            assert!(depth(&child) <= search.config.max_depth);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_search_is_reproducible() -> TaResult<()> {
+        let bars = bars();
+        let first = search(42).with_threads(1).run(&bars)?;
+        assert_eq!(first.population.len(), 16);
+        assert_eq!(first.history.len(), 5);
+        for individual in &first.population {
+            individual.strategy.validate()?;
+        }
+        assert!(
+            first
+                .population
+                .windows(2)
+                .all(|pair| pair[0].fitness >= pair[1].fitness)
+        );
+        // Elitism keeps the best fitness from getting worse.
+        assert!(
+            first
+                .history
+                .windows(2)
+                .all(|pair| pair[1].best >= pair[0].best)
+        );
+        assert_eq!(search(42).with_threads(4).run(&bars)?, first);
+        assert_ne!(search(43).run(&bars)?, first);
+        Ok(())
+    }
+
+    #[test]
+    fn test_complexity_penalty() -> TaResult<()> {
+        let bars = bars();
+        let penalty = 5.0;
+        let mut penalized = search(7);
+        penalized.config = penalized.config.with_complexity_penalty(penalty);
+        let result = penalized.run(&bars)?;
+        for individual in &result.population {
+            assert_eq!(individual.complexity, complexity(&individual.strategy));
+            let expected = individual
+                .score
+                .map(|score| score - penalty * individual.complexity as f64);
+            assert_eq!(individual.fitness, expected);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_palette() {
+        let mut palette = Palette::default();
+        palette
+            .genes
+            .push(Gene::new("Rsi", (0, 10)).with_thresholds(30.0, 70.0));
+        assert!(palette.validate().is_err());
+        assert!(
+            Palette {
+                actions: Vec::new(),
+                ..Palette::default()
+            }
+            .validate()
+            .is_err()
+        );
+        assert!(
+            Palette {
+                genes: vec![Gene::new("Sma", (2, 5))],
+                ..Palette::default()
+            }
+            .validate()
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_palette_of_invalid_conditions() {
+        let palette = Palette {
+            genes: vec![Gene::new("None", (1, 5)).with_thresholds(0.0, 1.0)],
+            ..Palette::default()
+        };
+        assert!(palette.validate().is_err());
+        assert!(
+            GeneticSearch::new(palette, GeneticConfig::default())
+                .run(&[])
+                .is_err()
+        );
+    }
+}
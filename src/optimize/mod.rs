@@ -0,0 +1,20 @@
+//! Tuning of strategy parameters against historical bars.
+//!
+//! A `ParameterSpace` marks the tunable fields of a strategy template, and `Search` builds,
+//! backtests and ranks the strategies of a grid or of a seeded random sample of the space.
+//! `WalkForward` repeats the search on successive windows and validates the best parameters
+//! out of sample. `GeneticSearch` evolves whole strategy trees instead of tuning a template.
+
+pub mod genetic;
+pub mod search;
+pub mod space;
+pub mod walk_forward;
+
+pub use genetic::{
+    Gene, GenerationStats, GeneticConfig, GeneticResult, GeneticSearch, Individual, Palette,
+};
+pub use search::{Candidate, Objective, Search, SearchResult};
+pub use space::{Parameter, ParameterKind, ParameterSpace};
+pub use walk_forward::{
+    Fold, FoldWindow, ParameterStability, SearchMethod, WalkForward, WalkForwardReport, WindowMode,
+};
@@ -0,0 +1,350 @@
+use std::{collections::BTreeMap, num::NonZeroUsize, thread};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    backtest::{Backtest, BacktestReport, TimedBar},
+    error::TaResult,
+    metrics::{Metrics, finite},
+    optimize::space::ParameterSpace,
+    strategy::StrategyNode,
+};
+
+/// Metric candidates are ranked by. Higher scores rank first, except for `MaxDrawdown` which
+/// is negated so that shallower drawdowns rank first.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Objective {
+    #[default]
+    NetProfit,
+    TotalReturn,
+    Sharpe,
+    Sortino,
+    Calmar,
+    ProfitFactor,
+    Expectancy,
+    WinRate,
+    MaxDrawdown,
+}
+
+impl Objective {
+    /// Scores a backtest, given its report and metrics. Undefined scores, such as the ratios
+    /// of a run without trades, are `-inf` and a profit factor without losses is `+inf`, so
+    /// that they rank last and first.
+    pub fn score(&self, report: &BacktestReport, metrics: &Metrics) -> f64 {
+        let score = match self {
+            Objective::NetProfit => report.net_profit(),
+            Objective::TotalReturn => metrics.total_return.unwrap_or(f64::NAN),
+            Objective::Sharpe => metrics.sharpe.unwrap_or(f64::NAN),
+            Objective::Sortino => metrics.sortino.unwrap_or(f64::NAN),
+            Objective::Calmar => metrics.calmar.unwrap_or(f64::NAN),
+            Objective::ProfitFactor => metrics.profit_factor.unwrap_or(f64::INFINITY),
+            Objective::Expectancy => metrics.expectancy.unwrap_or(f64::NAN),
+            Objective::WinRate => metrics.win_rate,
+            Objective::MaxDrawdown => -metrics.max_drawdown.unwrap_or(f64::NAN),
+        };
+        if score.is_nan() {
+            f64::NEG_INFINITY
+        } else {
+            score
+        }
+    }
+}
+
+/// One evaluated combination of parameter values.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Candidate {
+    /// Values in the order of the parameters of the space.
+    pub values: Vec<f64>,
+    /// Values keyed by parameter name.
+    pub parameters: BTreeMap<String, f64>,
+    /// Score of the objective, `None` when it is infinite or undefined. Candidates are ranked
+    /// before their score is stored, so a profit factor without losses still ranks first.
+    pub score: Option<f64>,
+    pub metrics: Metrics,
+}
+
+/// Candidates of a search, best first.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SearchResult {
+    pub objective: Objective,
+    pub candidates: Vec<Candidate>,
+}
+
+impl SearchResult {
+    pub fn best(&self) -> Option<&Candidate> {
+        self.candidates.first()
+    }
+}
+
+/// Grid and random search over a `ParameterSpace`.
+///
+/// Every candidate is instantiated from the template, backtested over the same bars and
+/// scored by the objective. Candidates are spread over `threads` worker threads; the result
+/// does not depend on the number of threads, and candidates with equal scores keep the order
+/// in which they were generated.
+#[derive(Debug, Clone)]
+pub struct Search {
+    pub space: ParameterSpace,
+    pub backtest: Backtest,
+    pub objective: Objective,
+    /// Number of bars in a year, used by the annualized metrics.
+    pub periods_per_year: f64,
+    pub threads: usize,
+}
+
+impl Search {
+    pub fn new(space: ParameterSpace) -> Self {
+        Self {
+            space,
+            backtest: Backtest::default(),
+            objective: Objective::default(),
+            periods_per_year: 252.0,
+            threads: thread::available_parallelism().map_or(1, NonZeroUsize::get),
+        }
+    }
+
+    pub fn with_backtest(mut self, backtest: Backtest) -> Self {
+        self.backtest = backtest;
+        self
+    }
+
+    pub fn with_objective(mut self, objective: Objective) -> Self {
+        self.objective = objective;
+        self
+    }
+
+    pub fn with_periods_per_year(mut self, periods_per_year: f64) -> Self {
+        self.periods_per_year = periods_per_year;
+        self
+    }
+
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    /// Evaluates every combination of the space.
+    pub fn grid(&self, bars: &[TimedBar]) -> TaResult<SearchResult> {
+        self.space.validate()?;
+        self.rank(self.space.grid(), bars)
+    }
+
+    /// Evaluates `count` combinations drawn with the given seed. The same combination may be
+    /// drawn more than once.
+    pub fn random(&self, bars: &[TimedBar], count: usize, seed: u64) -> TaResult<SearchResult> {
+        self.space.validate()?;
+        self.rank(self.space.sample(count, seed), bars)
+    }
+
+    /// Backtests one strategy and scores it.
+    pub fn evaluate(&self, strategy: StrategyNode, bars: &[TimedBar]) -> TaResult<(f64, Metrics)> {
+        let report = self.backtest.run_node(strategy, bars)?;
+        let metrics = report.metrics(self.periods_per_year);
+        Ok((self.objective.score(&report, &metrics), metrics))
+    }
+
+    /// Evaluates `combinations` on the worker threads and sorts them best first. Fails with
+    /// the error of the first failing combination.
+    pub fn rank(&self, combinations: Vec<Vec<f64>>, bars: &[TimedBar]) -> TaResult<SearchResult> {
+        let evaluated = parallel_map(&combinations, self.threads, |values| {
+            self.candidate(values, bars)
+        });
+        let mut scored = evaluated.into_iter().collect::<TaResult<Vec<_>>>()?;
+        scored.sort_by(|(a, _), (b, _)| b.total_cmp(a));
+        Ok(SearchResult {
+            objective: self.objective,
+            candidates: scored.into_iter().map(|(_, candidate)| candidate).collect(),
+        })
+    }
+
+    /// Evaluates a combination, returning its ranking score along with the candidate.
+    fn candidate(&self, values: &[f64], bars: &[TimedBar]) -> TaResult<(f64, Candidate)> {
+        let strategy = self.space.instantiate(values)?;
+        let (score, metrics) = self.evaluate(strategy, bars)?;
+        let candidate = Candidate {
+            values: values.to_vec(),
+            parameters: self.space.named(values),
+            score: finite(score),
+            metrics,
+        };
+        Ok((score, candidate))
+    }
+}
+
+/// Applies `f` to every item on up to `threads` scoped threads, keeping the order of the
+/// items.
+pub(crate) fn parallel_map<T: Sync, R: Send>(
+    items: &[T],
+    threads: usize,
+    f: impl Fn(&T) -> R + Sync,
+) -> Vec<R> {
+    let chunk = items.len().div_ceil(threads.max(1)).max(1);
+    let f = &f;
+    thread::scope(|scope| {
+        let workers: Vec<_> = items
+            .chunks(chunk)
+            .map(|chunk| scope.spawn(move || chunk.iter().map(f).collect::<Vec<_>>()))
+            .collect();
+        workers
+            .into_iter()
+            .flat_map(|worker| worker.join().expect("search worker panicked"))
+            .collect()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(not(feature = "js"))]
+    use crate::Indicator;
+    #[cfg(feature = "js")]
+    use crate::indicators::indicator::Indicator;
+
+    use super::*;
+    use crate::error::TaError;
+    use crate::optimize::{Parameter, ParameterKind};
+    use crate::strategy::{Action, Condition, MarketData, StrategyError};
+    use crate::types::OutputType;
+
+    const PERIOD: &str = "/Timeout/action/If/condition/Value/indicator/period";
+    const BUY: &str = "/Timeout/action/If/condition/Value/value";
+    const COOLDOWN: &str = "/Timeout/cooldown";
+
+    fn template() -> TaResult<StrategyNode> {
+        Ok(StrategyNode::Timeout {
+            cooldown: 0,
+            remaining: 0,
+            action: Box::new(StrategyNode::If {
+                condition: Condition::greater_than(Indicator::sma(5)?, OutputType::Single(100.0)),
+                then_branch: Box::new(StrategyNode::Action(Action::Buy)),
+                else_branch: Some(Box::new(StrategyNode::Action(Action::Sell))),
+            }),
+        })
+    }
+
+    fn space() -> TaResult<ParameterSpace> {
+        Ok(ParameterSpace::new(&template()?)?
+            .with_parameter(Parameter::range(
+                "period",
+                PERIOD,
+                ParameterKind::Period,
+                2.0,
+                10.0,
+                2.0,
+            ))
+            .with_parameter(Parameter::new(
+                "buy",
+                BUY,
+                ParameterKind::Threshold,
+                vec![98.0, 100.0, 102.0],
+            ))
+            .with_parameter(Parameter::new(
+                "cooldown",
+                COOLDOWN,
+                ParameterKind::Cooldown,
+                vec![0.0, 3.0],
+            )))
+    }
+
+    fn bars() -> Vec<TimedBar> {
+        (0..300)
+            .map(|i| {
+                let close = 100.0 + 8.0 * (i as f64 / 7.0).sin() + 0.02 * i as f64;
+                TimedBar::from(MarketData::Float(close))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_space() -> TaResult<()> {
+        let space = space()?;
+        assert_eq!(space.parameters()[0].values, vec![2.0, 4.0, 6.0, 8.0, 10.0]);
+        assert_eq!(space.size(), 30);
+        let grid = space.grid();
+        assert_eq!(grid.len(), 30);
+        assert_eq!(grid[0], vec![2.0, 98.0, 0.0]);
+        assert_eq!(grid[1], vec![2.0, 98.0, 3.0]);
+
+        let strategy = space.instantiate(&[8.0, 102.0, 3.0])?;
+        let expected = StrategyNode::Timeout {
+            cooldown: 3,
+            remaining: 0,
+            action: Box::new(StrategyNode::If {
+                condition: Condition::greater_than(Indicator::sma(8)?, OutputType::Single(102.0)),
+                then_branch: Box::new(StrategyNode::Action(Action::Buy)),
+                else_branch: Some(Box::new(StrategyNode::Action(Action::Sell))),
+            }),
+        };
+        assert_eq!(strategy, expected);
+
+        assert!(space.instantiate(&[2.5, 100.0, 0.0]).is_err());
+        assert!(space.instantiate(&[0.0, 100.0, 0.0]).is_err());
+        let missing = space.with_parameter(Parameter::new(
+            "sell",
+            "/Timeout/action/If/else_branch/If",
+            ParameterKind::Threshold,
+            vec![1.0],
+        ));
+        assert!(matches!(
+            missing.validate(),
+            Err(TaError::Strategy(StrategyError::Configuration(_)))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_grid_search_ranks_deterministically() -> TaResult<()> {
+        let bars = bars();
+        let single = Search::new(space()?).with_threads(1).grid(&bars)?;
+        let parallel = Search::new(space()?).with_threads(4).grid(&bars)?;
+        assert_eq!(single, parallel);
+        assert_eq!(single.candidates.len(), 30);
+        assert!(
+            single
+                .candidates
+                .windows(2)
+                .all(|pair| pair[0].score >= pair[1].score)
+        );
+
+        let best = single.best().unwrap();
+        let strategy = space()?.instantiate(&best.values)?;
+        let (score, _) = Search::new(space()?).evaluate(strategy, &bars)?;
+        assert_eq!(finite(score), best.score);
+        assert_eq!(best.parameters["period"], best.values[0]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_random_search_is_seeded() -> TaResult<()> {
+        let bars = bars();
+        let search = Search::new(space()?).with_objective(Objective::Sharpe);
+        let first = search.random(&bars, 8, 11)?;
+        assert_eq!(first.candidates.len(), 8);
+        assert_eq!(search.with_threads(3).random(&bars, 8, 11)?, first);
+        let other = Search::new(space()?).random(&bars, 8, 12)?;
+        let values = |result: &SearchResult| {
+            let mut values: Vec<_> = result.candidates.iter().map(|c| c.values.clone()).collect();
+            values.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            values
+        };
+        assert_ne!(values(&first), values(&other));
+        Ok(())
+    }
+
+    #[test]
+    fn test_infinite_scores_serialize() -> TaResult<()> {
+        let rising: Vec<TimedBar> = (0..60)
+            .map(|i| TimedBar::from(MarketData::Float(95.0 + i as f64)))
+            .collect();
+        let result = Search::new(space()?)
+            .with_objective(Objective::ProfitFactor)
+            .grid(&rising)?;
+        let best = result.best().unwrap();
+        assert_eq!((best.score, best.metrics.profit_factor), (None, None));
+        let json = serde_json::to_string(&result)?;
+        let read: SearchResult = serde_json::from_str(&json)?;
+        assert_eq!(read.candidates.len(), result.candidates.len());
+        assert_eq!(read.best().unwrap().score, None);
+        assert_eq!(read.best().unwrap().values, best.values);
+        Ok(())
+    }
+}
@@ -0,0 +1,199 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+use crate::{
+    error::TaResult,
+    random::Rng,
+    strategy::{StrategyError, StrategyNode},
+};
+
+/// What a parameter of a `ParameterSpace` changes in the strategy template.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParameterKind {
+    /// The period of an indicator, such as `/If/condition/Value/indicator/period`.
+    Period,
+    /// The value of a `Condition::Value`, such as `/If/condition/Value/value`.
+    Threshold,
+    /// The cooldown of a `Timeout` node, such as `/Timeout/cooldown`.
+    Cooldown,
+}
+
+/// A tunable field of a strategy template and the values it is searched over.
+///
+/// The field is addressed by a JSON pointer into the serialized template.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Parameter {
+    pub name: String,
+    pub pointer: String,
+    pub kind: ParameterKind,
+    pub values: Vec<f64>,
+}
+
+impl Parameter {
+    pub fn new(
+        name: impl Into<String>,
+        pointer: impl Into<String>,
+        kind: ParameterKind,
+        values: Vec<f64>,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            pointer: pointer.into(),
+            kind,
+            values,
+        }
+    }
+
+    /// Searches the values from `start` to `end`, both included, in increments of `step`.
+    pub fn range(
+        name: impl Into<String>,
+        pointer: impl Into<String>,
+        kind: ParameterKind,
+        start: f64,
+        end: f64,
+        step: f64,
+    ) -> Self {
+        let mut values = Vec::new();
+        if step > 0.0 {
+            let steps = ((end - start) / step + 1e-9).floor().max(-1.0) as i64;
+            values.extend((0..=steps).map(|i| start + step * i as f64));
+        }
+        Self::new(name, pointer, kind, values)
+    }
+
+    fn encode(&self, value: f64) -> TaResult<Value> {
+        match self.kind {
+            ParameterKind::Threshold if value.is_finite() => Ok(json!({ "Single": value })),
+            ParameterKind::Period | ParameterKind::Cooldown
+                if value >= 0.0 && value.fract() == 0.0 =>
+            {
+                Ok(json!(value as u64))
+            }
+            _ => Err(StrategyError::Configuration(format!(
+                "{} is not a valid {:?} for parameter '{}'",
+                value, self.kind, self.name
+            ))
+            .into()),
+        }
+    }
+}
+
+/// A strategy template together with the parameters to search.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParameterSpace {
+    template: Value,
+    parameters: Vec<Parameter>,
+}
+
+impl ParameterSpace {
+    pub fn new(template: &StrategyNode) -> TaResult<Self> {
+        Ok(Self {
+            template: serde_json::to_value(template)?,
+            parameters: Vec::new(),
+        })
+    }
+
+    pub fn with_parameter(mut self, parameter: Parameter) -> Self {
+        self.parameters.push(parameter);
+        self
+    }
+
+    pub fn parameters(&self) -> &[Parameter] {
+        &self.parameters
+    }
+
+    /// Checks that every parameter has values and points at a field of the template.
+    pub fn validate(&self) -> TaResult<()> {
+        for parameter in &self.parameters {
+            if parameter.values.is_empty() {
+                return Err(StrategyError::Configuration(format!(
+                    "parameter '{}' has no values",
+                    parameter.name
+                ))
+                .into());
+            }
+            if self.template.pointer(&parameter.pointer).is_none() {
+                return Err(StrategyError::Configuration(format!(
+                    "parameter '{}' points at missing field '{}'",
+                    parameter.name, parameter.pointer
+                ))
+                .into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Number of combinations in the grid.
+    pub fn size(&self) -> usize {
+        self.parameters.iter().map(|p| p.values.len()).product()
+    }
+
+    /// Every combination of values, with the last parameter changing fastest.
+    pub fn grid(&self) -> Vec<Vec<f64>> {
+        let mut combinations = vec![Vec::with_capacity(self.parameters.len())];
+        for parameter in &self.parameters {
+            combinations = combinations
+                .into_iter()
+                .flat_map(|combination| {
+                    parameter.values.iter().map(move |&value| {
+                        let mut combination = combination.clone();
+                        combination.push(value);
+                        combination
+                    })
+                })
+                .collect();
+        }
+        combinations
+    }
+
+    /// Draws `count` combinations, each value picked uniformly among the values of its
+    /// parameter. The same seed always draws the same combinations.
+    pub fn sample(&self, count: usize, seed: u64) -> Vec<Vec<f64>> {
+        let mut rng = Rng::new(seed);
+        (0..count)
+            .map(|_| {
+                self.parameters
+                    .iter()
+                    .map(|p| p.values[rng.below(p.values.len())])
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Names the values of a combination after their parameters.
+    pub fn named(&self, values: &[f64]) -> BTreeMap<String, f64> {
+        self.parameters
+            .iter()
+            .zip(values)
+            .map(|(parameter, &value)| (parameter.name.clone(), value))
+            .collect()
+    }
+
+    /// Builds the strategy for one combination of values, given in parameter order, and
+    /// validates it.
+    pub fn instantiate(&self, values: &[f64]) -> TaResult<StrategyNode> {
+        if values.len() != self.parameters.len() {
+            return Err(StrategyError::Configuration(format!(
+                "{} values for {} parameters",
+                values.len(),
+                self.parameters.len()
+            ))
+            .into());
+        }
+        let mut document = self.template.clone();
+        for (parameter, &value) in self.parameters.iter().zip(values) {
+            let field = document.pointer_mut(&parameter.pointer).ok_or_else(|| {
+                StrategyError::Configuration(format!(
+                    "parameter '{}' points at missing field '{}'",
+                    parameter.name, parameter.pointer
+                ))
+            })?;
+            *field = parameter.encode(value)?;
+        }
+        let strategy: StrategyNode = serde_json::from_value(document)?;
+        strategy.validate()?;
+        Ok(strategy)
+    }
+}
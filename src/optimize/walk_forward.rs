@@ -0,0 +1,393 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    backtest::{TimedBar, Trade},
+    error::TaResult,
+    metrics::{Metrics, TradeRecord, finite},
+    optimize::search::{Search, SearchResult},
+    strategy::{StrategyError, strat::Strategy},
+    traits::Period,
+};
+
+/// How the in-sample window moves from one fold to the next.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WindowMode {
+    /// The in-sample window keeps its length and slides forward.
+    #[default]
+    Rolling,
+    /// The in-sample window always starts at the first bar and grows.
+    Anchored,
+}
+
+/// How the parameters are optimized on every in-sample window.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SearchMethod {
+    #[default]
+    Grid,
+    /// Random search drawing `count` combinations. Fold `i` uses the seed `seed + i`.
+    Random { count: usize, seed: u64 },
+}
+
+/// Bar ranges of one fold, as `[start, end)` indices into the series.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FoldWindow {
+    pub in_sample: (usize, usize),
+    pub out_of_sample: (usize, usize),
+}
+
+/// Result of one fold of a walk-forward analysis.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Fold {
+    pub window: FoldWindow,
+    /// Best parameter values of the in-sample window.
+    pub values: Vec<f64>,
+    pub parameters: BTreeMap<String, f64>,
+    /// Scores of the objective, `None` when infinite or undefined.
+    pub in_sample_score: Option<f64>,
+    pub out_of_sample_score: Option<f64>,
+    pub in_sample_metrics: Metrics,
+    pub out_of_sample_metrics: Metrics,
+}
+
+/// How much the best value of a parameter moved between folds.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ParameterStability {
+    pub name: String,
+    /// Best value of every fold.
+    pub values: Vec<f64>,
+    pub mean: f64,
+    pub std_dev: f64,
+    pub min: f64,
+    pub max: f64,
+    /// Number of folds whose best value differs from the previous fold.
+    pub changes: usize,
+}
+
+impl ParameterStability {
+    pub fn new(name: impl Into<String>, values: Vec<f64>) -> Self {
+        let count = values.len().max(1) as f64;
+        let mean = values.iter().sum::<f64>() / count;
+        let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / count;
+        Self {
+            name: name.into(),
+            mean,
+            std_dev: variance.sqrt(),
+            min: values.iter().copied().fold(f64::INFINITY, f64::min),
+            max: values.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+            changes: values.windows(2).filter(|pair| pair[0] != pair[1]).count(),
+            values,
+        }
+    }
+}
+
+/// Result of a walk-forward analysis.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct WalkForwardReport {
+    pub folds: Vec<Fold>,
+    /// Out-of-sample equity of every fold chained together, each fold starting from the
+    /// equity the previous one ended with. One entry per out-of-sample bar.
+    pub equity: Vec<f64>,
+    /// Out-of-sample trades of every fold, with indices into the whole series.
+    pub trades: Vec<Trade>,
+    /// Metrics of the stitched equity and trades.
+    pub metrics: Metrics,
+    pub stability: Vec<ParameterStability>,
+    /// Average annualized out-of-sample return over the average annualized in-sample return.
+    /// `None` when the in-sample return is not positive or a fold has no annualized return.
+    pub efficiency: Option<f64>,
+}
+
+/// Walk-forward analysis: optimizes the parameters on an in-sample window, trades the best
+/// ones on the following out-of-sample window and moves both windows forward by the
+/// out-of-sample length until the series is exhausted.
+///
+/// The strategy of every out-of-sample window is warmed up on the last in-sample bars, so
+/// its trades are not delayed by the indicator periods.
+#[derive(Debug, Clone)]
+pub struct WalkForward {
+    pub search: Search,
+    pub method: SearchMethod,
+    pub mode: WindowMode,
+    /// Number of bars of the (first) in-sample window.
+    pub in_sample: usize,
+    /// Number of bars of every out-of-sample window. The last one may be shorter.
+    pub out_of_sample: usize,
+}
+
+impl WalkForward {
+    pub fn new(search: Search, in_sample: usize, out_of_sample: usize) -> Self {
+        Self {
+            search,
+            method: SearchMethod::default(),
+            mode: WindowMode::default(),
+            in_sample,
+            out_of_sample,
+        }
+    }
+
+    pub fn with_method(mut self, method: SearchMethod) -> Self {
+        self.method = method;
+        self
+    }
+
+    pub fn with_mode(mut self, mode: WindowMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Splits a series of `len` bars into folds.
+    pub fn windows(&self, len: usize) -> TaResult<Vec<FoldWindow>> {
+        if self.in_sample == 0 || self.out_of_sample == 0 {
+            return Err(StrategyError::Configuration(format!(
+                "walk-forward windows of {} and {} bars",
+                self.in_sample, self.out_of_sample
+            ))
+            .into());
+        }
+        let mut windows = Vec::new();
+        let mut end = self.in_sample;
+        while end < len {
+            let start = match self.mode {
+                WindowMode::Rolling => end - self.in_sample,
+                WindowMode::Anchored => 0,
+            };
+            let out_end = (end + self.out_of_sample).min(len);
+            windows.push(FoldWindow {
+                in_sample: (start, end),
+                out_of_sample: (end, out_end),
+            });
+            end = out_end;
+        }
+        if windows.is_empty() {
+            return Err(StrategyError::Configuration(format!(
+                "{len} bars leave no out-of-sample window after {} in-sample bars",
+                self.in_sample
+            ))
+            .into());
+        }
+        Ok(windows)
+    }
+
+    pub fn run(&self, bars: &[TimedBar]) -> TaResult<WalkForwardReport> {
+        let mut folds = Vec::new();
+        let mut equity = Vec::new();
+        let mut trades = Vec::new();
+        let initial_capital = self.search.backtest.config.initial_capital;
+        let mut scale = 1.0;
+        for (index, window) in self.windows(bars.len())?.into_iter().enumerate() {
+            let (is_start, is_end) = window.in_sample;
+            let (oos_start, oos_end) = window.out_of_sample;
+            let in_sample = &bars[is_start..is_end];
+            let result = self.optimize(in_sample, index as u64)?;
+            let Some(best) = result.best() else {
+                return Err(StrategyError::EmptyIterator("walk-forward candidates".into()).into());
+            };
+
+            let node = self.search.space.instantiate(&best.values)?;
+            let warm_up = node.period().min(in_sample.len());
+            let mut strategy = Strategy::new(node);
+            for bar in &in_sample[in_sample.len() - warm_up..] {
+                match bar.timestamp {
+                    Some(timestamp) => strategy.evaluate_at(&bar.data, timestamp)?,
+                    None => strategy.evaluate(&bar.data)?,
+                };
+            }
+            let report = self
+                .search
+                .backtest
+                .run(&mut strategy, &bars[oos_start..oos_end])?;
+            let metrics = report.metrics(self.search.periods_per_year);
+            let score = self.search.objective.score(&report, &metrics);
+
+            equity.extend(report.equity.iter().map(|value| value * scale));
+            scale *= report.final_equity / initial_capital;
+            trades.extend(report.trades.into_iter().map(|mut trade| {
+                trade.entry_index += oos_start;
+                trade.exit_index += oos_start;
+                trade
+            }));
+            folds.push(Fold {
+                window,
+                values: best.values.clone(),
+                parameters: best.parameters.clone(),
+                in_sample_score: best.score,
+                out_of_sample_score: finite(score),
+                in_sample_metrics: best.metrics,
+                out_of_sample_metrics: metrics,
+            });
+        }
+
+        let records: Vec<TradeRecord> = trades.iter().map(TradeRecord::from).collect();
+        let mut curve = Vec::with_capacity(equity.len() + 1);
+        curve.push(initial_capital);
+        curve.extend(&equity);
+        let metrics = Metrics::compute(&curve, &records, self.search.periods_per_year);
+        let stability = self
+            .search
+            .space
+            .parameters()
+            .iter()
+            .enumerate()
+            .map(|(i, parameter)| {
+                ParameterStability::new(
+                    parameter.name.clone(),
+                    folds.iter().map(|fold| fold.values[i]).collect(),
+                )
+            })
+            .collect();
+        let efficiency = self.efficiency(&folds);
+        Ok(WalkForwardReport {
+            folds,
+            equity,
+            trades,
+            metrics,
+            stability,
+            efficiency,
+        })
+    }
+
+    fn optimize(&self, bars: &[TimedBar], fold: u64) -> TaResult<SearchResult> {
+        match self.method {
+            SearchMethod::Grid => self.search.grid(bars),
+            SearchMethod::Random { count, seed } => {
+                self.search.random(bars, count, seed.wrapping_add(fold))
+            }
+        }
+    }
+
+    fn efficiency(&self, folds: &[Fold]) -> Option<f64> {
+        if folds.is_empty() {
+            return None;
+        }
+        let count = folds.len() as f64;
+        let in_sample = folds
+            .iter()
+            .map(|fold| fold.in_sample_metrics.annualized_return)
+            .sum::<Option<f64>>()?
+            / count;
+        let out_of_sample = folds
+            .iter()
+            .map(|fold| fold.out_of_sample_metrics.annualized_return)
+            .sum::<Option<f64>>()?
+            / count;
+        (in_sample > 0.0).then(|| out_of_sample / in_sample)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(not(feature = "js"))]
+    use crate::Indicator;
+    #[cfg(feature = "js")]
+    use crate::indicators::indicator::Indicator;
+
+    use super::*;
+    use crate::optimize::{Parameter, ParameterKind, ParameterSpace};
+    use crate::strategy::{Action, Condition, MarketData, StrategyNode};
+    use crate::types::OutputType;
+
+    fn search() -> TaResult<Search> {
+        let template = StrategyNode::If {
+            condition: Condition::greater_than(Indicator::sma(5)?, OutputType::Single(100.0)),
+            then_branch: Box::new(StrategyNode::Action(Action::Buy)),
+            else_branch: Some(Box::new(StrategyNode::Action(Action::Sell))),
+        };
+        let space = ParameterSpace::new(&template)?
+            .with_parameter(Parameter::new(
+                "period",
+                "/If/condition/Value/indicator/period",
+                ParameterKind::Period,
+                vec![3.0, 6.0, 12.0],
+            ))
+            .with_parameter(Parameter::new(
+                "threshold",
+                "/If/condition/Value/value",
+                ParameterKind::Threshold,
+                vec![99.0, 101.0],
+            ));
+        Ok(Search::new(space).with_threads(2))
+    }
+
+    fn bars() -> Vec<TimedBar> {
+        (0..260)
+            .map(|i| {
+                let close = 100.0 + 6.0 * (i as f64 / 9.0).sin() + 0.01 * i as f64;
+                TimedBar::from(MarketData::Float(close))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_windows() -> TaResult<()> {
+        let rolling = WalkForward::new(search()?, 100, 40).windows(230)?;
+        let expected = [
+            ((0, 100), (100, 140)),
+            ((40, 140), (140, 180)),
+            ((80, 180), (180, 220)),
+            ((120, 220), (220, 230)),
+        ];
+        assert_eq!(rolling.len(), expected.len());
+        for (window, (in_sample, out_of_sample)) in rolling.iter().zip(expected) {
+            assert_eq!(
+                (window.in_sample, window.out_of_sample),
+                (in_sample, out_of_sample)
+            );
+        }
+
+        let anchored = WalkForward::new(search()?, 100, 40)
+            .with_mode(WindowMode::Anchored)
+            .windows(230)?;
+        assert!(anchored.iter().all(|window| window.in_sample.0 == 0));
+        assert_eq!(anchored[3].in_sample, (0, 220));
+
+        assert!(WalkForward::new(search()?, 100, 40).windows(100).is_err());
+        assert!(WalkForward::new(search()?, 100, 0).windows(300).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_run_stitches_out_of_sample() -> TaResult<()> {
+        let bars = bars();
+        let walk = WalkForward::new(search()?, 100, 50);
+        let report = walk.run(&bars)?;
+        assert_eq!(report.folds.len(), 4);
+        assert_eq!(report.equity.len(), 160);
+        assert!(
+            report
+                .trades
+                .iter()
+                .all(|trade| trade.entry_index >= 100 && trade.exit_index < bars.len())
+        );
+        // The first out-of-sample bar already trades thanks to the warm-up.
+        assert!(report.trades.iter().any(|trade| trade.entry_index == 100));
+
+        assert_eq!(report.stability.len(), 2);
+        assert_eq!(report.stability[0].values.len(), 4);
+        assert_eq!(
+            report.stability[0].values,
+            report
+                .folds
+                .iter()
+                .map(|fold| fold.values[0])
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(walk.run(&bars)?, report);
+
+        let random = walk
+            .clone()
+            .with_method(SearchMethod::Random { count: 4, seed: 3 });
+        assert_eq!(random.run(&bars)?, random.run(&bars)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_stability() {
+        let stability = ParameterStability::new("period", vec![10.0, 10.0, 14.0, 10.0]);
+        assert_eq!(stability.mean, 11.0);
+        assert_eq!(stability.std_dev, 3.0f64.sqrt());
+        assert_eq!((stability.min, stability.max), (10.0, 14.0));
+        assert_eq!(stability.changes, 2);
+    }
+}
@@ -14,3 +14,42 @@ pub enum Action {
     /// Submit a sell order or signal with strong conviction.
     StrongSell,
 }
+
+impl Action {
+    /// Returns the conviction level of the action: `0` for `Hold`, `1` for
+    /// `Buy`/`Sell` and `2` for `StrongBuy`/`StrongSell`.
+    pub fn conviction(&self) -> u8 {
+        match self {
+            Action::StrongBuy | Action::StrongSell => 2,
+            Action::Buy | Action::Sell => 1,
+            Action::Hold => 0,
+        }
+    }
+
+    /// Returns the signed score of the action, from `+2` for `StrongBuy` down to `-2`
+    /// for `StrongSell`, with `Hold` scoring `0`.
+    pub fn score(&self) -> i8 {
+        match self {
+            Action::StrongBuy => 2,
+            Action::Buy => 1,
+            Action::Hold => 0,
+            Action::Sell => -1,
+            Action::StrongSell => -2,
+        }
+    }
+
+    /// Returns the action whose score is nearest to `score`.
+    pub fn from_score(score: f64) -> Action {
+        if score >= 1.5 {
+            Action::StrongBuy
+        } else if score >= 0.5 {
+            Action::Buy
+        } else if score > -0.5 {
+            Action::Hold
+        } else if score > -1.5 {
+            Action::Sell
+        } else {
+            Action::StrongSell
+        }
+    }
+}
@@ -1,14 +1,20 @@
 use crate::{
     error::{TaError, TaResult},
-    strategy::{wrapper::IndicatorState, MarketData, StrategyError},
+    strategy::{
+        context::Context,
+        position::Side,
+        session::{in_window, Session}, registry::IndicatorRegistry, trace::ConditionTrace,
+        wrapper::IndicatorState, MarketData, StrategyError,
+    },
     traits::{IndicatorTrait, Period, Reset},
-    types::OutputType,
+    types::{OutputShape, OutputType},
 };
 #[cfg(feature = "js")]
 use crate::indicators::indicator::Indicator;
 #[cfg(not(feature = "js"))]
 use crate::Indicator;
 
+use chrono::{DateTime, Datelike, FixedOffset, NaiveTime, Utc, Weekday};
 use serde::{Deserialize, Serialize};
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
@@ -22,6 +28,91 @@ pub enum Operator {
     CrossUnder(#[serde(skip)] Option<OutputType>),
 }
 
+/// Operand of a `Condition::Compare`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum Operand {
+    /// Latest output of the indicator registered under this id in the enclosing
+    /// `StrategyNode::Shared` node.
+    Indicator(String),
+    /// Output of the indicator registered under `indicator` in the timeframe named
+    /// `timeframe` of the enclosing `StrategyNode::MultiTimeframe` node, as of the last
+    /// closed bucket, or including the forming bucket if `forming` is set.
+    Timeframe {
+        timeframe: String,
+        indicator: String,
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        forming: bool,
+    },
+    /// Latest output of the indicator registered under `indicator` for `symbol` in the
+    /// enclosing `MultiAssetStrategy`.
+    Symbol { symbol: String, indicator: String },
+    /// Constant or market data field.
+    Value(OutputType),
+}
+
+impl Operand {
+    /// Resolves the operand against market data and the shared indicators of `context`.
+    pub fn resolve(&self, data: &MarketData, context: &Context) -> TaResult<OutputType> {
+        match self {
+            Operand::Indicator(id) => context
+                .indicators
+                .ok_or_else(|| StrategyError::UnknownIndicator(id.clone()))?
+                .prev(id),
+            Operand::Timeframe {
+                timeframe,
+                indicator,
+                forming,
+            } => {
+                let timeframe = context
+                    .timeframes
+                    .and_then(|timeframes| timeframes.get(timeframe))
+                    .ok_or_else(|| StrategyError::UnknownTimeframe(timeframe.clone()))?;
+                if *forming {
+                    timeframe.peek(indicator)
+                } else {
+                    timeframe.prev(indicator)
+                }
+            }
+            Operand::Symbol { symbol, indicator } => context
+                .symbols
+                .and_then(|symbols| symbols.get(symbol))
+                .ok_or_else(|| StrategyError::UnknownSymbol(symbol.clone()))?
+                .prev(indicator),
+            Operand::Value(value) => Ok(value.resolve(data)?),
+        }
+    }
+
+    fn output_shape(&self, context: &Context) -> TaResult<OutputShape> {
+        match self {
+            Operand::Indicator(id) => context
+                .indicators
+                .and_then(|registry| registry.get(id))
+                .map(|indicator| indicator.output_shape())
+                .ok_or_else(|| StrategyError::UnknownIndicator(id.clone()).into()),
+            Operand::Timeframe {
+                timeframe,
+                indicator,
+                ..
+            } => context
+                .timeframes
+                .and_then(|timeframes| timeframes.get(timeframe))
+                .ok_or_else(|| StrategyError::UnknownTimeframe(timeframe.clone()))?
+                .indicators
+                .get(indicator)
+                .map(|indicator| indicator.output_shape())
+                .ok_or_else(|| StrategyError::UnknownIndicator(indicator.clone()).into()),
+            Operand::Symbol { symbol, indicator } => context
+                .symbols
+                .and_then(|symbols| symbols.get(symbol))
+                .ok_or_else(|| StrategyError::UnknownSymbol(symbol.clone()))?
+                .get(indicator)
+                .map(|indicator| indicator.output_shape())
+                .ok_or_else(|| StrategyError::UnknownIndicator(indicator.clone()).into()),
+            Operand::Value(value) => Ok(value.output_shape()?),
+        }
+    }
+}
+
 /// Logical conditions for strategy execution.
 /// This enum represents various conditions that can be used to control the flow of a trading strategy.
 /// It includes comparisons between indicators and values, logical operations (AND, OR, NOT), and allows for complex condition trees.
@@ -98,17 +189,47 @@ pub enum Condition {
         right: Box<IndicatorState>,
         operator: Operator,
     },
+    /// Compares two operands, which may reference shared indicators, using an operator.
+    Compare {
+        left: Operand,
+        right: Operand,
+        operator: Operator,
+    },
     /// Logical AND of multiple conditions.
     And(Vec<Condition>),
     /// Logical OR of multiple conditions.
     Or(Vec<Condition>),
     /// Logical NOT of a condition.
     Not(Box<Condition>),
+    /// Holds when the strategy's position is on the given side.
+    Position(Side),
+    /// Compares the number of bars since the position was opened to `bars`.
+    /// Never holds while flat.
+    BarsInTrade { operator: Operator, bars: usize },
+    /// Holds when the bar's time of day, shifted by `tz_offset` minutes east of UTC, lies in
+    /// `[start, end)`. Windows with `start > end` wrap around midnight. The offset must be
+    /// less than a day.
+    TimeOfDay {
+        start: NaiveTime,
+        end: NaiveTime,
+        #[serde(default)]
+        tz_offset: i32,
+    },
+    /// Holds when the bar's day of the week, in UTC, is one of the given days.
+    DayOfWeek(Vec<Weekday>),
+    /// Holds when the bar's time falls within the given market session.
+    Session(Session),
 }
 
 impl Condition {
     /// Validate the condition
     pub fn validate(&self) -> TaResult<()> {
+        self.validate_with(&Context::default())
+    }
+
+    /// Validates the condition, resolving `Operand` references against the shared indicators
+    /// and timeframes of `context`.
+    pub fn validate_with(&self, context: &Context) -> TaResult<()> {
         match self {
             Condition::ValueOnly { left, right, .. } => {
                 if left.output_shape()? != right.output_shape()? {
@@ -153,13 +274,32 @@ impl Condition {
                 }
                 Ok(())
             }
+            Condition::Compare { left, right, .. } => {
+                let (left, right) = (left.output_shape(context)?, right.output_shape(context)?);
+                if left != right {
+                    return Err(TaError::Strategy(StrategyError::IncompatibleShapes {
+                        name: "Condition::Compare".to_string(),
+                        indicator: left,
+                        value: right,
+                    }));
+                }
+                Ok(())
+            }
             Condition::And(conds) | Condition::Or(conds) => {
                 for c in conds {
-                    c.validate()?;
+                    c.validate_with(context)?;
                 }
                 Ok(())
             }
-            Condition::Not(c) => c.validate(),
+            Condition::Not(c) => c.validate_with(context),
+            Condition::TimeOfDay { tz_offset, .. } => {
+                fixed_offset(*tz_offset)?;
+                Ok(())
+            }
+            Condition::Position(_)
+            | Condition::BarsInTrade { .. }
+            | Condition::DayOfWeek(_)
+            | Condition::Session(_) => Ok(()),
         }
     }
 
@@ -178,20 +318,57 @@ impl Condition {
                 Ok(())
             }
             Condition::Not(c) => c.update(data),
-            Condition::ValueOnly { .. } => Ok(())
+            Condition::ValueOnly { .. }
+            | Condition::Compare { .. }
+            | Condition::Position(_)
+            | Condition::BarsInTrade { .. }
+            | Condition::TimeOfDay { .. }
+            | Condition::DayOfWeek(_)
+            | Condition::Session(_) => Ok(()),
         }
     }
 
     /// Evaluate the condition against market data.
     pub fn evaluate(&mut self, data: &MarketData) -> TaResult<bool> {
-        match self {
+        self.evaluate_with(data, &Context::default())
+    }
+
+    /// Evaluate the condition against market data and the strategy's `context`.
+    pub fn evaluate_with(&mut self, data: &MarketData, context: &Context) -> TaResult<bool> {
+        self.evaluate_recorded(data, context, None, &mut Vec::new(), None)
+    }
+
+    /// Evaluates the condition like `evaluate`.
+    ///
+    /// When `location` is provided this condition is identified by it and, if it holds, the
+    /// ids of the sub-conditions that held followed by its own id are appended to `fired`.
+    /// When `trace` is provided a `ConditionTrace` of this condition is pushed onto it.
+    pub(crate) fn evaluate_recorded(
+        &mut self,
+        data: &MarketData,
+        context: &Context,
+        location: Option<&str>,
+        fired: &mut Vec<String>,
+        trace: Option<&mut Vec<ConditionTrace>>,
+    ) -> TaResult<bool> {
+        let tracing = trace.is_some();
+        let mut children = tracing.then(Vec::new);
+        let mut operands = None;
+        let kind = self.kind();
+        let result = match self {
             // Condition::GreaterThan { indicator, value } => {
             //     let lhs = indicator.next(data)?;
             //     let rhs = value.resolve(data)?;
             //     Ok(lhs.gt(&rhs))
             // }
             Condition::ValueOnly { left, right, operator } => {
-                operator.evaluate(&left.resolve(data)?, &right.resolve(data)?)
+                let lhs = left.resolve(data)?;
+                let rhs = right.resolve(data)?;
+                let result = operator.evaluate(&lhs, &rhs);
+                if tracing {
+                    operands = Some((operator.clone(), lhs, rhs));
+                }
+                result
             },
             Condition::Value {
                 indicator,
@@ -200,12 +377,21 @@ impl Condition {
             } => {
                 let lhs = indicator.prev()?;
                 let rhs = value.resolve(data)?;
-                operator.evaluate(&lhs, &rhs)
+                let result = operator.evaluate(&lhs, &rhs);
+                if tracing {
+                    operands = Some((operator.clone(), lhs, rhs));
+                }
+                result
             },
             Condition::ValueInversed { value, indicator, operator } => {
                 let lhs = value.resolve(data)?;
                 let rhs = indicator.prev()?;
-                operator.evaluate(&rhs, &lhs)
+                let result = operator.evaluate(&rhs, &lhs);
+                if tracing {
+                    // Recorded in the order the operator compares them.
+                    operands = Some((operator.clone(), rhs, lhs));
+                }
+                result
             },
             Condition::Indicator {
                 left,
@@ -214,25 +400,116 @@ impl Condition {
             } => {
                 let lhs = left.prev()?;
                 let rhs = right.prev()?;
-                operator.evaluate(&lhs, &rhs)
+                let result = operator.evaluate(&lhs, &rhs);
+                if tracing {
+                    operands = Some((operator.clone(), lhs, rhs));
+                }
+                result
+            }
+            Condition::Compare {
+                left,
+                right,
+                operator,
+            } => {
+                let lhs = left.resolve(data, context)?;
+                let rhs = right.resolve(data, context)?;
+                let result = operator.evaluate(&lhs, &rhs);
+                if tracing {
+                    operands = Some((operator.clone(), lhs, rhs));
+                }
+                result
             }
             Condition::And(conds) => {
-                for c in conds.iter_mut() {
-                    if !c.evaluate(data)? {
-                        return Ok(false);
+                // Sub-conditions only count as fired if the whole conjunction holds.
+                let mut held = Vec::new();
+                let mut result = true;
+                for (index, c) in conds.iter_mut().enumerate() {
+                    let child = location.map(|location| format!("{location}.And[{index}]"));
+                    if !c.evaluate_recorded(data, context, child.as_deref(), &mut held, children.as_mut())? {
+                        result = false;
+                        break;
                     }
                 }
-                Ok(true)
+                if result {
+                    fired.append(&mut held);
+                }
+                Ok(result)
             }
             Condition::Or(conds) => {
-                for c in conds.iter_mut() {
-                    if c.evaluate(data)? {
-                        return Ok(true);
+                let mut result = false;
+                for (index, c) in conds.iter_mut().enumerate() {
+                    let child = location.map(|location| format!("{location}.Or[{index}]"));
+                    if c.evaluate_recorded(data, context, child.as_deref(), fired, children.as_mut())? {
+                        result = true;
+                        break;
                     }
                 }
-                Ok(false)
+                Ok(result)
+            }
+            Condition::Not(c) => {
+                // Sub-conditions of a negation never count as fired.
+                let child = location.map(|location| format!("{location}.Not"));
+                Ok(!c.evaluate_recorded(data, context, child.as_deref(), &mut Vec::new(), children.as_mut())?)
+            }
+            Condition::Position(side) => Ok(context.position.side == *side),
+            Condition::BarsInTrade { operator, bars } => {
+                let lhs = OutputType::Single(context.position.bars_in_trade as f64);
+                let rhs = OutputType::Single(*bars as f64);
+                let result = operator.evaluate(&lhs, &rhs)?;
+                if tracing {
+                    operands = Some((operator.clone(), lhs, rhs));
+                }
+                Ok(result && !context.position.is_flat())
+            }
+            Condition::TimeOfDay {
+                start,
+                end,
+                tz_offset,
+            } => {
+                let offset = fixed_offset(*tz_offset)?;
+                let time = timestamp(context)?.with_timezone(&offset).time();
+                Ok(in_window(time, *start, *end))
             }
-            Condition::Not(c) => Ok(!c.evaluate(data)?),
+            Condition::DayOfWeek(days) => Ok(days.contains(&timestamp(context)?.weekday())),
+            Condition::Session(session) => Ok(session.contains(timestamp(context)?.time())),
+        }?;
+        if let Some(location) = location.filter(|_| result) {
+            fired.push(location.to_string());
+        }
+        if let Some(trace) = trace {
+            let (operator, left, right) = match operands {
+                Some((operator, left, right)) => (Some(operator), Some(left), Some(right)),
+                None => (None, None, None),
+            };
+            trace.push(ConditionTrace {
+                id: location.unwrap_or_default().to_string(),
+                kind: kind.to_string(),
+                result,
+                operator,
+                left,
+                right,
+                children: children.unwrap_or_default(),
+            });
+        }
+        Ok(result)
+    }
+
+    /// Returns the name of the condition variant, as used in condition ids and traces.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Condition::ValueOnly { .. } => "ValueOnly",
+            Condition::Value { .. } => "Value",
+            Condition::ValueInversed { .. } => "ValueInversed",
+            Condition::Indicator { .. } => "Indicator",
+            Condition::Compare { .. } => "Compare",
+            Condition::And(_) => "And",
+            Condition::Or(_) => "Or",
+            Condition::Not(_) => "Not",
+            Condition::Position(_) => "Position",
+            Condition::BarsInTrade { .. } => "BarsInTrade",
+            Condition::TimeOfDay { .. } => "TimeOfDay",
+            Condition::DayOfWeek(_) => "DayOfWeek",
+            Condition::Session(_) => "Session",
         }
     }
 
@@ -246,6 +523,51 @@ impl Condition {
                 conds.iter().filter_map(|c| c.max_period()).max()
             }
             Condition::Not(cond) => cond.max_period(),
+            // Shared indicators are accounted for by the enclosing `Shared` node.
+            Condition::Compare { .. }
+            | Condition::Position(_)
+            | Condition::BarsInTrade { .. }
+            | Condition::TimeOfDay { .. }
+            | Condition::DayOfWeek(_)
+            | Condition::Session(_) => None,
+        }
+    }
+
+    /// Replaces every indicator owned by this condition with a reference to a structurally
+    /// equal indicator in `indicators`, registering it if needed.
+    pub(crate) fn into_shared(self, indicators: &mut IndicatorRegistry) -> Condition {
+        match self {
+            Condition::Value {
+                indicator,
+                value,
+                operator,
+            }
+            | Condition::ValueInversed {
+                value,
+                indicator,
+                operator,
+            } => Condition::Compare {
+                left: Operand::Indicator(indicators.register(indicator.indicator)),
+                right: Operand::Value(value),
+                operator,
+            },
+            Condition::Indicator {
+                left,
+                right,
+                operator,
+            } => Condition::Compare {
+                left: Operand::Indicator(indicators.register(left.indicator)),
+                right: Operand::Indicator(indicators.register(right.indicator)),
+                operator,
+            },
+            Condition::And(conds) => {
+                Condition::And(conds.into_iter().map(|c| c.into_shared(indicators)).collect())
+            }
+            Condition::Or(conds) => {
+                Condition::Or(conds.into_iter().map(|c| c.into_shared(indicators)).collect())
+            }
+            Condition::Not(c) => Condition::Not(Box::new(c.into_shared(indicators))),
+            other => other,
         }
     }
 
@@ -306,6 +628,27 @@ impl Condition {
     }
 }
 
+/// Returns the timestamp of the bar being evaluated, required by time based conditions.
+fn timestamp(context: &Context) -> TaResult<DateTime<Utc>> {
+    context
+        .timestamp
+        .ok_or_else(|| StrategyError::MissingTimestamp.into())
+}
+
+/// Returns the offset of `tz_offset` minutes east of UTC, which must be less than a day.
+fn fixed_offset(tz_offset: i32) -> Result<FixedOffset, StrategyError> {
+    (-1439..=1439)
+        .contains(&tz_offset)
+        .then(|| tz_offset.checked_mul(60))
+        .flatten()
+        .and_then(FixedOffset::east_opt)
+        .ok_or_else(|| {
+            StrategyError::Configuration(format!(
+                "Invalid TimeOfDay tz_offset: {tz_offset} minutes"
+            ))
+        })
+}
+
 impl Operator {
     /// Returns the operator as a string.
     pub fn as_str(&self) -> &str {
@@ -402,6 +745,16 @@ impl Reset for Condition {
                 }
             }
             Condition::Not(c) => c.reset(),
+            Condition::Position(_)
+            | Condition::TimeOfDay { .. }
+            | Condition::DayOfWeek(_)
+            | Condition::Session(_) => {}
+            Condition::Compare { operator, .. } | Condition::BarsInTrade { operator, .. } => match operator {
+                Operator::CrossOver(prev_value) | Operator::CrossUnder(prev_value) => {
+                    *prev_value = None; // Reset crossover state
+                }
+                _ => {}
+            },
         }
     }
 }
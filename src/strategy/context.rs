@@ -0,0 +1,34 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+
+use crate::strategy::position::Position;
+use crate::strategy::registry::IndicatorRegistry;
+use crate::strategy::timeframe::Timeframe;
+
+/// State outside of the strategy tree that nodes and conditions can observe while evaluating.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Context<'a> {
+    /// Position currently held by the strategy, flat if none.
+    pub position: Position,
+    /// Indicators of the closest enclosing `StrategyNode::Shared` node.
+    pub indicators: Option<&'a IndicatorRegistry>,
+    /// Timeframes of the closest enclosing `StrategyNode::MultiTimeframe` node.
+    pub timeframes: Option<&'a BTreeMap<String, Timeframe>>,
+    /// Indicators of every symbol of the enclosing `MultiAssetStrategy`, keyed by symbol.
+    pub symbols: Option<&'a BTreeMap<String, IndicatorRegistry>>,
+    /// Timestamp of the bar being evaluated, required by time based conditions.
+    pub timestamp: Option<DateTime<Utc>>,
+    /// Time at which the last losing trade was closed, used by `RateLimit::AfterLoss`.
+    pub last_loss: Option<DateTime<Utc>>,
+}
+
+impl Context<'_> {
+    /// Creates a context for a bar with the given timestamp.
+    pub fn at(timestamp: DateTime<Utc>) -> Self {
+        Self {
+            timestamp: Some(timestamp),
+            ..Self::default()
+        }
+    }
+}
@@ -19,6 +19,58 @@ pub enum StrategyError {
         indicator: OutputShape,
         value: OutputShape,
     },
+    /// A `Weighted` sequence does not provide exactly one weight per child node.
+    #[error("Sequence weights mismatch: {weights} weights for {nodes} nodes")]
+    WeightsMismatch { weights: usize, nodes: usize },
+
+    /// A `StateMachine` node has no states.
+    #[error("StateMachine node must contain at least one state")]
+    EmptyStateMachine,
+
+    /// Two states of a `StateMachine` node share the same name.
+    #[error("Duplicate state '{0}'")]
+    DuplicateState(String),
+
+    /// A `StateMachine` transition targets a state that does not exist.
+    #[error("Unknown state '{0}'")]
+    UnknownState(String),
+
+    /// A condition references a shared indicator that is not registered.
+    #[error("Unknown shared indicator '{0}'")]
+    UnknownIndicator(String),
+
+    /// A condition references a timeframe that is not defined.
+    #[error("Unknown timeframe '{0}'")]
+    UnknownTimeframe(String),
+
+    /// A snapshot or condition references a symbol the strategy does not know.
+    #[error("Unknown symbol '{0}'")]
+    UnknownSymbol(String),
+
+    /// A time based condition was evaluated without a timestamp.
+    #[error("Time based condition evaluated without a timestamp")]
+    MissingTimestamp,
+
+    /// A `Percentage` sequence threshold is above 100.
+    #[error("Sequence percentage {0} is above 100")]
+    InvalidPercentage(u8),
+
+    /// A branch or state of the strategy can never be taken.
+    #[error("Unreachable: {0}")]
+    UnreachableBranch(String),
+
+    /// The sub-conditions of an `And` condition can never hold together.
+    #[error("Contradictory conditions: {0}")]
+    ContradictoryConditions(String),
+
+    /// A strategy document was written in a format version this crate cannot load.
+    #[error("Unsupported strategy file version {0}")]
+    UnsupportedVersion(u32),
+
+    /// A row of market data could not be loaded.
+    #[error("Invalid data at line {line}: {reason}")]
+    InvalidData { line: usize, reason: String },
+
     #[error("Invalid indicator period: {period}")]
     InvalidIndicatorPeriod { period: usize },
 
@@ -34,6 +86,7 @@ pub enum StrategyError {
     #[error("IO Error: {0}")]
     IO(String),
 
-    #[error("Configuration error, {0}")]
+    /// A strategy, backtest or search setting is out of range.
+    #[error("Invalid configuration: {0}")]
     Configuration(String),
 }
@@ -0,0 +1,218 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+use crate::{
+    error::TaResult,
+    strategy::{StrategyError, StrategyNode},
+};
+
+/// Version of the strategy file format written by this crate.
+///
+/// Bump it whenever the serialized form of the strategy types changes in a way older
+/// documents do not deserialize into, and register a migration from the previous version in
+/// `Migrations::default`.
+pub const STRATEGY_FILE_VERSION: u32 = 1;
+
+/// Upgrades a document of some version to the next one. Receives and returns the whole
+/// document, `{ "version": .., "strategy": .. }`; the version field is updated by the caller.
+pub type Migration = fn(Value) -> TaResult<Value>;
+
+/// A strategy tree stored together with the version of the format it was written in.
+///
+/// Serializes as `{ "version": 1, "strategy": { .. } }`. Loading goes through `Migrations`,
+/// which upgrades documents written by older versions of the crate, including bare
+/// `StrategyNode` documents from before the envelope existed (version 0).
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct StrategyFile {
+    pub version: u32,
+    pub strategy: StrategyNode,
+}
+
+impl StrategyFile {
+    /// Wraps `strategy` in a document of the current version.
+    pub fn new(strategy: StrategyNode) -> Self {
+        Self {
+            version: STRATEGY_FILE_VERSION,
+            strategy,
+        }
+    }
+
+    /// Parses a JSON document of any supported version, upgrading it with the default
+    /// migrations.
+    pub fn from_json(json: &str) -> TaResult<Self> {
+        Self::from_value(serde_json::from_str(json)?)
+    }
+
+    /// Loads a document of any supported version, upgrading it with the default migrations.
+    pub fn from_value(document: Value) -> TaResult<Self> {
+        Migrations::default().load(document)
+    }
+
+    /// Serializes the document as pretty printed JSON.
+    pub fn to_json(&self) -> TaResult<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    /// Returns the strategy tree.
+    pub fn into_strategy(self) -> StrategyNode {
+        self.strategy
+    }
+}
+
+impl From<StrategyNode> for StrategyFile {
+    fn from(strategy: StrategyNode) -> Self {
+        Self::new(strategy)
+    }
+}
+
+/// Migrations applied to documents of older versions when loading a `StrategyFile`, keyed by
+/// the version they upgrade from.
+#[derive(Debug, Clone)]
+pub struct Migrations {
+    steps: BTreeMap<u32, Migration>,
+}
+
+impl Default for Migrations {
+    /// Migrations of the formats written by previous versions of the crate.
+    fn default() -> Self {
+        Self::empty().with_migration(0, wrap_bare_strategy)
+    }
+}
+
+impl Migrations {
+    /// Creates a table without any migration, which only loads documents of the current
+    /// version.
+    pub fn empty() -> Self {
+        Self {
+            steps: BTreeMap::new(),
+        }
+    }
+
+    /// Registers `migration` to upgrade documents of version `from` to `from + 1`, replacing
+    /// any migration registered for the same version.
+    pub fn with_migration(mut self, from: u32, migration: Migration) -> Self {
+        self.steps.insert(from, migration);
+        self
+    }
+
+    /// Upgrades `document` to the current version and deserializes it.
+    ///
+    /// A document without a `version` field is treated as version 0. Fails with
+    /// `StrategyError::UnsupportedVersion` if the document is newer than this crate or no
+    /// migration is registered for one of the versions in between.
+    pub fn load(&self, mut document: Value) -> TaResult<StrategyFile> {
+        let mut version = document_version(&document)?;
+        while version < STRATEGY_FILE_VERSION {
+            let migration = self
+                .steps
+                .get(&version)
+                .ok_or(StrategyError::UnsupportedVersion(version))?;
+            document = migration(document)?;
+            version += 1;
+            document["version"] = json!(version);
+        }
+        if version > STRATEGY_FILE_VERSION {
+            return Err(StrategyError::UnsupportedVersion(version).into());
+        }
+        Ok(serde_json::from_value(document)?)
+    }
+}
+
+fn document_version(document: &Value) -> TaResult<u32> {
+    match document.get("version") {
+        None => Ok(0),
+        Some(version) => version
+            .as_u64()
+            .and_then(|version| u32::try_from(version).ok())
+            .ok_or_else(|| {
+                StrategyError::Serialization(format!("invalid version {version}")).into()
+            }),
+    }
+}
+
+/// Version 0 documents are bare serialized `StrategyNode`s.
+fn wrap_bare_strategy(document: Value) -> TaResult<Value> {
+    Ok(json!({ "version": 0, "strategy": document }))
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(not(feature = "js"))]
+    use crate::Indicator;
+    #[cfg(feature = "js")]
+    use crate::indicators::indicator::Indicator;
+
+    use super::*;
+    use crate::error::TaError;
+    use crate::strategy::{Action, Condition};
+    use crate::types::OutputType;
+
+    fn strategy() -> TaResult<StrategyNode> {
+        Ok(StrategyNode::If {
+            condition: Condition::less_than(Indicator::rsi(14)?, OutputType::Single(30.0)),
+            then_branch: Box::new(StrategyNode::Action(Action::Buy)),
+            else_branch: Some(Box::new(StrategyNode::Action(Action::Hold))),
+        })
+    }
+
+    #[test]
+    fn test_round_trip_current_version() -> TaResult<()> {
+        let file = StrategyFile::new(strategy()?);
+        let json = file.to_json()?;
+        assert!(json.contains("\"version\": 1"));
+        assert_eq!(StrategyFile::from_json(&json)?, file);
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_bare_strategy() -> TaResult<()> {
+        let json = serde_json::to_string(&strategy()?)?;
+        let file = StrategyFile::from_json(&json)?;
+        assert_eq!(file.version, STRATEGY_FILE_VERSION);
+        assert_eq!(file.strategy, strategy()?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_custom_migration() -> TaResult<()> {
+        // A hypothetical older format that named the `then_branch` field `then`.
+        fn rename_then(mut document: Value) -> TaResult<Value> {
+            let node = &mut document["strategy"]["If"];
+            if let Some(then) = node.as_object_mut().and_then(|node| node.remove("then")) {
+                node["then_branch"] = then;
+            }
+            Ok(document)
+        }
+        let mut document = serde_json::to_value(StrategyFile::new(strategy()?))?;
+        let then = document["strategy"]["If"]
+            .as_object_mut()
+            .and_then(|node| node.remove("then_branch"))
+            .unwrap();
+        document["strategy"]["If"]["then"] = then;
+        document["version"] = json!(0);
+
+        let migrations = Migrations::empty().with_migration(0, rename_then);
+        assert_eq!(migrations.load(document.clone())?.strategy, strategy()?);
+        assert!(StrategyFile::from_value(document).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_unsupported_versions() -> TaResult<()> {
+        let strategy = serde_json::to_value(strategy()?)?;
+        let newer = json!({ "version": STRATEGY_FILE_VERSION + 1, "strategy": strategy });
+        assert_eq!(
+            StrategyFile::from_value(newer),
+            Err(TaError::Strategy(StrategyError::UnsupportedVersion(
+                STRATEGY_FILE_VERSION + 1
+            )))
+        );
+        assert_eq!(
+            Migrations::empty().load(strategy),
+            Err(TaError::Strategy(StrategyError::UnsupportedVersion(0)))
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,1605 @@
+use std::fmt::Write;
+
+use chipa_lang_utils::{
+    Lang, Pair, Rule,
+    errors::{LangError, LangErrorKind, LangResult},
+};
+use chipa_ta_utils::output::Statics;
+use chrono::{NaiveTime, Timelike, Weekday};
+
+#[cfg(not(feature = "js"))]
+use crate::Indicator;
+#[cfg(feature = "js")]
+use crate::indicators::indicator::Indicator;
+use crate::{
+    preprocessing::PreprocessingStep,
+    strategy::{
+        Action, Condition, IndicatorRegistry, MachineState, Operand, RateLimit, Resample, Session,
+        Side, StrategyNode, Timeframe,
+        condition::Operator,
+        node::{SequenceMode, TieBreak},
+    },
+    types::OutputType,
+};
+
+/// Text form of strategy trees, documented in `STRATEGY_LANG.md`.
+///
+/// Only the configuration of a tree is written; runtime state such as indicator values,
+/// cooldowns or the current state of a state machine is reset when parsing.
+impl Lang for StrategyNode {
+    fn from_ct(input: &str) -> LangResult<Self> {
+        Parser::new(input)?.parse_all(Parser::node)
+    }
+
+    fn to_ct(&self) -> String {
+        let mut out = String::new();
+        write_node(&mut out, self, 0);
+        out
+    }
+
+    fn from_pair(pair: Pair<Rule>) -> LangResult<Self> {
+        Self::from_ct(pair.as_str())
+    }
+}
+
+impl Lang for Condition {
+    fn from_ct(input: &str) -> LangResult<Self> {
+        Parser::new(input)?.parse_all(Parser::condition)
+    }
+
+    fn to_ct(&self) -> String {
+        let mut out = String::new();
+        write_condition(&mut out, self);
+        out
+    }
+
+    fn from_pair(pair: Pair<Rule>) -> LangResult<Self> {
+        Self::from_ct(pair.as_str())
+    }
+}
+
+const ACTIONS: [(&str, Action); 5] = [
+    ("StrongBuy", Action::StrongBuy),
+    ("Buy", Action::Buy),
+    ("Hold", Action::Hold),
+    ("Sell", Action::Sell),
+    ("StrongSell", Action::StrongSell),
+];
+
+const SIDES: [(&str, Side); 3] = [
+    ("Flat", Side::Flat),
+    ("Long", Side::Long),
+    ("Short", Side::Short),
+];
+
+const SESSIONS: [(&str, Session); 4] = [
+    ("Sydney", Session::Sydney),
+    ("Tokyo", Session::Tokyo),
+    ("London", Session::London),
+    ("NewYork", Session::NewYork),
+];
+
+const FIELDS: [(&str, OutputType); 7] = [
+    ("open", OutputType::Open),
+    ("high", OutputType::High),
+    ("low", OutputType::Low),
+    ("close", OutputType::Close),
+    ("volume", OutputType::Volume),
+    ("true", OutputType::Static(Statics::True)),
+    ("false", OutputType::Static(Statics::False)),
+];
+
+fn name_of<T: PartialEq>(names: &[(&'static str, T)], value: &T) -> &'static str {
+    names
+        .iter()
+        .find(|(_, v)| v == value)
+        .map(|(name, _)| *name)
+        .unwrap_or_default()
+}
+
+fn write_block(out: &mut String, node: &StrategyNode, indent: usize) {
+    out.push_str("{\n");
+    write_indent(out, indent + 1);
+    write_node(out, node, indent + 1);
+    out.push('\n');
+    write_indent(out, indent);
+    out.push('}');
+}
+
+fn write_indent(out: &mut String, indent: usize) {
+    out.extend(std::iter::repeat_n("    ", indent));
+}
+
+fn write_node(out: &mut String, node: &StrategyNode, indent: usize) {
+    match node {
+        StrategyNode::Action(action) => out.push_str(name_of(&ACTIONS, action)),
+        StrategyNode::If {
+            condition,
+            then_branch,
+            else_branch,
+        } => {
+            out.push_str("if ");
+            write_condition(out, condition);
+            out.push(' ');
+            write_block(out, then_branch, indent);
+            match else_branch.as_deref() {
+                Some(node @ StrategyNode::If { .. }) => {
+                    out.push_str(" else ");
+                    write_node(out, node, indent);
+                }
+                Some(node) => {
+                    out.push_str(" else ");
+                    write_block(out, node, indent);
+                }
+                None => {}
+            }
+        }
+        StrategyNode::Sequence {
+            mode,
+            nodes,
+            tie_break,
+        } => {
+            out.push_str("sequence ");
+            match mode {
+                SequenceMode::First => out.push_str("first"),
+                SequenceMode::Last => out.push_str("last"),
+                SequenceMode::Any => out.push_str("any"),
+                SequenceMode::All => out.push_str("all"),
+                SequenceMode::Majority => out.push_str("majority"),
+                SequenceMode::Percentage(percent) => {
+                    let _ = write!(out, "percentage({percent})");
+                }
+                SequenceMode::Weighted(weights) => {
+                    out.push_str("weighted(");
+                    write_list(out, weights, |out, w| write_number(out, *w));
+                    out.push(')');
+                }
+                SequenceMode::Score {
+                    buy_threshold,
+                    sell_threshold,
+                } => {
+                    out.push_str("score(");
+                    write_number(out, *buy_threshold);
+                    out.push_str(", ");
+                    write_number(out, *sell_threshold);
+                    out.push(')');
+                }
+            }
+            match tie_break {
+                TieBreak::Hold => {}
+                TieBreak::PreferStronger => out.push_str(" tie_break prefer_stronger"),
+                TieBreak::PreferFirst => out.push_str(" tie_break prefer_first"),
+            }
+            out.push_str(" {\n");
+            for node in nodes {
+                write_indent(out, indent + 1);
+                write_node(out, node, indent + 1);
+                out.push('\n');
+            }
+            write_indent(out, indent);
+            out.push('}');
+        }
+        StrategyNode::Timeout {
+            cooldown, action, ..
+        } => {
+            let _ = write!(out, "timeout({cooldown}) ");
+            write_block(out, action, indent);
+        }
+        StrategyNode::RateLimit { limit, action, .. } => {
+            let _ = match limit {
+                RateLimit::Signals { max, window_secs } => {
+                    write!(out, "rate_limit signals({max}, {window_secs}) ")
+                }
+                RateLimit::Cooldown { secs } => write!(out, "rate_limit cooldown({secs}) "),
+                RateLimit::AfterLoss { secs } => write!(out, "rate_limit after_loss({secs}) "),
+            };
+            write_block(out, action, indent);
+        }
+        StrategyNode::Preprocess { step, then_branch } => {
+            out.push_str(match step {
+                PreprocessingStep::WaveletDenoise => "preprocess wavelet_denoise ",
+                PreprocessingStep::Normalize => "preprocess normalize ",
+            });
+            write_block(out, then_branch, indent);
+        }
+        StrategyNode::StateMachine { states, .. } => {
+            out.push_str("state_machine {\n");
+            for state in states {
+                write_indent(out, indent + 1);
+                out.push_str("state ");
+                write_string(out, &state.name);
+                out.push_str(" {\n");
+                for transition in &state.transitions {
+                    write_indent(out, indent + 2);
+                    out.push_str("on ");
+                    write_condition(out, &transition.condition);
+                    write_target(out, &transition.to, transition.action);
+                }
+                if let Some(timeout) = &state.timeout {
+                    write_indent(out, indent + 2);
+                    let _ = write!(out, "after {}", timeout.bars);
+                    write_target(out, &timeout.to, timeout.action);
+                }
+                write_indent(out, indent + 1);
+                out.push_str("}\n");
+            }
+            write_indent(out, indent);
+            out.push('}');
+        }
+        StrategyNode::Shared { indicators, node } => {
+            out.push_str("shared");
+            write_registry(out, indicators);
+            out.push(' ');
+            write_block(out, node, indent);
+        }
+        StrategyNode::MultiTimeframe { timeframes, node } => {
+            out.push_str("timeframes(");
+            write_list(out, timeframes, |out, (name, timeframe)| {
+                write_string(out, name);
+                let _ = match timeframe.resample {
+                    Resample::Seconds(secs) => write!(out, " = seconds({secs}) "),
+                    Resample::Bars(bars) => write!(out, " = bars({bars}) "),
+                };
+                write_registry(out, &timeframe.indicators);
+            });
+            out.push_str(") ");
+            write_block(out, node, indent);
+        }
+    }
+}
+
+fn write_target(out: &mut String, to: &str, action: Option<Action>) {
+    out.push_str(" -> ");
+    write_string(out, to);
+    if let Some(action) = action {
+        out.push_str(" emit ");
+        out.push_str(name_of(&ACTIONS, &action));
+    }
+    out.push('\n');
+}
+
+fn write_registry(out: &mut String, registry: &IndicatorRegistry) {
+    out.push('(');
+    write_list(out, registry.ids(), |out, id| {
+        write_string(out, id);
+        out.push_str(" = ");
+        if let Some(indicator) = registry.get(id) {
+            out.push_str(&indicator.indicator.to_ct());
+        }
+    });
+    out.push(')');
+}
+
+fn write_list<I: IntoIterator>(
+    out: &mut String,
+    items: I,
+    mut write: impl FnMut(&mut String, I::Item),
+) {
+    for (index, item) in items.into_iter().enumerate() {
+        if index > 0 {
+            out.push_str(", ");
+        }
+        write(out, item);
+    }
+}
+
+fn write_string(out: &mut String, value: &str) {
+    out.push('"');
+    for c in value.chars() {
+        if matches!(c, '"' | '\\') {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out.push('"');
+}
+
+fn write_number(out: &mut String, value: f64) {
+    // `Display` prints the shortest representation that parses back to the same value.
+    let _ = write!(out, "{value}");
+}
+
+fn write_value(out: &mut String, value: &OutputType) {
+    match value {
+        OutputType::Single(value) => write_number(out, *value),
+        OutputType::Array(values) => {
+            out.push('[');
+            write_list(out, values, |out, v| write_number(out, *v));
+            out.push(']');
+        }
+        OutputType::Custom(values) => {
+            out.push_str("custom(");
+            write_list(out, values, write_value);
+            out.push(')');
+        }
+        field => out.push_str(name_of(&FIELDS, field)),
+    }
+}
+
+fn write_operand(out: &mut String, operand: &Operand) {
+    match operand {
+        Operand::Indicator(id) => {
+            out.push_str("shared(");
+            write_string(out, id);
+            out.push(')');
+        }
+        Operand::Timeframe {
+            timeframe,
+            indicator,
+            forming,
+        } => {
+            out.push_str(if *forming { "forming(" } else { "timeframe(" });
+            write_string(out, timeframe);
+            out.push_str(", ");
+            write_string(out, indicator);
+            out.push(')');
+        }
+        Operand::Symbol { symbol, indicator } => {
+            out.push_str("symbol(");
+            write_string(out, symbol);
+            out.push_str(", ");
+            write_string(out, indicator);
+            out.push(')');
+        }
+        Operand::Value(value) => write_value(out, value),
+    }
+}
+
+fn write_comparison(
+    out: &mut String,
+    left: impl FnOnce(&mut String),
+    operator: &Operator,
+    right: impl FnOnce(&mut String),
+) {
+    left(out);
+    let _ = write!(out, " {} ", operator.as_str());
+    right(out);
+}
+
+/// Returns the operator comparing the same two terms written in the opposite order.
+fn mirrored(operator: &Operator) -> Operator {
+    match operator {
+        Operator::GreaterThan => Operator::LessThan,
+        Operator::LessThan => Operator::GreaterThan,
+        Operator::Equals => Operator::Equals,
+        Operator::GreaterThanOrEqual => Operator::LessThanOrEqual,
+        Operator::LessThanOrEqual => Operator::GreaterThanOrEqual,
+        Operator::CrossOver(previous) => Operator::CrossUnder(previous.clone()),
+        Operator::CrossUnder(previous) => Operator::CrossOver(previous.clone()),
+    }
+}
+
+fn write_time(out: &mut String, time: &NaiveTime) {
+    let format = if time.nanosecond() != 0 {
+        "%H:%M:%S%.f"
+    } else if time.second() != 0 {
+        "%H:%M:%S"
+    } else {
+        "%H:%M"
+    };
+    let _ = write!(out, "{}", time.format(format));
+}
+
+/// Returns whether the condition is written with infix `and` / `or`, and so needs
+/// parentheses when nested in another infix condition.
+fn is_infix(condition: &Condition) -> bool {
+    matches!(condition, Condition::And(c) | Condition::Or(c) if c.len() > 1)
+}
+
+fn write_nested(out: &mut String, condition: &Condition) {
+    if is_infix(condition) {
+        out.push('(');
+        write_condition(out, condition);
+        out.push(')');
+    } else {
+        write_condition(out, condition);
+    }
+}
+
+fn write_condition(out: &mut String, condition: &Condition) {
+    match condition {
+        Condition::ValueOnly {
+            left,
+            right,
+            operator,
+        } => write_comparison(
+            out,
+            |o| write_value(o, left),
+            operator,
+            |o| write_value(o, right),
+        ),
+        Condition::Value {
+            indicator,
+            value,
+            operator,
+        } => write_comparison(
+            out,
+            |o| o.push_str(&indicator.indicator.to_ct()),
+            operator,
+            |o| write_value(o, value),
+        ),
+        // Compares the indicator against the value, so the operator is mirrored to write the
+        // value first.
+        Condition::ValueInversed {
+            value,
+            indicator,
+            operator,
+        } => write_comparison(
+            out,
+            |o| write_value(o, value),
+            &mirrored(operator),
+            |o| o.push_str(&indicator.indicator.to_ct()),
+        ),
+        Condition::Indicator {
+            left,
+            right,
+            operator,
+        } => write_comparison(
+            out,
+            |o| o.push_str(&left.indicator.to_ct()),
+            operator,
+            |o| o.push_str(&right.indicator.to_ct()),
+        ),
+        Condition::Compare {
+            left,
+            right,
+            operator,
+        } => {
+            // Without a reference the comparison would read back as `ValueOnly`.
+            let explicit = matches!((left, right), (Operand::Value(_), Operand::Value(_)));
+            if explicit {
+                out.push_str("compare(");
+            }
+            write_comparison(
+                out,
+                |o| write_operand(o, left),
+                operator,
+                |o| write_operand(o, right),
+            );
+            if explicit {
+                out.push(')');
+            }
+        }
+        Condition::And(conditions) | Condition::Or(conditions) => {
+            let keyword = if matches!(condition, Condition::And(_)) {
+                "and"
+            } else {
+                "or"
+            };
+            if conditions.len() > 1 {
+                for (index, condition) in conditions.iter().enumerate() {
+                    if index > 0 {
+                        let _ = write!(out, " {keyword} ");
+                    }
+                    write_nested(out, condition);
+                }
+            } else {
+                let _ = write!(out, "{keyword}(");
+                write_list(out, conditions, write_condition);
+                out.push(')');
+            }
+        }
+        Condition::Not(condition) => {
+            out.push_str("not ");
+            write_nested(out, condition);
+        }
+        Condition::Position(side) => {
+            let _ = write!(out, "position({})", name_of(&SIDES, side));
+        }
+        Condition::BarsInTrade { operator, bars } => {
+            let _ = write!(out, "bars_in_trade {} {bars}", operator.as_str());
+        }
+        Condition::TimeOfDay {
+            start,
+            end,
+            tz_offset,
+        } => {
+            out.push_str("time_of_day(");
+            write_time(out, start);
+            out.push_str(", ");
+            write_time(out, end);
+            if *tz_offset != 0 {
+                let _ = write!(out, ", {tz_offset}");
+            }
+            out.push(')');
+        }
+        Condition::DayOfWeek(days) => {
+            out.push_str("day_of_week(");
+            write_list(out, days, |out, day| {
+                let _ = write!(out, "{day}");
+            });
+            out.push(')');
+        }
+        Condition::Session(session) => {
+            let _ = write!(out, "session({})", name_of(&SESSIONS, session));
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Number(f64),
+    Str(String),
+    Time(NaiveTime),
+    Symbol(&'static str),
+}
+
+impl Token {
+    fn describe(&self) -> String {
+        match self {
+            Token::Ident(ident) => format!("'{ident}'"),
+            Token::Number(number) => format!("number {number}"),
+            Token::Str(value) => format!("string \"{value}\""),
+            Token::Time(time) => format!("time {time}"),
+            Token::Symbol(symbol) => format!("'{symbol}'"),
+        }
+    }
+}
+
+const SYMBOLS: [&str; 14] = [
+    "->", ">=", "<=", "==", ">", "<", "(", ")", "{", "}", "[", "]", ",", "=",
+];
+
+/// Left hand or right hand side of a comparison.
+enum Term {
+    Indicator(Box<Indicator>),
+    Value(OutputType),
+    Reference(Operand),
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    /// Tokens with the byte offsets of their start and end.
+    tokens: Vec<(Token, usize, usize)>,
+    position: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(input: &'a str) -> LangResult<Self> {
+        let mut parser = Self {
+            input,
+            tokens: Vec::new(),
+            position: 0,
+        };
+        parser.lex()?;
+        Ok(parser)
+    }
+
+    fn error_at(&self, offset: usize, message: impl std::fmt::Display) -> LangError {
+        let before = &self.input[..offset.min(self.input.len())];
+        let line = before.matches('\n').count() + 1;
+        let column = before.len() - before.rfind('\n').map_or(0, |i| i + 1) + 1;
+        LangErrorKind::ParseError(format!("line {line}, column {column}: {message}")).into()
+    }
+
+    fn error(&self, message: impl std::fmt::Display) -> LangError {
+        let offset = self
+            .tokens
+            .get(self.position)
+            .map_or(self.input.len(), |(_, start, _)| *start);
+        self.error_at(offset, message)
+    }
+
+    fn lex(&mut self) -> LangResult<()> {
+        let input = self.input;
+        let bytes = input.as_bytes();
+        let mut i = 0;
+        while i < bytes.len() {
+            let c = bytes[i];
+            let start = i;
+            let token = if c.is_ascii_whitespace() {
+                i += 1;
+                continue;
+            } else if c == b'#' {
+                while i < bytes.len() && bytes[i] != b'\n' {
+                    i += 1;
+                }
+                continue;
+            } else if c == b'"' {
+                let mut value = String::new();
+                let mut chars = input[i + 1..].char_indices();
+                loop {
+                    match chars.next() {
+                        Some((offset, '"')) => {
+                            i += offset + 2;
+                            break;
+                        }
+                        Some((_, '\\')) => match chars.next() {
+                            Some((_, c)) => value.push(c),
+                            None => return Err(self.error_at(start, "unterminated string")),
+                        },
+                        Some((_, c)) => value.push(c),
+                        None => return Err(self.error_at(start, "unterminated string")),
+                    }
+                }
+                Token::Str(value)
+            } else if input[i..].starts_with("-inf") {
+                i += 4;
+                Token::Number(f64::NEG_INFINITY)
+            } else if c.is_ascii_digit()
+                || (c == b'-' && bytes.get(i + 1).is_some_and(u8::is_ascii_digit))
+            {
+                i += 1;
+                while i < bytes.len()
+                    && (bytes[i].is_ascii_alphanumeric() || matches!(bytes[i], b'.' | b':'))
+                {
+                    // Exponent signs, as in `1e-7`.
+                    if matches!(bytes[i], b'e' | b'E')
+                        && matches!(bytes.get(i + 1), Some(b'-' | b'+'))
+                    {
+                        i += 1;
+                    }
+                    i += 1;
+                }
+                let text = &input[start..i];
+                if text.contains(':') {
+                    let time = NaiveTime::parse_from_str(text, "%H:%M")
+                        .or_else(|_| NaiveTime::parse_from_str(text, "%H:%M:%S%.f"))
+                        .map_err(|_| self.error_at(start, format!("invalid time '{text}'")))?;
+                    Token::Time(time)
+                } else {
+                    let number = text
+                        .parse()
+                        .map_err(|_| self.error_at(start, format!("invalid number '{text}'")))?;
+                    Token::Number(number)
+                }
+            } else if c.is_ascii_alphabetic() || c == b'_' {
+                while i < bytes.len() && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'_') {
+                    i += 1;
+                }
+                match &input[start..i] {
+                    "inf" => Token::Number(f64::INFINITY),
+                    "NaN" => Token::Number(f64::NAN),
+                    ident => Token::Ident(ident.to_string()),
+                }
+            } else if let Some(symbol) = SYMBOLS.iter().find(|s| input[i..].starts_with(**s)) {
+                i += symbol.len();
+                Token::Symbol(symbol)
+            } else {
+                let c = input[i..].chars().next().unwrap_or_default();
+                return Err(self.error_at(start, format!("unexpected character '{c}'")));
+            };
+            self.tokens.push((token, start, i));
+        }
+        Ok(())
+    }
+
+    /// Parses the whole input with `parse`, failing on trailing tokens.
+    fn parse_all<T>(mut self, parse: impl FnOnce(&mut Self) -> LangResult<T>) -> LangResult<T> {
+        let value = parse(&mut self)?;
+        match self.peek() {
+            Some(token) => Err(self.error(format!("unexpected {}", token.describe()))),
+            None => Ok(value),
+        }
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position).map(|(token, ..)| token)
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<&Token> {
+        self.tokens
+            .get(self.position + offset)
+            .map(|(token, ..)| token)
+    }
+
+    fn next(&mut self) -> LangResult<Token> {
+        let token = self
+            .peek()
+            .cloned()
+            .ok_or_else(|| self.error("unexpected end of input"))?;
+        self.position += 1;
+        Ok(token)
+    }
+
+    fn is_symbol(&self, symbol: &str) -> bool {
+        matches!(self.peek(), Some(Token::Symbol(s)) if *s == symbol)
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Token::Ident(ident)) if ident == keyword)
+    }
+
+    fn eat_symbol(&mut self, symbol: &str) -> bool {
+        let found = self.is_symbol(symbol);
+        if found {
+            self.position += 1;
+        }
+        found
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        let found = self.is_keyword(keyword);
+        if found {
+            self.position += 1;
+        }
+        found
+    }
+
+    fn expect_symbol(&mut self, symbol: &str) -> LangResult<()> {
+        if self.eat_symbol(symbol) {
+            return Ok(());
+        }
+        Err(self.expected(&format!("'{symbol}'")))
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> LangResult<()> {
+        if self.eat_keyword(keyword) {
+            return Ok(());
+        }
+        Err(self.expected(&format!("'{keyword}'")))
+    }
+
+    fn expected(&self, what: &str) -> LangError {
+        match self.peek() {
+            Some(token) => self.error(format!("expected {what}, found {}", token.describe())),
+            None => self.error(format!("expected {what}, found end of input")),
+        }
+    }
+
+    fn ident(&mut self) -> LangResult<String> {
+        match self.peek() {
+            Some(Token::Ident(ident)) => {
+                let ident = ident.clone();
+                self.position += 1;
+                Ok(ident)
+            }
+            _ => Err(self.expected("a name")),
+        }
+    }
+
+    fn string(&mut self) -> LangResult<String> {
+        match self.peek() {
+            Some(Token::Str(value)) => {
+                let value = value.clone();
+                self.position += 1;
+                Ok(value)
+            }
+            _ => Err(self.expected("a string")),
+        }
+    }
+
+    fn number(&mut self) -> LangResult<f64> {
+        match self.peek() {
+            Some(Token::Number(number)) => {
+                let number = *number;
+                self.position += 1;
+                Ok(number)
+            }
+            _ => Err(self.expected("a number")),
+        }
+    }
+
+    fn integer<T: TryFrom<i64>>(&mut self) -> LangResult<T> {
+        let start = self.position;
+        let number = self.number()?;
+        let value = (number.fract() == 0.0 && number.abs() < 2f64.powi(53))
+            .then(|| T::try_from(number as i64).ok())
+            .flatten();
+        value.ok_or_else(|| {
+            self.position = start;
+            self.error(format!("expected an integer in range, found {number}"))
+        })
+    }
+
+    fn named<T: Copy>(&mut self, names: &[(&str, T)], what: &str) -> LangResult<T> {
+        let value = match self.peek() {
+            Some(Token::Ident(ident)) => names
+                .iter()
+                .find(|(name, _)| name == ident)
+                .map(|(_, value)| *value),
+            _ => None,
+        };
+        if value.is_some() {
+            self.position += 1;
+        }
+        value.ok_or_else(|| self.expected(what))
+    }
+
+    /// Parses `(item, item, ...)`.
+    fn list<T>(&mut self, mut item: impl FnMut(&mut Self) -> LangResult<T>) -> LangResult<Vec<T>> {
+        self.expect_symbol("(")?;
+        let mut items = Vec::new();
+        while !self.eat_symbol(")") {
+            if !items.is_empty() {
+                self.expect_symbol(",")?;
+            }
+            items.push(item(self)?);
+        }
+        Ok(items)
+    }
+
+    /// Parses `(value)`.
+    fn argument<T>(&mut self, item: impl FnOnce(&mut Self) -> LangResult<T>) -> LangResult<T> {
+        self.expect_symbol("(")?;
+        let value = item(self)?;
+        self.expect_symbol(")")?;
+        Ok(value)
+    }
+
+    fn block(&mut self) -> LangResult<Box<StrategyNode>> {
+        self.expect_symbol("{")?;
+        let node = self.node()?;
+        self.expect_symbol("}")?;
+        Ok(Box::new(node))
+    }
+
+    fn node(&mut self) -> LangResult<StrategyNode> {
+        let keyword = self.ident()?;
+        Ok(match keyword.as_str() {
+            "if" => {
+                let condition = self.condition()?;
+                let then_branch = self.block()?;
+                let else_branch = if !self.eat_keyword("else") {
+                    None
+                } else if self.is_keyword("if") {
+                    Some(Box::new(self.node()?))
+                } else {
+                    Some(self.block()?)
+                };
+                StrategyNode::If {
+                    condition,
+                    then_branch,
+                    else_branch,
+                }
+            }
+            "sequence" => {
+                let mode = self.sequence_mode()?;
+                let tie_break = if self.eat_keyword("tie_break") {
+                    match self.ident()?.as_str() {
+                        "hold" => TieBreak::Hold,
+                        "prefer_stronger" => TieBreak::PreferStronger,
+                        "prefer_first" => TieBreak::PreferFirst,
+                        other => {
+                            self.position -= 1;
+                            return Err(self.error(format!("unknown tie break '{other}'")));
+                        }
+                    }
+                } else {
+                    TieBreak::default()
+                };
+                self.expect_symbol("{")?;
+                let mut nodes = Vec::new();
+                while !self.eat_symbol("}") {
+                    nodes.push(self.node()?);
+                }
+                StrategyNode::Sequence {
+                    mode,
+                    nodes,
+                    tie_break,
+                }
+            }
+            "timeout" => StrategyNode::Timeout {
+                cooldown: self.argument(Self::integer)?,
+                remaining: 0,
+                action: self.block()?,
+            },
+            "rate_limit" => {
+                let limit = match self.ident()?.as_str() {
+                    "signals" => {
+                        self.expect_symbol("(")?;
+                        let max = self.integer()?;
+                        self.expect_symbol(",")?;
+                        let window_secs = self.integer()?;
+                        self.expect_symbol(")")?;
+                        RateLimit::Signals { max, window_secs }
+                    }
+                    "cooldown" => RateLimit::Cooldown {
+                        secs: self.argument(Self::integer)?,
+                    },
+                    "after_loss" => RateLimit::AfterLoss {
+                        secs: self.argument(Self::integer)?,
+                    },
+                    other => {
+                        self.position -= 1;
+                        return Err(self.error(format!("unknown rate limit '{other}'")));
+                    }
+                };
+                StrategyNode::RateLimit {
+                    limit,
+                    action: self.block()?,
+                    fired: Vec::new(),
+                }
+            }
+            "preprocess" => {
+                let step = match self.ident()?.as_str() {
+                    "wavelet_denoise" => PreprocessingStep::WaveletDenoise,
+                    "normalize" => PreprocessingStep::Normalize,
+                    other => {
+                        self.position -= 1;
+                        return Err(self.error(format!("unknown preprocessing step '{other}'")));
+                    }
+                };
+                StrategyNode::Preprocess {
+                    step,
+                    then_branch: self.block()?,
+                }
+            }
+            "state_machine" => {
+                self.expect_symbol("{")?;
+                let mut states = Vec::new();
+                while !self.eat_symbol("}") {
+                    states.push(self.state()?);
+                }
+                StrategyNode::StateMachine {
+                    states,
+                    current: 0,
+                    bars_in_state: 0,
+                }
+            }
+            "shared" => StrategyNode::Shared {
+                indicators: self.registry()?,
+                node: self.block()?,
+            },
+            "timeframes" => {
+                let timeframes = self.list(|parser| {
+                    let name = parser.string()?;
+                    parser.expect_symbol("=")?;
+                    let resample = match parser.ident()?.as_str() {
+                        "seconds" => Resample::Seconds(parser.argument(Self::integer)?),
+                        "bars" => Resample::Bars(parser.argument(Self::integer)?),
+                        other => {
+                            parser.position -= 1;
+                            return Err(parser.error(format!("unknown resampling '{other}'")));
+                        }
+                    };
+                    let mut timeframe = Timeframe::new(resample);
+                    timeframe.indicators = parser.registry()?;
+                    Ok((name, timeframe))
+                })?;
+                StrategyNode::MultiTimeframe {
+                    timeframes: timeframes.into_iter().collect(),
+                    node: self.block()?,
+                }
+            }
+            action => match ACTIONS.iter().find(|(name, _)| *name == action) {
+                Some((_, action)) => StrategyNode::Action(*action),
+                None => {
+                    self.position -= 1;
+                    return Err(self.error(format!("unknown node '{action}'")));
+                }
+            },
+        })
+    }
+
+    fn sequence_mode(&mut self) -> LangResult<SequenceMode> {
+        Ok(match self.ident()?.as_str() {
+            "first" => SequenceMode::First,
+            "last" => SequenceMode::Last,
+            "any" => SequenceMode::Any,
+            "all" => SequenceMode::All,
+            "majority" => SequenceMode::Majority,
+            "percentage" => SequenceMode::Percentage(self.argument(Self::integer)?),
+            "weighted" => SequenceMode::Weighted(self.list(Self::number)?),
+            "score" => {
+                self.expect_symbol("(")?;
+                let buy_threshold = self.number()?;
+                self.expect_symbol(",")?;
+                let sell_threshold = self.number()?;
+                self.expect_symbol(")")?;
+                SequenceMode::Score {
+                    buy_threshold,
+                    sell_threshold,
+                }
+            }
+            other => {
+                self.position -= 1;
+                return Err(self.error(format!("unknown sequence mode '{other}'")));
+            }
+        })
+    }
+
+    fn state(&mut self) -> LangResult<MachineState> {
+        self.expect_keyword("state")?;
+        let mut state = MachineState::new(self.string()?);
+        self.expect_symbol("{")?;
+        while !self.eat_symbol("}") {
+            if self.eat_keyword("on") {
+                let condition = self.condition()?;
+                let (to, action) = self.target()?;
+                state = state.on(condition, to, action);
+            } else if self.is_keyword("after") {
+                if state.timeout.is_some() {
+                    return Err(self.error(format!("state '{}' has two timeouts", state.name)));
+                }
+                self.position += 1;
+                let bars = self.integer()?;
+                let (to, action) = self.target()?;
+                state = state.with_timeout(bars, to, action);
+            } else {
+                return Err(self.expected("'on' or 'after'"));
+            }
+        }
+        Ok(state)
+    }
+
+    /// Parses `-> "state" [emit Action]`.
+    fn target(&mut self) -> LangResult<(String, Option<Action>)> {
+        self.expect_symbol("->")?;
+        let to = self.string()?;
+        let action = if self.eat_keyword("emit") {
+            Some(self.named(&ACTIONS, "an action")?)
+        } else {
+            None
+        };
+        Ok((to, action))
+    }
+
+    /// Parses `("id" = Indicator, ...)`.
+    fn registry(&mut self) -> LangResult<IndicatorRegistry> {
+        let entries = self.list(|parser| {
+            let id = parser.string()?;
+            parser.expect_symbol("=")?;
+            Ok((id, parser.indicator()?))
+        })?;
+        let mut registry = IndicatorRegistry::new();
+        for (id, indicator) in entries {
+            registry.insert(id, indicator);
+        }
+        Ok(registry)
+    }
+
+    /// Parses an indicator written in its own Chipa Trading Lang form, such as `Rsi(14)`.
+    fn indicator(&mut self) -> LangResult<Indicator> {
+        let start = self.position;
+        match (self.peek(), self.peek_at(1)) {
+            (Some(Token::Ident(_)), Some(Token::Symbol("("))) => {}
+            _ => return Err(self.expected("an indicator")),
+        }
+        self.position += 2;
+        let mut depth = 1;
+        while depth > 0 {
+            match self.next()? {
+                Token::Symbol("(") => depth += 1,
+                Token::Symbol(")") => depth -= 1,
+                _ => {}
+            }
+        }
+        let (from, to) = (self.tokens[start].1, self.tokens[self.position - 1].2);
+        Indicator::from_ct(&self.input[from..to]).map_err(|error| {
+            self.error_at(
+                from,
+                format!("invalid indicator '{}': {error}", &self.input[from..to]),
+            )
+        })
+    }
+
+    fn condition(&mut self) -> LangResult<Condition> {
+        let mut conditions = vec![self.conjunction()?];
+        while self.eat_keyword("or") {
+            conditions.push(self.conjunction()?);
+        }
+        Ok(if conditions.len() == 1 {
+            conditions.remove(0)
+        } else {
+            Condition::Or(conditions)
+        })
+    }
+
+    fn conjunction(&mut self) -> LangResult<Condition> {
+        let mut conditions = vec![self.unary()?];
+        while self.eat_keyword("and") {
+            conditions.push(self.unary()?);
+        }
+        Ok(if conditions.len() == 1 {
+            conditions.remove(0)
+        } else {
+            Condition::And(conditions)
+        })
+    }
+
+    fn unary(&mut self) -> LangResult<Condition> {
+        if self.eat_keyword("not") {
+            return Ok(Condition::Not(Box::new(self.unary()?)));
+        }
+        if self.eat_symbol("(") {
+            let condition = self.condition()?;
+            self.expect_symbol(")")?;
+            return Ok(condition);
+        }
+        let keyword = match self.peek() {
+            Some(Token::Ident(keyword)) => keyword.clone(),
+            _ => return self.comparison(),
+        };
+        let call = matches!(self.peek_at(1), Some(Token::Symbol("(")));
+        Ok(match keyword.as_str() {
+            "and" if call => {
+                self.position += 1;
+                Condition::And(self.list(Self::condition)?)
+            }
+            "or" if call => {
+                self.position += 1;
+                Condition::Or(self.list(Self::condition)?)
+            }
+            "position" if call => {
+                self.position += 1;
+                Condition::Position(self.argument(|p| p.named(&SIDES, "a side"))?)
+            }
+            "session" if call => {
+                self.position += 1;
+                Condition::Session(self.argument(|p| p.named(&SESSIONS, "a session"))?)
+            }
+            "day_of_week" if call => {
+                self.position += 1;
+                Condition::DayOfWeek(self.list(|parser| {
+                    let day = parser.ident()?;
+                    day.parse::<Weekday>().map_err(|_| {
+                        parser.position -= 1;
+                        parser.expected("a weekday")
+                    })
+                })?)
+            }
+            "time_of_day" if call => {
+                self.position += 1;
+                self.expect_symbol("(")?;
+                let start = self.time()?;
+                self.expect_symbol(",")?;
+                let end = self.time()?;
+                let tz_offset = if self.eat_symbol(",") {
+                    self.integer()?
+                } else {
+                    0
+                };
+                self.expect_symbol(")")?;
+                Condition::TimeOfDay {
+                    start,
+                    end,
+                    tz_offset,
+                }
+            }
+            "bars_in_trade" => {
+                self.position += 1;
+                let operator = self.operator()?;
+                Condition::BarsInTrade {
+                    operator,
+                    bars: self.integer()?,
+                }
+            }
+            "compare" if call => {
+                self.position += 1;
+                self.expect_symbol("(")?;
+                let left = self.term()?;
+                let operator = self.operator()?;
+                let right = self.term()?;
+                self.expect_symbol(")")?;
+                Condition::Compare {
+                    left: self.operand(left)?,
+                    right: self.operand(right)?,
+                    operator,
+                }
+            }
+            _ => self.comparison()?,
+        })
+    }
+
+    fn time(&mut self) -> LangResult<NaiveTime> {
+        match self.peek() {
+            Some(Token::Time(time)) => {
+                let time = *time;
+                self.position += 1;
+                Ok(time)
+            }
+            _ => Err(self.expected("a time such as 09:30")),
+        }
+    }
+
+    fn operator(&mut self) -> LangResult<Operator> {
+        let operator = match self.peek() {
+            Some(Token::Symbol(">")) => Operator::GreaterThan,
+            Some(Token::Symbol("<")) => Operator::LessThan,
+            Some(Token::Symbol("==")) => Operator::Equals,
+            Some(Token::Symbol(">=")) => Operator::GreaterThanOrEqual,
+            Some(Token::Symbol("<=")) => Operator::LessThanOrEqual,
+            Some(Token::Ident(ident)) if ident == "crossover" => Operator::CrossOver(None),
+            Some(Token::Ident(ident)) if ident == "crossunder" => Operator::CrossUnder(None),
+            _ => return Err(self.expected("a comparison operator")),
+        };
+        self.position += 1;
+        Ok(operator)
+    }
+
+    fn comparison(&mut self) -> LangResult<Condition> {
+        let left = self.term()?;
+        let operator = self.operator()?;
+        let right = self.term()?;
+        Ok(match (left, right) {
+            (Term::Indicator(indicator), Term::Value(value)) => Condition::Value {
+                indicator: Box::new((*indicator).into()),
+                value,
+                operator,
+            },
+            (Term::Value(value), Term::Indicator(indicator)) => Condition::ValueInversed {
+                value,
+                indicator: Box::new((*indicator).into()),
+                operator: mirrored(&operator),
+            },
+            (Term::Indicator(left), Term::Indicator(right)) => Condition::Indicator {
+                left: Box::new((*left).into()),
+                right: Box::new((*right).into()),
+                operator,
+            },
+            (Term::Value(left), Term::Value(right)) => Condition::ValueOnly {
+                left,
+                right,
+                operator,
+            },
+            (left, right) => Condition::Compare {
+                left: self.operand(left)?,
+                right: self.operand(right)?,
+                operator,
+            },
+        })
+    }
+
+    fn operand(&self, term: Term) -> LangResult<Operand> {
+        match term {
+            Term::Value(value) => Ok(Operand::Value(value)),
+            Term::Reference(operand) => Ok(operand),
+            Term::Indicator(indicator) => Err(self.error(format!(
+                "indicator {} cannot be compared with a reference; register it in shared(...)",
+                indicator.to_ct()
+            ))),
+        }
+    }
+
+    fn term(&mut self) -> LangResult<Term> {
+        let call = matches!(self.peek_at(1), Some(Token::Symbol("(")));
+        let reference = |parser: &mut Self| -> LangResult<(String, String)> {
+            parser.position += 1;
+            parser.expect_symbol("(")?;
+            let first = parser.string()?;
+            parser.expect_symbol(",")?;
+            let second = parser.string()?;
+            parser.expect_symbol(")")?;
+            Ok((first, second))
+        };
+        match self.peek() {
+            Some(Token::Ident(ident)) if call && ident == "shared" => {
+                self.position += 1;
+                Ok(Term::Reference(Operand::Indicator(
+                    self.argument(Self::string)?,
+                )))
+            }
+            Some(Token::Ident(ident)) if call && (ident == "timeframe" || ident == "forming") => {
+                let forming = ident == "forming";
+                let (timeframe, indicator) = reference(self)?;
+                Ok(Term::Reference(Operand::Timeframe {
+                    timeframe,
+                    indicator,
+                    forming,
+                }))
+            }
+            Some(Token::Ident(ident)) if call && ident == "symbol" => {
+                let (symbol, indicator) = reference(self)?;
+                Ok(Term::Reference(Operand::Symbol { symbol, indicator }))
+            }
+            Some(Token::Ident(ident)) if call && ident.starts_with(|c: char| c.is_uppercase()) => {
+                Ok(Term::Indicator(Box::new(self.indicator()?)))
+            }
+            _ => Ok(Term::Value(self.value()?)),
+        }
+    }
+
+    fn value(&mut self) -> LangResult<OutputType> {
+        if self.is_symbol("[") {
+            self.position += 1;
+            let mut values = Vec::new();
+            while !self.eat_symbol("]") {
+                if !values.is_empty() {
+                    self.expect_symbol(",")?;
+                }
+                values.push(self.number()?);
+            }
+            return Ok(OutputType::Array(values));
+        }
+        match self.peek() {
+            Some(Token::Number(_)) => Ok(OutputType::Single(self.number()?)),
+            Some(Token::Ident(ident)) if ident == "custom" => {
+                self.position += 1;
+                Ok(OutputType::Custom(self.list(Self::value)?))
+            }
+            Some(Token::Ident(ident)) => match FIELDS.iter().find(|(name, _)| *name == ident) {
+                Some((_, field)) => {
+                    let field = field.clone();
+                    self.position += 1;
+                    Ok(field)
+                }
+                None => Err(self.expected("an indicator or value")),
+            },
+            _ => Err(self.expected("an indicator or value")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use chrono::NaiveTime;
+
+    use super::*;
+    use crate::error::TaResult;
+    use crate::strategy::MarketData;
+    use crate::strategy::registry::IndicatorRegistry;
+
+    fn parse(text: &str) -> StrategyNode {
+        StrategyNode::from_ct(text).unwrap_or_else(|e| panic!("{e}\n{text}"))
+    }
+
+    fn round_trip(node: &StrategyNode) {
+        let text = node.to_ct();
+        let parsed = parse(&text);
+        assert_eq!(&parsed, node, "{text}");
+        assert_eq!(parsed.to_ct(), text);
+    }
+
+    fn everything() -> TaResult<StrategyNode> {
+        let mut shared = IndicatorRegistry::new();
+        shared.insert("Rsi(14)", Indicator::rsi(14)?);
+        let timeframe = Timeframe::new(Resample::Seconds(900))
+            .with_indicator("trend \"fast\"", Indicator::ema(9)?);
+        Ok(StrategyNode::Sequence {
+            mode: SequenceMode::Weighted(vec![1.0, 0.5, 2.25, 1e-7, 3.0]),
+            tie_break: TieBreak::PreferFirst,
+            nodes: vec![
+                StrategyNode::If {
+                    condition: Condition::And(vec![
+                        Condition::less_than(Indicator::rsi(14)?, OutputType::Single(30.0)),
+                        Condition::Or(vec![
+                            Condition::indicator(
+                                Indicator::sma(5)?,
+                                Indicator::ema(20)?,
+                                Operator::CrossOver(None),
+                            ),
+                            Condition::Not(Box::new(Condition::ValueOnly {
+                                left: OutputType::Close,
+                                right: OutputType::Array(vec![-1.5, f64::INFINITY]),
+                                operator: Operator::LessThanOrEqual,
+                            })),
+                        ]),
+                        Condition::ValueInversed {
+                            value: OutputType::Custom(vec![
+                                OutputType::Single(1.0),
+                                OutputType::Static(Statics::True),
+                            ]),
+                            indicator: Box::new(Indicator::sma(3)?.into()),
+                            operator: Operator::Equals,
+                        },
+                        Condition::And(vec![Condition::Position(Side::Long)]),
+                        Condition::Or(vec![]),
+                    ]),
+                    then_branch: Box::new(StrategyNode::Action(Action::StrongBuy)),
+                    else_branch: Some(Box::new(StrategyNode::If {
+                        condition: Condition::BarsInTrade {
+                            operator: Operator::GreaterThanOrEqual,
+                            bars: 10,
+                        },
+                        then_branch: Box::new(StrategyNode::Action(Action::Sell)),
+                        else_branch: None,
+                    })),
+                },
+                StrategyNode::Timeout {
+                    cooldown: 3,
+                    remaining: 0,
+                    action: Box::new(StrategyNode::RateLimit {
+                        limit: RateLimit::Signals {
+                            max: 2,
+                            window_secs: 3600,
+                        },
+                        action: Box::new(StrategyNode::Preprocess {
+                            step: PreprocessingStep::Normalize,
+                            then_branch: Box::new(StrategyNode::Action(Action::Buy)),
+                        }),
+                        fired: Vec::new(),
+                    }),
+                },
+                StrategyNode::StateMachine {
+                    states: vec![
+                        MachineState::new("idle").on(
+                            Condition::And(vec![
+                                Condition::Session(Session::NewYork),
+                                Condition::DayOfWeek(vec![Weekday::Mon, Weekday::Fri]),
+                                Condition::TimeOfDay {
+                                    start: NaiveTime::from_hms_opt(9, 30, 0).unwrap(),
+                                    end: NaiveTime::from_hms_opt(16, 0, 15).unwrap(),
+                                    tz_offset: -300,
+                                },
+                            ]),
+                            "armed",
+                            None,
+                        ),
+                        MachineState::new("armed")
+                            .on(
+                                Condition::greater_than(Indicator::sma(1)?, OutputType::High),
+                                "idle",
+                                Some(Action::Buy),
+                            )
+                            .with_timeout(5, "idle", Some(Action::Hold)),
+                    ],
+                    current: 0,
+                    bars_in_state: 0,
+                },
+                StrategyNode::Shared {
+                    indicators: shared,
+                    node: Box::new(StrategyNode::MultiTimeframe {
+                        timeframes: BTreeMap::from([
+                            ("15m".to_string(), timeframe),
+                            ("x10".to_string(), Timeframe::new(Resample::Bars(10))),
+                        ]),
+                        node: Box::new(StrategyNode::If {
+                            condition: Condition::Or(vec![
+                                Condition::Compare {
+                                    left: Operand::Indicator("Rsi(14)".to_string()),
+                                    right: Operand::Timeframe {
+                                        timeframe: "15m".to_string(),
+                                        indicator: "trend \"fast\"".to_string(),
+                                        forming: true,
+                                    },
+                                    operator: Operator::CrossUnder(None),
+                                },
+                                Condition::Compare {
+                                    left: Operand::Symbol {
+                                        symbol: "DXY".to_string(),
+                                        indicator: "fast".to_string(),
+                                    },
+                                    right: Operand::Value(OutputType::Single(100.0)),
+                                    operator: Operator::GreaterThan,
+                                },
+                                Condition::Compare {
+                                    left: Operand::Value(OutputType::Open),
+                                    right: Operand::Value(OutputType::Single(0.0)),
+                                    operator: Operator::GreaterThan,
+                                },
+                            ]),
+                            then_branch: Box::new(StrategyNode::Action(Action::StrongSell)),
+                            else_branch: Some(Box::new(StrategyNode::Action(Action::Hold))),
+                        }),
+                    }),
+                },
+                StrategyNode::Sequence {
+                    mode: SequenceMode::Score {
+                        buy_threshold: 0.5,
+                        sell_threshold: -0.5,
+                    },
+                    nodes: vec![],
+                    tie_break: TieBreak::Hold,
+                },
+            ],
+        })
+    }
+
+    #[test]
+    fn test_round_trip_every_node() -> TaResult<()> {
+        round_trip(&everything()?);
+        for mode in [
+            SequenceMode::First,
+            SequenceMode::Last,
+            SequenceMode::Any,
+            SequenceMode::All,
+            SequenceMode::Majority,
+            SequenceMode::Percentage(60),
+        ] {
+            round_trip(&StrategyNode::Sequence {
+                mode,
+                nodes: vec![StrategyNode::Action(Action::Buy)],
+                tie_break: TieBreak::PreferStronger,
+            });
+        }
+        for limit in [
+            RateLimit::Cooldown { secs: 60 },
+            RateLimit::AfterLoss { secs: 1800 },
+        ] {
+            round_trip(&StrategyNode::RateLimit {
+                limit,
+                action: Box::new(StrategyNode::Action(Action::Sell)),
+                fired: Vec::new(),
+            });
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_handwritten_strategy() -> TaResult<()> {
+        let text = r#"
+            # Mean reversion with a trend filter.
+            sequence majority {
+                if Rsi(14) < 30 and (Sma(5) crossover Sma(20) or close > 100) {
+                    Buy
+                } else if not Rsi(14) < 70 {
+                    Sell
+                } else {
+                    Hold
+                }
+                timeout(2) { StrongBuy }
+            }
+        "#;
+        let node = parse(text);
+        node.validate()?;
+        let StrategyNode::Sequence { nodes, mode, .. } = &node else {
+            unreachable!()
+        };
+        assert_eq!(*mode, SequenceMode::Majority);
+        let StrategyNode::If {
+            condition: Condition::And(conditions),
+            else_branch: Some(else_branch),
+            ..
+        } = &nodes[0]
+        else {
+            unreachable!()
+        };
+        assert!(matches!(&conditions[1], Condition::Or(c) if c.len() == 2));
+        assert!(matches!(**else_branch, StrategyNode::If { .. }));
+        assert_eq!(
+            node.to_ct().lines().nth(1).map(str::trim),
+            Some("if Rsi(14) < 30 and (Sma(5) crossover Sma(20) or close > 100) {")
+        );
+        round_trip(&node);
+        Ok(())
+    }
+
+    #[test]
+    fn test_condition_precedence() -> LangResult<()> {
+        let condition = Condition::from_ct("close > 1 or close > 2 and not close > 3")?;
+        let Condition::Or(conditions) = &condition else {
+            unreachable!()
+        };
+        assert!(matches!(&conditions[1], Condition::And(c) if matches!(c[1], Condition::Not(_))));
+        assert_eq!(
+            condition.to_ct(),
+            "close > 1 or (close > 2 and not close > 3)"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_value_first_comparison_evaluates_as_written() -> TaResult<()> {
+        let condition = |text: &str| Condition::from_ct(text).unwrap_or_else(|e| panic!("{e}"));
+        let holds = |condition: &mut Condition, price: f64| -> TaResult<bool> {
+            let data = MarketData::Float(price);
+            condition.update(&data)?;
+            condition.evaluate(&data)
+        };
+
+        let mut below = condition("100 > Sma(1)");
+        assert!(matches!(
+            below,
+            Condition::ValueInversed {
+                operator: Operator::LessThan,
+                ..
+            }
+        ));
+        assert_eq!(below.to_ct(), "100 > Sma(1)");
+        assert!(holds(&mut below, 90.0)?);
+        assert!(!holds(&mut below, 110.0)?);
+
+        // The value crossing over the indicator is the indicator crossing under it.
+        let mut cross = condition("100 crossover Sma(1)");
+        assert_eq!(cross.to_ct(), "100 crossover Sma(1)");
+        let crossed = [110.0, 105.0, 95.0, 90.0]
+            .into_iter()
+            .map(|price| holds(&mut cross, price))
+            .collect::<TaResult<Vec<_>>>()?;
+        assert_eq!(crossed, vec![false, false, true, false]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_errors_report_position() {
+        let error = |text: &str| match StrategyNode::from_ct(text) {
+            Err(error) => error.to_string(),
+            Ok(node) => panic!("parsed {node:?}"),
+        };
+        assert!(
+            error("if close > 1 {\n    Buy\n} else {\n    Jump\n}").contains("line 4, column 5")
+        );
+        assert!(error("if close >> 1 { Buy }").contains("expected an indicator or value"));
+        assert!(error("Buy Sell").contains("unexpected 'Sell'"));
+        assert!(error("timeout(-1) { Buy }").contains("integer"));
+        assert!(error("if shared(\"a\") > Sma(3) { Buy }").contains("register it in shared"));
+        assert!(
+            error("state_machine { state \"a\" { after 1 -> \"a\" after 2 -> \"a\" } }")
+                .contains("two timeouts")
+        );
+    }
+}
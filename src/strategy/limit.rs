@@ -0,0 +1,247 @@
+use chrono::{DateTime, TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{error::TaResult, strategy::StrategyError};
+
+/// Time based limits applied by `StrategyNode::RateLimit`.
+///
+/// Limits compare the timestamps of the evaluated bars, so they behave the same in
+/// backtests and live runs and keep counting while the node is not evaluated.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum RateLimit {
+    /// Emit at most `max` non-Hold actions within any window of `window_secs` seconds.
+    Signals { max: usize, window_secs: u64 },
+    /// Emit nothing for `secs` seconds after the previous non-Hold action.
+    Cooldown { secs: u64 },
+    /// Emit nothing for `secs` seconds after the last losing trade reported by the context.
+    AfterLoss { secs: u64 },
+}
+
+impl RateLimit {
+    /// Returns the length of the window within which past actions or losses block new ones.
+    /// Fails if it can not be represented as a `TimeDelta`.
+    pub fn window(&self) -> TaResult<TimeDelta> {
+        let secs = match self {
+            RateLimit::Signals { window_secs, .. } => *window_secs,
+            RateLimit::Cooldown { secs } | RateLimit::AfterLoss { secs } => *secs,
+        };
+        i64::try_from(secs)
+            .ok()
+            .and_then(TimeDelta::try_seconds)
+            .ok_or_else(|| {
+                StrategyError::Configuration(format!("RateLimit window of {secs}s is too long"))
+                    .into()
+            })
+    }
+
+    pub fn validate(&self) -> TaResult<()> {
+        if let RateLimit::Signals { max: 0, .. } = self {
+            return Err(StrategyError::Configuration(
+                "RateLimit must allow at least one signal".to_string(),
+            )
+            .into());
+        }
+        self.window()?;
+        Ok(())
+    }
+
+    /// Drops the actions in `fired` that no longer count towards the limit at `now`.
+    pub(crate) fn prune(&self, fired: &mut Vec<DateTime<Utc>>, now: DateTime<Utc>) -> TaResult<()> {
+        let window = self.window()?;
+        fired.retain(|time| now - *time < window);
+        Ok(())
+    }
+
+    /// Returns why an action is blocked at `now`, given the recent actions in `fired` and the
+    /// time of the last losing trade, or `None` if it is allowed.
+    pub(crate) fn blocked(
+        &self,
+        fired: &[DateTime<Utc>],
+        last_loss: Option<DateTime<Utc>>,
+        now: DateTime<Utc>,
+    ) -> TaResult<Option<String>> {
+        Ok(match self {
+            RateLimit::Signals { max, .. } if fired.len() >= *max => {
+                Some(format!("rate limit: {} signals in window", fired.len()))
+            }
+            RateLimit::Cooldown { .. } => fired
+                .last()
+                .map(|time| format!("cooldown: last signal at {time}")),
+            RateLimit::AfterLoss { .. } => {
+                let window = self.window()?;
+                last_loss
+                    .filter(|time| now - *time < window)
+                    .map(|time| format!("cooldown: loss at {time}"))
+            }
+            RateLimit::Signals { .. } => None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(not(feature = "js"))]
+    use crate::Indicator;
+    #[cfg(feature = "js")]
+    use crate::indicators::indicator::Indicator;
+
+    use super::*;
+    use crate::error::{TaError, TaResult};
+    use crate::strategy::{
+        Action, Condition, Context, ManagedStrategy, MarketData, Side, StrategyError, StrategyNode,
+    };
+    use crate::traits::Reset;
+    use crate::types::OutputType;
+    use chrono::TimeZone;
+
+    fn minutes(minutes: i64) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 1, 2, 9, 0, 0).unwrap() + TimeDelta::minutes(minutes)
+    }
+
+    fn limited(limit: RateLimit) -> StrategyNode {
+        StrategyNode::RateLimit {
+            limit,
+            action: Box::new(StrategyNode::Action(Action::Buy)),
+            fired: Vec::new(),
+        }
+    }
+
+    fn run(node: &mut StrategyNode, times: &[i64]) -> TaResult<Vec<Action>> {
+        times
+            .iter()
+            .map(|t| node.evaluate_with(&MarketData::Float(1.0), &Context::at(minutes(*t))))
+            .collect()
+    }
+
+    #[test]
+    fn test_timeout_counts_down_in_unvisited_branch() -> TaResult<()> {
+        let node = || -> TaResult<StrategyNode> {
+            Ok(StrategyNode::If {
+                condition: Condition::greater_than(Indicator::sma(1)?, OutputType::Single(50.0)),
+                then_branch: Box::new(StrategyNode::Timeout {
+                    cooldown: 2,
+                    remaining: 0,
+                    action: Box::new(StrategyNode::Action(Action::Buy)),
+                }),
+                else_branch: None,
+            })
+        };
+        let evaluate = |node: &mut StrategyNode, prices: &[f64]| -> TaResult<Vec<Action>> {
+            prices
+                .iter()
+                .map(|p| node.evaluate(&MarketData::Float(*p)))
+                .collect()
+        };
+        assert_eq!(
+            evaluate(&mut node()?, &[60.0, 60.0, 60.0, 60.0])?,
+            vec![Action::Buy, Action::Hold, Action::Hold, Action::Buy]
+        );
+        assert_eq!(
+            evaluate(&mut node()?, &[60.0, 10.0, 10.0, 60.0])?,
+            vec![Action::Buy, Action::Hold, Action::Hold, Action::Buy]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_signals_per_window() -> TaResult<()> {
+        let mut node = limited(RateLimit::Signals {
+            max: 3,
+            window_secs: 3600,
+        });
+        node.validate()?;
+        let actions = run(&mut node, &[0, 10, 20, 30, 40, 50, 60, 70, 80, 85])?;
+        use Action::{Buy, Hold};
+        assert_eq!(
+            actions,
+            vec![Buy, Buy, Buy, Hold, Hold, Hold, Buy, Buy, Buy, Hold]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_cooldown_after_signal() -> TaResult<()> {
+        let mut node = limited(RateLimit::Cooldown { secs: 1800 });
+        use Action::{Buy, Hold};
+        assert_eq!(
+            run(&mut node, &[0, 10, 29, 30, 45, 60])?,
+            vec![Buy, Hold, Hold, Buy, Hold, Buy]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_cooldown_after_loss() -> TaResult<()> {
+        let strategy = StrategyNode::RateLimit {
+            limit: RateLimit::AfterLoss { secs: 1800 },
+            action: Box::new(StrategyNode::If {
+                condition: Condition::And(vec![
+                    Condition::Position(Side::Flat),
+                    Condition::greater_than(Indicator::sma(1)?, OutputType::Single(100.0)),
+                ]),
+                then_branch: Box::new(StrategyNode::Action(Action::Buy)),
+                else_branch: Some(Box::new(StrategyNode::If {
+                    condition: Condition::less_than(Indicator::sma(1)?, OutputType::Single(90.0)),
+                    then_branch: Box::new(StrategyNode::Action(Action::Sell)),
+                    else_branch: None,
+                })),
+            }),
+            fired: Vec::new(),
+        };
+        let mut managed = ManagedStrategy::new(strategy);
+        let mut step =
+            |price: f64, t: i64| managed.evaluate_at(&MarketData::Float(price), minutes(t));
+        assert_eq!(step(101.0, 0)?, Action::Buy);
+        assert_eq!(step(85.0, 10)?, Action::Sell);
+        assert_eq!(step(101.0, 20)?, Action::Hold);
+        assert_eq!(step(101.0, 39)?, Action::Hold);
+        assert_eq!(step(101.0, 40)?, Action::Buy);
+        assert_eq!(managed.last_loss(), Some(minutes(10)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_rate_limit_requires_timestamp() {
+        let mut node = limited(RateLimit::Cooldown { secs: 60 });
+        assert_eq!(
+            node.evaluate(&MarketData::Float(1.0)),
+            Err(TaError::Strategy(StrategyError::MissingTimestamp))
+        );
+        assert!(
+            limited(RateLimit::Signals {
+                max: 0,
+                window_secs: 60
+            })
+            .validate()
+            .is_err()
+        );
+
+        let mut endless = limited(RateLimit::Cooldown { secs: u64::MAX / 2 });
+        assert!(endless.validate().is_err());
+        assert!(run(&mut endless, &[0]).is_err());
+    }
+
+    #[test]
+    fn test_rate_limit_serde_and_reset() -> TaResult<()> {
+        let mut node = limited(RateLimit::Signals {
+            max: 2,
+            window_secs: 3600,
+        });
+        run(&mut node, &[0, 10])?;
+        let json = serde_json::to_string(&node)?;
+        let mut restored: StrategyNode = serde_json::from_str(&json)?;
+        assert_eq!(restored, node);
+        assert_eq!(run(&mut restored, &[20])?, vec![Action::Hold]);
+
+        node.reset();
+        assert_eq!(
+            node,
+            limited(RateLimit::Signals {
+                max: 2,
+                window_secs: 3600
+            })
+        );
+        assert_eq!(run(&mut node, &[20])?, vec![Action::Buy]);
+        Ok(())
+    }
+}
@@ -0,0 +1,749 @@
+use core::fmt;
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::TaError,
+    indicators::indicator::Indicator,
+    strategy::{
+        Action, Condition, Context, Operand, Side, StrategyError, StrategyNode,
+        condition::Operator, machine::validate_states, node::SequenceMode,
+        registry::IndicatorRegistry,
+    },
+    traits::Period,
+    types::OutputType,
+};
+
+/// How serious a `Diagnostic` is.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    /// The strategy runs, but part of it cannot have any effect or is likely a mistake.
+    Warning,
+    /// The strategy is invalid and `validate` rejects it or evaluation fails.
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// A problem found by `StrategyNode::lint`.
+#[derive(Debug, PartialEq)]
+pub struct Diagnostic {
+    /// Location of the offending node or condition, using the ids of `NodeTrace`, for example
+    /// `root.Sequence[2].If.condition.And[1]`.
+    pub path: String,
+    pub severity: Severity,
+    pub error: StrategyError,
+}
+
+impl Diagnostic {
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}: {}", self.severity, self.path, self.error)
+    }
+}
+
+impl StrategyNode {
+    /// Checks the whole tree and returns every problem found, in tree order.
+    ///
+    /// Reports everything `validate` rejects as errors, together with `Percentage` thresholds
+    /// above 100. Warnings point at parts of the tree that can never have an effect: branches
+    /// behind conditions that always or never hold, `And` conditions bounding the same
+    /// indicator by contradictory constants, sequence children after one that always acts in
+    /// `First` and `Any` modes and states that are never entered. `If` nodes without an
+    /// `else_branch` are reported as warnings too.
+    pub fn lint(&self) -> Vec<Diagnostic> {
+        let mut linter = Linter::default();
+        linter.node(self, &format!("root.{}", self.kind()), &Context::default());
+        linter.diagnostics
+    }
+}
+
+#[derive(Default)]
+struct Linter {
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Linter {
+    fn report(&mut self, path: &str, severity: Severity, error: StrategyError) {
+        self.diagnostics.push(Diagnostic {
+            path: path.to_string(),
+            severity,
+            error,
+        });
+    }
+
+    fn error(&mut self, path: &str, error: TaError) {
+        let error = match error {
+            TaError::Strategy(error) => error,
+            other => StrategyError::Configuration(other.to_string()),
+        };
+        self.report(path, Severity::Error, error);
+    }
+
+    fn unreachable(&mut self, path: &str, reason: impl Into<String>) {
+        self.report(
+            path,
+            Severity::Warning,
+            StrategyError::UnreachableBranch(reason.into()),
+        );
+    }
+
+    fn child(&mut self, node: &StrategyNode, path: &str, context: &Context) {
+        self.node(node, &format!("{path}.{}", node.kind()), context);
+    }
+
+    fn node(&mut self, node: &StrategyNode, path: &str, context: &Context) {
+        match node {
+            StrategyNode::Action(_) => {}
+            StrategyNode::Preprocess { then_branch, .. } => {
+                self.child(then_branch, &format!("{path}.then_branch"), context)
+            }
+            StrategyNode::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                let at = |segment: &str| format!("{path}.{segment}");
+                self.condition(condition, &at("condition"), context);
+                match (constant(condition), else_branch) {
+                    (Some(false), _) => {
+                        self.unreachable(&at("then_branch"), "condition never holds")
+                    }
+                    (Some(true), Some(_)) => {
+                        self.unreachable(&at("else_branch"), "condition always holds")
+                    }
+                    _ => {}
+                }
+                self.child(then_branch, &at("then_branch"), context);
+                match else_branch {
+                    Some(else_branch) => self.child(else_branch, &at("else_branch"), context),
+                    None => self.report(path, Severity::Warning, StrategyError::MissingElseBranch),
+                }
+            }
+            StrategyNode::Sequence { mode, nodes, .. } => {
+                if nodes.is_empty() {
+                    self.report(path, Severity::Error, StrategyError::EmptySequence);
+                }
+                if let Err(error) = mode.validate(nodes.len()) {
+                    self.report(path, Severity::Error, error);
+                }
+                let stops = matches!(mode, SequenceMode::First | SequenceMode::Any);
+                let mut acting = None;
+                for (index, child) in nodes.iter().enumerate() {
+                    let at = format!("{path}[{index}]");
+                    if let Some(acting) = acting {
+                        self.unreachable(&at, format!("node [{acting}] always acts first"));
+                    } else if stops && always_acts(child) {
+                        acting = Some(index);
+                    }
+                    self.child(child, &at, context);
+                }
+            }
+            StrategyNode::Timeout { action, .. } => {
+                self.child(action, &format!("{path}.action"), context)
+            }
+            StrategyNode::RateLimit { limit, action, .. } => {
+                if let Err(error) = limit.validate() {
+                    self.error(path, error);
+                }
+                self.child(action, &format!("{path}.action"), context)
+            }
+            StrategyNode::StateMachine {
+                states, current, ..
+            } => {
+                if let Err(error) = validate_states(states, *current) {
+                    self.error(path, error);
+                }
+                for (index, state) in states.iter().enumerate() {
+                    for (i, transition) in state.transitions.iter().enumerate() {
+                        let at = format!("{path}.states[{index}].transitions[{i}].condition");
+                        self.condition(&transition.condition, &at, context);
+                    }
+                }
+                let names: BTreeMap<&str, usize> = states
+                    .iter()
+                    .enumerate()
+                    .map(|(index, state)| (state.name.as_str(), index))
+                    .collect();
+                let mut entered = BTreeSet::from([0]);
+                let mut pending = vec![0];
+                while let Some(index) = pending.pop() {
+                    let Some(state) = states.get(index) else {
+                        continue;
+                    };
+                    let targets = state
+                        .transitions
+                        .iter()
+                        .filter(|t| constant(&t.condition) != Some(false))
+                        .map(|t| &t.to)
+                        .chain(state.timeout.iter().map(|t| &t.to));
+                    for &target in targets.filter_map(|target| names.get(target.as_str())) {
+                        if entered.insert(target) {
+                            pending.push(target);
+                        }
+                    }
+                }
+                for (index, state) in states.iter().enumerate() {
+                    if !entered.contains(&index) {
+                        self.unreachable(
+                            &format!("{path}.states[{index}]"),
+                            format!("state '{}' is never entered", state.name),
+                        );
+                    }
+                }
+            }
+            StrategyNode::Shared { indicators, node } => {
+                self.registry(indicators, &format!("{path}.indicators"));
+                self.child(
+                    node,
+                    &format!("{path}.node"),
+                    &Context {
+                        indicators: Some(indicators),
+                        ..context.clone()
+                    },
+                );
+            }
+            StrategyNode::MultiTimeframe { timeframes, node } => {
+                for (name, timeframe) in timeframes {
+                    let at = format!("{path}.timeframes[{name:?}]");
+                    if let Err(error) = timeframe.validate() {
+                        match error {
+                            TaError::Strategy(StrategyError::InvalidIndicatorPeriod { .. }) => {
+                                self.registry(&timeframe.indicators, &format!("{at}.indicators"))
+                            }
+                            error => self.error(&at, error),
+                        }
+                    }
+                }
+                self.child(
+                    node,
+                    &format!("{path}.node"),
+                    &Context {
+                        timeframes: Some(timeframes),
+                        ..context.clone()
+                    },
+                );
+            }
+        }
+    }
+
+    fn registry(&mut self, registry: &IndicatorRegistry, path: &str) {
+        for id in registry.ids() {
+            let period = registry
+                .get(id)
+                .map(|indicator| indicator.indicator.period());
+            if period == Some(0) {
+                self.report(
+                    &format!("{path}[{id:?}]"),
+                    Severity::Error,
+                    StrategyError::InvalidIndicatorPeriod { period: 0 },
+                );
+            }
+        }
+    }
+
+    fn condition(&mut self, condition: &Condition, path: &str, context: &Context) {
+        match condition {
+            Condition::And(conditions) => {
+                // A contradiction within a nested `And` is reported there only.
+                let nested = conditions
+                    .iter()
+                    .any(|c| matches!(c, Condition::And(_)) && contradiction(c).is_some());
+                if let Some(reason) = contradiction(condition).filter(|_| !nested) {
+                    self.report(
+                        path,
+                        Severity::Warning,
+                        StrategyError::ContradictoryConditions(reason),
+                    );
+                }
+                for (index, condition) in conditions.iter().enumerate() {
+                    self.condition(condition, &format!("{path}.And[{index}]"), context);
+                }
+            }
+            Condition::Or(conditions) => {
+                for (index, condition) in conditions.iter().enumerate() {
+                    self.condition(condition, &format!("{path}.Or[{index}]"), context);
+                }
+            }
+            Condition::Not(condition) => self.condition(condition, &format!("{path}.Not"), context),
+            leaf => {
+                if let Err(error) = leaf.validate_with(context) {
+                    self.error(path, error);
+                }
+            }
+        }
+    }
+}
+
+/// Returns whether the node emits a non-Hold action on every bar.
+fn always_acts(node: &StrategyNode) -> bool {
+    match node {
+        StrategyNode::Action(action) => *action != Action::Hold,
+        StrategyNode::If {
+            condition,
+            then_branch,
+            else_branch,
+        } => match constant(condition) {
+            Some(true) => always_acts(then_branch),
+            Some(false) => else_branch.as_deref().is_some_and(always_acts),
+            None => always_acts(then_branch) && else_branch.as_deref().is_some_and(always_acts),
+        },
+        StrategyNode::Preprocess { then_branch, .. } => always_acts(then_branch),
+        StrategyNode::Shared { node, .. } => always_acts(node),
+        _ => false,
+    }
+}
+
+fn is_constant(value: &OutputType) -> bool {
+    match value {
+        OutputType::Single(_) | OutputType::Array(_) | OutputType::Static(_) => true,
+        OutputType::Custom(values) => values.iter().all(is_constant),
+        _ => false,
+    }
+}
+
+/// Returns the value of a condition that does not depend on the market data, the position or
+/// the time, or `None` if it may change from bar to bar.
+fn constant(condition: &Condition) -> Option<bool> {
+    match condition {
+        Condition::ValueOnly {
+            left,
+            right,
+            operator,
+        } if is_constant(left) && is_constant(right) => match operator {
+            Operator::CrossOver(_) | Operator::CrossUnder(_) => None,
+            operator => operator.clone().evaluate(left, right).ok(),
+        },
+        Condition::And(conditions) => {
+            if contradiction(condition).is_some() {
+                return Some(false);
+            }
+            let values: Vec<Option<bool>> = conditions.iter().map(constant).collect();
+            if values.contains(&Some(false)) {
+                Some(false)
+            } else if values.iter().all(|value| *value == Some(true)) {
+                Some(true)
+            } else {
+                None
+            }
+        }
+        Condition::Or(conditions) => {
+            let values: Vec<Option<bool>> = conditions.iter().map(constant).collect();
+            if values.contains(&Some(true)) {
+                Some(true)
+            } else if values.iter().all(|value| *value == Some(false)) {
+                Some(false)
+            } else {
+                None
+            }
+        }
+        Condition::Not(condition) => constant(condition).map(|value| !value),
+        Condition::DayOfWeek(days) if days.is_empty() => Some(false),
+        _ => None,
+    }
+}
+
+/// What a comparison against a constant bounds.
+#[derive(PartialEq)]
+enum Subject<'a> {
+    Indicator(&'a Indicator),
+    Operand(&'a Operand),
+}
+
+impl fmt::Display for Subject<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Subject::Indicator(indicator) => write!(f, "{}", serde_json::json!(indicator)),
+            Subject::Operand(operand) => write!(f, "{}", serde_json::json!(operand)),
+        }
+    }
+}
+
+/// Range of values a subject may take for every bound of an `And` to hold.
+struct Range {
+    lower: Option<(f64, bool)>,
+    upper: Option<(f64, bool)>,
+}
+
+impl Range {
+    fn restrict(&mut self, operator: &Operator, value: f64) {
+        let tighter = |bound: &mut Option<(f64, bool)>, value: f64, inclusive: bool, up: bool| {
+            let replace = match bound {
+                None => true,
+                Some((current, current_inclusive)) => {
+                    (if up {
+                        value < *current
+                    } else {
+                        value > *current
+                    }) || (value == *current && *current_inclusive && !inclusive)
+                }
+            };
+            if replace {
+                *bound = Some((value, inclusive));
+            }
+        };
+        match operator {
+            Operator::GreaterThan => tighter(&mut self.lower, value, false, false),
+            Operator::GreaterThanOrEqual => tighter(&mut self.lower, value, true, false),
+            Operator::LessThan => tighter(&mut self.upper, value, false, true),
+            Operator::LessThanOrEqual => tighter(&mut self.upper, value, true, true),
+            Operator::Equals => {
+                tighter(&mut self.lower, value, true, false);
+                tighter(&mut self.upper, value, true, true);
+            }
+            Operator::CrossOver(_) | Operator::CrossUnder(_) => {}
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        match (self.lower, self.upper) {
+            (Some((lower, lower_inclusive)), Some((upper, upper_inclusive))) => {
+                lower > upper || (lower == upper && !(lower_inclusive && upper_inclusive))
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Returns the subject, operator and constant of a comparison of an indicator or reference
+/// with a single constant, oriented as `subject operator constant`.
+fn bound(condition: &Condition) -> Option<(Subject<'_>, &Operator, f64)> {
+    match condition {
+        // `ValueInversed` also compares the indicator output against the value.
+        Condition::Value {
+            indicator,
+            value: OutputType::Single(value),
+            operator,
+        }
+        | Condition::ValueInversed {
+            value: OutputType::Single(value),
+            indicator,
+            operator,
+        } => Some((Subject::Indicator(&indicator.indicator), operator, *value)),
+        Condition::Compare {
+            left,
+            right: Operand::Value(OutputType::Single(value)),
+            operator,
+        } if !matches!(left, Operand::Value(_)) => Some((Subject::Operand(left), operator, *value)),
+        _ => None,
+    }
+}
+
+/// Describes why the conjunction of the direct children of an `And` condition, nested `And`s
+/// included, can never hold.
+fn contradiction(condition: &Condition) -> Option<String> {
+    let mut leaves = Vec::new();
+    flatten(condition, &mut leaves);
+
+    let sides: Vec<&Side> = leaves
+        .iter()
+        .filter_map(|leaf| match leaf {
+            Condition::Position(side) => Some(side),
+            _ => None,
+        })
+        .collect();
+    if let Some(other) = sides.iter().find(|side| **side != sides[0]) {
+        return Some(format!(
+            "position cannot be both {:?} and {other:?}",
+            sides[0]
+        ));
+    }
+
+    let bounds: Vec<_> = leaves.iter().filter_map(|leaf| bound(leaf)).collect();
+    for (index, (subject, ..)) in bounds.iter().enumerate() {
+        if bounds[..index].iter().any(|(other, ..)| other == subject) {
+            continue;
+        }
+        let mut range = Range {
+            lower: None,
+            upper: None,
+        };
+        let mut described = Vec::new();
+        for (_, operator, value) in bounds.iter().filter(|(other, ..)| other == subject) {
+            range.restrict(operator, *value);
+            described.push(format!("{} {value}", operator.as_str()));
+        }
+        if range.is_empty() {
+            return Some(format!("{subject} {}", described.join(" and ")));
+        }
+    }
+    None
+}
+
+fn flatten<'a>(condition: &'a Condition, leaves: &mut Vec<&'a Condition>) {
+    match condition {
+        Condition::And(conditions) => {
+            for condition in conditions {
+                flatten(condition, leaves);
+            }
+        }
+        leaf => leaves.push(leaf),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(not(feature = "js"))]
+    use crate::Indicator;
+    #[cfg(feature = "js")]
+    use crate::indicators::indicator::Indicator;
+
+    use super::*;
+    use crate::error::TaResult;
+    use crate::strategy::node::TieBreak;
+    use crate::strategy::{MachineState, Timeframe, timeframe::Resample};
+
+    fn action(action: Action) -> Box<StrategyNode> {
+        Box::new(StrategyNode::Action(action))
+    }
+
+    fn sequence(mode: SequenceMode, nodes: Vec<StrategyNode>) -> StrategyNode {
+        StrategyNode::Sequence {
+            mode,
+            nodes,
+            tie_break: TieBreak::Hold,
+        }
+    }
+
+    fn paths(diagnostics: &[Diagnostic]) -> Vec<(&str, Severity)> {
+        diagnostics
+            .iter()
+            .map(|d| (d.path.as_str(), d.severity))
+            .collect()
+    }
+
+    #[test]
+    fn test_lint_clean_strategy() -> TaResult<()> {
+        let node = StrategyNode::If {
+            condition: Condition::And(vec![
+                Condition::greater_than(Indicator::rsi(14)?, OutputType::Single(30.0)),
+                Condition::less_than(Indicator::rsi(14)?, OutputType::Single(70.0)),
+            ]),
+            then_branch: action(Action::Buy),
+            else_branch: Some(action(Action::Hold)),
+        };
+        assert_eq!(node.lint(), vec![]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_lint_reports_every_problem_with_path() -> TaResult<()> {
+        let contradictory = Condition::And(vec![
+            Condition::greater_than(Indicator::rsi(14)?, OutputType::Single(70.0)),
+            Condition::Or(vec![Condition::Position(Side::Flat)]),
+            Condition::less_than(Indicator::rsi(14)?, OutputType::Single(30.0)),
+        ]);
+        let mismatched = Condition::ValueOnly {
+            left: OutputType::Close,
+            right: OutputType::Array(vec![1.0, 2.0]),
+            operator: Operator::GreaterThan,
+        };
+        let node = sequence(
+            SequenceMode::Percentage(150),
+            vec![
+                sequence(SequenceMode::Majority, vec![]),
+                StrategyNode::If {
+                    condition: contradictory,
+                    then_branch: action(Action::Buy),
+                    else_branch: Some(action(Action::Hold)),
+                },
+                StrategyNode::If {
+                    condition: Condition::And(vec![Condition::Position(Side::Long), mismatched]),
+                    then_branch: action(Action::Sell),
+                    else_branch: None,
+                },
+            ],
+        );
+        let diagnostics = node.lint();
+        assert_eq!(
+            paths(&diagnostics),
+            vec![
+                ("root.Sequence", Severity::Error),
+                ("root.Sequence[0].Sequence", Severity::Error),
+                ("root.Sequence[1].If.condition", Severity::Warning),
+                ("root.Sequence[1].If.then_branch", Severity::Warning),
+                ("root.Sequence[2].If.condition.And[1]", Severity::Error),
+                ("root.Sequence[2].If", Severity::Warning),
+            ]
+        );
+        assert_eq!(diagnostics[0].error, StrategyError::InvalidPercentage(150));
+        assert_eq!(diagnostics[1].error, StrategyError::EmptySequence);
+        assert!(matches!(
+            &diagnostics[2].error,
+            StrategyError::ContradictoryConditions(reason) if reason.contains("> 70 and < 30")
+        ));
+        assert!(matches!(
+            diagnostics[4].error,
+            StrategyError::IncompatibleShapes { .. }
+        ));
+        assert_eq!(diagnostics[5].error, StrategyError::MissingElseBranch);
+        assert!(diagnostics[0].is_error() && !diagnostics[5].is_error());
+        assert_eq!(
+            diagnostics[5].to_string(),
+            "warning: root.Sequence[2].If: If node is missing an else_branch"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_lint_reports_contradiction_at_innermost_and() -> TaResult<()> {
+        let rsi = |operator, value| Condition::Value {
+            indicator: Box::new(Indicator::rsi(14).unwrap().into()),
+            value: OutputType::Single(value),
+            operator,
+        };
+        let guarded = |condition| StrategyNode::If {
+            condition,
+            then_branch: action(Action::Buy),
+            else_branch: Some(action(Action::Hold)),
+        };
+
+        let nested = guarded(Condition::And(vec![
+            Condition::Position(Side::Flat),
+            Condition::And(vec![
+                rsi(Operator::GreaterThan, 70.0),
+                rsi(Operator::LessThan, 30.0),
+            ]),
+        ]));
+        assert_eq!(
+            paths(&nested.lint()),
+            vec![
+                ("root.If.condition.And[1]", Severity::Warning),
+                ("root.If.then_branch", Severity::Warning),
+            ]
+        );
+
+        let spanning = guarded(Condition::And(vec![
+            rsi(Operator::GreaterThan, 70.0),
+            Condition::And(vec![
+                Condition::Position(Side::Flat),
+                rsi(Operator::LessThan, 30.0),
+            ]),
+        ]));
+        assert_eq!(
+            paths(&spanning.lint()),
+            vec![
+                ("root.If.condition", Severity::Warning),
+                ("root.If.then_branch", Severity::Warning),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_lint_errors_fail_validation() {
+        let node = sequence(
+            SequenceMode::Percentage(150),
+            vec![StrategyNode::Action(Action::Buy)],
+        );
+        assert!(node.lint().iter().all(Diagnostic::is_error));
+        assert_eq!(
+            node.validate(),
+            Err(StrategyError::InvalidPercentage(150).into())
+        );
+    }
+
+    #[test]
+    fn test_lint_unreachable_branches() -> TaResult<()> {
+        let always = Condition::ValueOnly {
+            left: OutputType::Single(2.0),
+            right: OutputType::Single(1.0),
+            operator: Operator::GreaterThan,
+        };
+        let node = sequence(
+            SequenceMode::First,
+            vec![
+                StrategyNode::If {
+                    condition: Condition::Not(Box::new(Condition::And(vec![
+                        Condition::Position(Side::Long),
+                        Condition::Position(Side::Short),
+                    ]))),
+                    then_branch: action(Action::Hold),
+                    else_branch: Some(action(Action::Buy)),
+                },
+                StrategyNode::If {
+                    condition: always,
+                    then_branch: action(Action::Sell),
+                    else_branch: Some(action(Action::Buy)),
+                },
+                StrategyNode::Action(Action::Buy),
+            ],
+        );
+        assert_eq!(
+            paths(&node.lint()),
+            vec![
+                ("root.Sequence[0].If.condition.Not", Severity::Warning),
+                ("root.Sequence[0].If.else_branch", Severity::Warning),
+                ("root.Sequence[1].If.else_branch", Severity::Warning),
+                ("root.Sequence[2]", Severity::Warning),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_lint_state_machine_and_zero_periods() -> TaResult<()> {
+        let mut shared: IndicatorRegistry =
+            serde_json::from_str(r#"{"slow":{"type":"Sma","period":0}}"#)?;
+        shared.insert("fast", Indicator::sma(3)?);
+        let node = StrategyNode::Shared {
+            indicators: shared,
+            node: Box::new(StrategyNode::MultiTimeframe {
+                timeframes: [("1h".to_string(), Timeframe::new(Resample::Bars(0)))].into(),
+                node: Box::new(StrategyNode::StateMachine {
+                    states: vec![
+                        MachineState::new("idle").on(
+                            Condition::Compare {
+                                left: Operand::Indicator("fast".to_string()),
+                                right: Operand::Indicator("missing".to_string()),
+                                operator: Operator::GreaterThan,
+                            },
+                            "idle",
+                            Some(Action::Buy),
+                        ),
+                        MachineState::new("orphan").with_timeout(1, "idle", None),
+                    ],
+                    current: 0,
+                    bars_in_state: 0,
+                }),
+            }),
+        };
+        let diagnostics = node.lint();
+        assert_eq!(
+            paths(&diagnostics),
+            vec![
+                ("root.Shared.indicators[\"slow\"]", Severity::Error),
+                (
+                    "root.Shared.node.MultiTimeframe.timeframes[\"1h\"]",
+                    Severity::Error
+                ),
+                (
+                    "root.Shared.node.MultiTimeframe.node.StateMachine.states[0].transitions[0].condition",
+                    Severity::Error
+                ),
+                (
+                    "root.Shared.node.MultiTimeframe.node.StateMachine.states[1]",
+                    Severity::Warning
+                ),
+            ]
+        );
+        assert_eq!(
+            diagnostics[2].error,
+            StrategyError::UnknownIndicator("missing".to_string())
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,287 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::TaResult,
+    strategy::{Action, Condition, StrategyError},
+};
+
+/// A named state of a `StrategyNode::StateMachine`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MachineState {
+    pub name: String,
+    /// Transitions checked in order on every bar spent in this state; the first whose
+    /// condition holds is taken.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub transitions: Vec<Transition>,
+    /// Transition taken once the machine has spent `bars` bars in this state without
+    /// taking any other transition.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<StateTimeout>,
+}
+
+/// A conditional transition to another state.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Transition {
+    pub condition: Condition,
+    /// Name of the state to move to.
+    pub to: String,
+    /// Action emitted when the transition is taken, `Hold` if `None`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub action: Option<Action>,
+}
+
+/// A transition taken after spending a number of bars in a state.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct StateTimeout {
+    pub bars: usize,
+    /// Name of the state to move to.
+    pub to: String,
+    /// Action emitted when the timeout fires, `Hold` if `None`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub action: Option<Action>,
+}
+
+impl MachineState {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            transitions: Vec::new(),
+            timeout: None,
+        }
+    }
+
+    /// Adds a transition to `to` taken when `condition` holds, emitting `action`.
+    pub fn on(
+        mut self,
+        condition: Condition,
+        to: impl Into<String>,
+        action: Option<Action>,
+    ) -> Self {
+        self.transitions.push(Transition {
+            condition,
+            to: to.into(),
+            action,
+        });
+        self
+    }
+
+    /// Sets the transition to `to` taken after `bars` bars in this state, emitting `action`.
+    pub fn with_timeout(
+        mut self,
+        bars: usize,
+        to: impl Into<String>,
+        action: Option<Action>,
+    ) -> Self {
+        self.timeout = Some(StateTimeout {
+            bars,
+            to: to.into(),
+            action,
+        });
+        self
+    }
+}
+
+/// Checks that `states` is not empty, that `current` is one of them, that state names are
+/// unique and that every transition targets an existing state. Transition conditions are not
+/// validated.
+pub(crate) fn validate_states(states: &[MachineState], current: usize) -> TaResult<()> {
+    if states.is_empty() {
+        return Err(StrategyError::EmptyStateMachine.into());
+    }
+    if current >= states.len() {
+        return Err(StrategyError::Configuration(format!(
+            "StateMachine has no state at index {current}"
+        ))
+        .into());
+    }
+    for (index, state) in states.iter().enumerate() {
+        if states[..index].iter().any(|s| s.name == state.name) {
+            return Err(StrategyError::DuplicateState(state.name.clone()).into());
+        }
+        let targets = state
+            .transitions
+            .iter()
+            .map(|t| &t.to)
+            .chain(state.timeout.iter().map(|t| &t.to));
+        for target in targets {
+            if !states.iter().any(|s| &s.name == target) {
+                return Err(StrategyError::UnknownState(target.clone()).into());
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(not(feature = "js"))]
+    use crate::Indicator;
+    #[cfg(feature = "js")]
+    use crate::indicators::indicator::Indicator;
+
+    use super::*;
+    use crate::error::{TaError, TaResult};
+    use crate::strategy::{MarketData, StrategyError, StrategyNode};
+    use crate::traits::Reset;
+    use crate::types::OutputType;
+
+    /// Waits for the price to drop below 30, then to recover above 40, then buys above 50
+    /// within 3 bars or goes back to idle.
+    fn machine() -> TaResult<StrategyNode> {
+        Ok(StrategyNode::StateMachine {
+            states: vec![
+                MachineState::new("idle").on(
+                    Condition::less_than(Indicator::sma(1)?, OutputType::Single(30.0)),
+                    "oversold",
+                    None,
+                ),
+                MachineState::new("oversold").on(
+                    Condition::greater_than(Indicator::sma(1)?, OutputType::Single(40.0)),
+                    "armed",
+                    None,
+                ),
+                MachineState::new("armed")
+                    .on(
+                        Condition::greater_than(Indicator::sma(1)?, OutputType::Single(50.0)),
+                        "idle",
+                        Some(Action::Buy),
+                    )
+                    .with_timeout(3, "idle", None),
+            ],
+            current: 0,
+            bars_in_state: 0,
+        })
+    }
+
+    fn run(node: &mut StrategyNode, prices: &[f64]) -> TaResult<Vec<(Action, String)>> {
+        prices
+            .iter()
+            .map(|price| {
+                let action = node.evaluate(&MarketData::Float(*price))?;
+                Ok((action, node.current_state().unwrap_or_default().to_string()))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_state_machine_transitions() -> TaResult<()> {
+        let mut node = machine()?;
+        node.validate()?;
+        let steps = run(&mut node, &[35.0, 25.0, 35.0, 45.0, 48.0, 55.0])?;
+        let expected = [
+            (Action::Hold, "idle"),
+            (Action::Hold, "oversold"),
+            (Action::Hold, "oversold"),
+            (Action::Hold, "armed"),
+            (Action::Hold, "armed"),
+            (Action::Buy, "idle"),
+        ];
+        assert_eq!(steps.len(), expected.len());
+        for ((action, state), (expected_action, expected_state)) in steps.iter().zip(expected) {
+            assert_eq!(*action, expected_action);
+            assert_eq!(state, expected_state);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_state_machine_timeout_aborts() -> TaResult<()> {
+        let mut node = machine()?;
+        let steps = run(&mut node, &[25.0, 45.0, 45.0, 45.0, 45.0, 55.0])?;
+        let states: Vec<&str> = steps.iter().map(|(_, s)| s.as_str()).collect();
+        assert_eq!(
+            states,
+            vec!["oversold", "armed", "armed", "armed", "idle", "idle"]
+        );
+        assert!(steps.iter().all(|(action, _)| *action == Action::Hold));
+        Ok(())
+    }
+
+    #[test]
+    fn test_state_machine_reset_and_serde() -> TaResult<()> {
+        let mut node = machine()?;
+        run(&mut node, &[25.0, 45.0])?;
+        assert_eq!(node.current_state(), Some("armed"));
+
+        let json = serde_json::to_string(&node)?;
+        let mut restored: StrategyNode = serde_json::from_str(&json)?;
+        assert_eq!(restored.current_state(), Some("armed"));
+        assert_eq!(
+            run(&mut restored, &[55.0])?,
+            vec![(Action::Buy, "idle".to_string())]
+        );
+
+        node.reset();
+        assert_eq!(node.current_state(), Some("idle"));
+        assert_eq!(node, machine()?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_state_machine_trace_reports_state() -> TaResult<()> {
+        let mut node = machine()?;
+        let trace = node.explain(&MarketData::Float(25.0))?;
+        assert_eq!(trace.state.as_deref(), Some("oversold"));
+        assert_eq!(trace.note.as_deref(), Some("transition: idle -> oversold"));
+        assert_eq!(trace.transitions.len(), 1);
+        assert_eq!(
+            trace.transitions[0].id,
+            "root.StateMachine.states[0].transitions[0].condition"
+        );
+        assert!(trace.transitions[0].result);
+
+        let trace = node.explain(&MarketData::Float(35.0))?;
+        assert_eq!(trace.state.as_deref(), Some("oversold"));
+        assert_eq!(trace.note, None);
+        assert!(!trace.transitions[0].result);
+        Ok(())
+    }
+
+    #[test]
+    fn test_state_machine_validation() -> TaResult<()> {
+        let empty = StrategyNode::StateMachine {
+            states: vec![],
+            current: 0,
+            bars_in_state: 0,
+        };
+        assert_eq!(
+            empty.validate(),
+            Err(TaError::Strategy(StrategyError::EmptyStateMachine))
+        );
+
+        let duplicate = StrategyNode::StateMachine {
+            states: vec![MachineState::new("idle"), MachineState::new("idle")],
+            current: 0,
+            bars_in_state: 0,
+        };
+        assert_eq!(
+            duplicate.validate(),
+            Err(TaError::Strategy(StrategyError::DuplicateState(
+                "idle".to_string()
+            )))
+        );
+
+        let unknown = StrategyNode::StateMachine {
+            states: vec![MachineState::new("idle").with_timeout(1, "missing", None)],
+            current: 0,
+            bars_in_state: 0,
+        };
+        assert_eq!(
+            unknown.validate(),
+            Err(TaError::Strategy(StrategyError::UnknownState(
+                "missing".to_string()
+            )))
+        );
+
+        let out_of_range = StrategyNode::StateMachine {
+            states: vec![MachineState::new("idle")],
+            current: 1,
+            bars_in_state: 0,
+        };
+        assert!(matches!(
+            out_of_range.validate(),
+            Err(TaError::Strategy(StrategyError::Configuration(_)))
+        ));
+        Ok(())
+    }
+}
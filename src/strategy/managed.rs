@@ -0,0 +1,414 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::TaResult,
+    strategy::{
+        Action, MarketData, StrategyNode,
+        context::Context,
+        position::{Exit, Position, Side, Stop},
+    },
+    traits::{Candle, Period, Reset},
+};
+
+/// A strategy that tracks the position opened by its actions and manages its exits.
+///
+/// Non-Hold actions open a position when flat and close it when they point the other way;
+/// actions in the direction of the open position are reported as `Hold`. While a position is
+/// open the stop loss and take profit are checked against the bar's low and high before the
+/// strategy is evaluated, and a hit closes the position with the opposite action. When both
+/// are hit within the same bar the stop loss wins. With `trailing` enabled the stop follows
+/// the most favourable price reached since entry at the distance captured at entry.
+///
+/// The strategy tree can branch on the position through `Condition::Position` and
+/// `Condition::BarsInTrade`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ManagedStrategy {
+    pub strategy: StrategyNode,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stop_loss: Option<Stop>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub take_profit: Option<Stop>,
+    /// Trail the stop loss behind the most favourable price reached since entry.
+    #[serde(default)]
+    pub trailing: bool,
+    #[serde(default)]
+    position: Position,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    last_exit: Option<Exit>,
+    /// Time at which the last losing trade was closed, if known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    last_loss: Option<DateTime<Utc>>,
+}
+
+impl ManagedStrategy {
+    pub fn new(strategy: StrategyNode) -> Self {
+        Self {
+            strategy,
+            stop_loss: None,
+            take_profit: None,
+            trailing: false,
+            position: Position::default(),
+            last_exit: None,
+            last_loss: None,
+        }
+    }
+
+    /// Sets the stop loss placed when a position is opened.
+    pub fn with_stop_loss(mut self, stop: Stop) -> Self {
+        self.stop_loss = Some(stop);
+        self
+    }
+
+    /// Sets the take profit placed when a position is opened.
+    pub fn with_take_profit(mut self, stop: Stop) -> Self {
+        self.take_profit = Some(stop);
+        self
+    }
+
+    /// Makes the stop loss trail the most favourable price reached since entry.
+    pub fn with_trailing_stop(mut self) -> Self {
+        self.trailing = true;
+        self
+    }
+
+    /// Returns the currently open position.
+    pub fn position(&self) -> &Position {
+        &self.position
+    }
+
+    /// Returns the time at which the last losing trade was closed. Only trades closed on bars
+    /// evaluated with `evaluate_at` are taken into account.
+    pub fn last_loss(&self) -> Option<DateTime<Utc>> {
+        self.last_loss
+    }
+
+    /// Returns the reason the position was closed on the last evaluated bar, if it was.
+    pub fn last_exit(&self) -> Option<Exit> {
+        self.last_exit
+    }
+
+    /// Evaluates the strategy against market data, returning the action to execute.
+    pub fn evaluate(&mut self, data: &MarketData) -> TaResult<Action> {
+        self.evaluate_in(data, None)
+    }
+
+    /// Evaluates the strategy like `evaluate` for a bar with the given timestamp, which
+    /// time based conditions are checked against.
+    pub fn evaluate_at(&mut self, data: &MarketData, timestamp: DateTime<Utc>) -> TaResult<Action> {
+        self.evaluate_in(data, Some(timestamp))
+    }
+
+    fn evaluate_in(
+        &mut self,
+        data: &MarketData,
+        timestamp: Option<DateTime<Utc>>,
+    ) -> TaResult<Action> {
+        for stop in self.stop_loss.iter_mut().chain(self.take_profit.iter_mut()) {
+            stop.update(data)?;
+        }
+        self.last_exit = None;
+        if !self.position.is_flat() {
+            self.position.bars_in_trade += 1;
+            if let Some(exit) = self.hit_exit(data) {
+                // The tree still has to see every bar to keep its indicators in sync.
+                self.strategy.update_with(
+                    data,
+                    &Context {
+                        timestamp,
+                        ..Context::default()
+                    },
+                )?;
+                let price = match exit {
+                    Exit::TakeProfit => self.position.target_price,
+                    _ => self.position.stop_price,
+                };
+                return Ok(self.close(exit, price.unwrap_or(data.close()), timestamp));
+            }
+            self.trail(data);
+        }
+
+        let context = Context {
+            position: self.position,
+            timestamp,
+            last_loss: self.last_loss,
+            ..Context::default()
+        };
+        let action = self.strategy.evaluate_with(data, &context)?;
+        match (self.position.side, Side::of(action)) {
+            (_, Side::Flat) => Ok(Action::Hold),
+            (Side::Flat, side) => {
+                self.open(side, data.close())?;
+                Ok(action)
+            }
+            (current, side) if current == side => Ok(Action::Hold),
+            _ => {
+                self.close(Exit::Signal, data.close(), timestamp);
+                Ok(action)
+            }
+        }
+    }
+
+    fn open(&mut self, side: Side, price: f64) -> TaResult<()> {
+        let stop = match &self.stop_loss {
+            Some(stop) => Some(stop.distance(price)?),
+            None => None,
+        };
+        let target = match &self.take_profit {
+            Some(target) => Some(target.distance(price)?),
+            None => None,
+        };
+        let direction = if side == Side::Long { 1.0 } else { -1.0 };
+        self.position = Position {
+            side,
+            entry_price: price,
+            bars_in_trade: 0,
+            stop_price: stop.map(|distance| price - direction * distance),
+            target_price: target.map(|distance| price + direction * distance),
+            best_price: price,
+            trail_distance: stop.filter(|_| self.trailing),
+        };
+        Ok(())
+    }
+
+    fn close(&mut self, exit: Exit, price: f64, timestamp: Option<DateTime<Utc>>) -> Action {
+        let action = self.position.side.exit_action();
+        let profit = match self.position.side {
+            Side::Long => price - self.position.entry_price,
+            Side::Short => self.position.entry_price - price,
+            Side::Flat => 0.0,
+        };
+        if profit < 0.0 {
+            self.last_loss = timestamp.or(self.last_loss);
+        }
+        self.position = Position::default();
+        self.last_exit = Some(exit);
+        action
+    }
+
+    /// Returns the exit triggered by the bar's range, if any.
+    fn hit_exit(&self, data: &MarketData) -> Option<Exit> {
+        let position = &self.position;
+        let (stopped, targeted) = match position.side {
+            Side::Flat => return None,
+            Side::Long => (
+                position.stop_price.is_some_and(|stop| data.low() <= stop),
+                position
+                    .target_price
+                    .is_some_and(|target| data.high() >= target),
+            ),
+            Side::Short => (
+                position.stop_price.is_some_and(|stop| data.high() >= stop),
+                position
+                    .target_price
+                    .is_some_and(|target| data.low() <= target),
+            ),
+        };
+        if stopped {
+            let trailed = position.best_price != position.entry_price;
+            Some(if position.trail_distance.is_some() && trailed {
+                Exit::TrailingStop
+            } else {
+                Exit::StopLoss
+            })
+        } else if targeted {
+            Some(Exit::TakeProfit)
+        } else {
+            None
+        }
+    }
+
+    /// Moves a trailing stop behind the most favourable price reached so far.
+    fn trail(&mut self, data: &MarketData) {
+        let position = &mut self.position;
+        let Some(distance) = position.trail_distance else {
+            return;
+        };
+        match position.side {
+            Side::Long => {
+                position.best_price = position.best_price.max(data.high());
+                let stop = position.best_price - distance;
+                position.stop_price = Some(position.stop_price.map_or(stop, |s| s.max(stop)));
+            }
+            Side::Short => {
+                position.best_price = position.best_price.min(data.low());
+                let stop = position.best_price + distance;
+                position.stop_price = Some(position.stop_price.map_or(stop, |s| s.min(stop)));
+            }
+            Side::Flat => {}
+        }
+    }
+}
+
+impl Reset for ManagedStrategy {
+    fn reset(&mut self) {
+        self.strategy.reset();
+        for stop in self.stop_loss.iter_mut().chain(self.take_profit.iter_mut()) {
+            stop.reset();
+        }
+        self.position = Position::default();
+        self.last_exit = None;
+        self.last_loss = None;
+    }
+}
+
+impl Period for ManagedStrategy {
+    fn period(&self) -> usize {
+        self.strategy.period()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(not(feature = "js"))]
+    use crate::Indicator;
+    #[cfg(feature = "js")]
+    use crate::indicators::indicator::Indicator;
+
+    use super::*;
+    use crate::strategy::Condition;
+    use crate::strategy::condition::Operator;
+    use crate::types::OutputType;
+    use chipa_ta_utils::Bar;
+
+    /// Buys when flat and above 100, sells when the price drops below 90.
+    fn trend() -> TaResult<StrategyNode> {
+        Ok(StrategyNode::If {
+            condition: Condition::And(vec![
+                Condition::Position(Side::Flat),
+                Condition::greater_than(Indicator::sma(1)?, OutputType::Single(100.0)),
+            ]),
+            then_branch: Box::new(StrategyNode::Action(Action::Buy)),
+            else_branch: Some(Box::new(StrategyNode::If {
+                condition: Condition::less_than(Indicator::sma(1)?, OutputType::Single(90.0)),
+                then_branch: Box::new(StrategyNode::Action(Action::Sell)),
+                else_branch: None,
+            })),
+        })
+    }
+
+    fn bar(high: f64, low: f64, close: f64) -> MarketData {
+        MarketData::Bar(Bar::new().set_high(high).set_low(low).set_close(close))
+    }
+
+    #[test]
+    fn test_signal_opens_and_closes_position() -> TaResult<()> {
+        let mut managed = ManagedStrategy::new(trend()?);
+        assert_eq!(managed.evaluate(&MarketData::Float(95.0))?, Action::Hold);
+        assert_eq!(managed.evaluate(&MarketData::Float(101.0))?, Action::Buy);
+        assert_eq!(managed.position().side, Side::Long);
+        assert_eq!(managed.position().entry_price, 101.0);
+        assert_eq!(managed.evaluate(&MarketData::Float(105.0))?, Action::Hold);
+        assert_eq!(managed.position().bars_in_trade, 1);
+        assert_eq!(managed.evaluate(&MarketData::Float(85.0))?, Action::Sell);
+        assert_eq!(managed.last_exit(), Some(Exit::Signal));
+        assert!(managed.position().is_flat());
+        Ok(())
+    }
+
+    #[test]
+    fn test_percent_stop_loss_and_take_profit() -> TaResult<()> {
+        let mut managed = ManagedStrategy::new(trend()?)
+            .with_stop_loss(Stop::Percent(5.0))
+            .with_take_profit(Stop::Fixed(10.0));
+        assert_eq!(managed.evaluate(&bar(100.0, 100.0, 100.0))?, Action::Hold);
+        assert_eq!(managed.evaluate(&bar(120.0, 120.0, 120.0))?, Action::Buy);
+        assert_eq!(managed.position().stop_price, Some(114.0));
+        assert_eq!(managed.position().target_price, Some(130.0));
+
+        assert_eq!(managed.evaluate(&bar(125.0, 116.0, 118.0))?, Action::Hold);
+        assert_eq!(managed.evaluate(&bar(131.0, 121.0, 125.0))?, Action::Sell);
+        assert_eq!(managed.last_exit(), Some(Exit::TakeProfit));
+
+        assert_eq!(managed.evaluate(&bar(120.0, 120.0, 120.0))?, Action::Buy);
+        assert_eq!(managed.evaluate(&bar(140.0, 110.0, 120.0))?, Action::Sell);
+        assert_eq!(managed.last_exit(), Some(Exit::StopLoss));
+        Ok(())
+    }
+
+    #[test]
+    fn test_trailing_stop_follows_best_price() -> TaResult<()> {
+        let mut managed = ManagedStrategy::new(trend()?)
+            .with_stop_loss(Stop::Fixed(5.0))
+            .with_trailing_stop();
+        managed.evaluate(&bar(101.0, 101.0, 101.0))?;
+        assert_eq!(managed.position().stop_price, Some(96.0));
+        managed.evaluate(&bar(110.0, 104.0, 108.0))?;
+        assert_eq!(managed.position().stop_price, Some(105.0));
+        managed.evaluate(&bar(108.0, 106.0, 107.0))?;
+        assert_eq!(managed.position().stop_price, Some(105.0));
+        assert_eq!(managed.evaluate(&bar(107.0, 104.0, 106.0))?, Action::Sell);
+        assert_eq!(managed.last_exit(), Some(Exit::TrailingStop));
+        Ok(())
+    }
+
+    #[test]
+    fn test_atr_stop_uses_atr_at_entry() -> TaResult<()> {
+        let mut managed = ManagedStrategy::new(trend()?).with_stop_loss(Stop::atr(1, 2.0)?);
+        managed.evaluate(&bar(104.0, 100.0, 102.0))?;
+        let atr = match &managed.stop_loss {
+            Some(Stop::Atr { atr, .. }) => atr.prev()?,
+            _ => unreachable!(),
+        };
+        let OutputType::Single(atr) = atr else {
+            unreachable!()
+        };
+        assert_eq!(managed.position().stop_price, Some(102.0 - 2.0 * atr));
+        Ok(())
+    }
+
+    #[test]
+    fn test_branch_on_bars_in_trade() -> TaResult<()> {
+        let strategy = StrategyNode::If {
+            condition: Condition::BarsInTrade {
+                operator: Operator::GreaterThanOrEqual,
+                bars: 2,
+            },
+            then_branch: Box::new(StrategyNode::Action(Action::Sell)),
+            else_branch: Some(Box::new(StrategyNode::If {
+                condition: Condition::Position(Side::Flat),
+                then_branch: Box::new(StrategyNode::Action(Action::Buy)),
+                else_branch: None,
+            })),
+        };
+        let mut managed = ManagedStrategy::new(strategy);
+        let data = MarketData::Float(100.0);
+        let actions = (0..6)
+            .map(|_| managed.evaluate(&data))
+            .collect::<TaResult<Vec<_>>>()?;
+        assert_eq!(
+            actions,
+            vec![
+                Action::Buy,
+                Action::Hold,
+                Action::Sell,
+                Action::Buy,
+                Action::Hold,
+                Action::Sell
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_reset_and_serde_roundtrip() -> TaResult<()> {
+        let mut managed = ManagedStrategy::new(trend()?)
+            .with_stop_loss(Stop::Percent(2.0))
+            .with_take_profit(Stop::Fixed(3.0))
+            .with_trailing_stop();
+        managed.evaluate(&MarketData::Float(101.0))?;
+        managed.evaluate(&MarketData::Float(102.0))?;
+
+        let json = serde_json::to_string(&managed)?;
+        let restored: ManagedStrategy = serde_json::from_str(&json)?;
+        assert_eq!(restored.position(), managed.position());
+        assert_eq!(restored.stop_loss, managed.stop_loss);
+        assert!(restored.trailing);
+
+        managed.reset();
+        assert!(managed.position().is_flat());
+        assert_eq!(managed.last_exit(), None);
+        assert_eq!(managed.evaluate(&MarketData::Float(101.0))?, Action::Buy);
+        Ok(())
+    }
+}
@@ -1,13 +1,43 @@
 pub mod action;
 pub mod condition;
+pub mod context;
 pub mod error;
+pub mod file;
+#[cfg(feature = "chipa_lang")]
+mod lang;
+pub mod limit;
+pub mod lint;
+pub mod machine;
+pub mod managed;
+pub mod multi_asset;
 pub mod node;
+pub mod position;
+pub mod registry;
+pub mod schema;
+pub mod session;
+pub mod signal;
+mod simplify;
 pub mod strat;
+pub mod timeframe;
+pub mod trace;
 pub mod wrapper;
 
 // Public re-exports for easy access
 pub use action::Action;
-pub use condition::Condition;
+pub use condition::{Condition, Operand};
+pub use context::Context;
 pub use error::StrategyError;
+pub use file::{Migration, Migrations, StrategyFile, STRATEGY_FILE_VERSION};
+pub use limit::RateLimit;
+pub use lint::{Diagnostic, Severity};
+pub use machine::{MachineState, StateTimeout, Transition};
+pub use managed::ManagedStrategy;
+pub use multi_asset::{Alignment, MultiAssetStrategy, Snapshot};
 pub use chipa_ta_utils::MarketData;
 pub use node::StrategyNode;
+pub use position::{Exit, Position, Side, Stop};
+pub use registry::IndicatorRegistry;
+pub use session::Session;
+pub use signal::{Expiry, Signal};
+pub use timeframe::{Resample, Timeframe};
+pub use trace::{ConditionTrace, NodeTrace};
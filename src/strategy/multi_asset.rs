@@ -0,0 +1,400 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
+
+#[cfg(not(feature = "js"))]
+use crate::Indicator;
+#[cfg(feature = "js")]
+use crate::indicators::indicator::Indicator;
+use crate::{
+    error::TaResult,
+    strategy::{
+        Action, Context, MarketData, StrategyError, StrategyNode, registry::IndicatorRegistry,
+    },
+    traits::Reset,
+};
+
+/// Bars of several symbols received at the same time. Symbols without a new bar are left out.
+#[derive(Debug, Clone, Default)]
+pub struct Snapshot {
+    /// Time of the snapshot, required by `Alignment::MaxAge` and time based conditions.
+    pub timestamp: Option<DateTime<Utc>>,
+    pub bars: BTreeMap<String, MarketData>,
+}
+
+impl Snapshot {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates an empty snapshot taken at `timestamp`.
+    pub fn at(timestamp: DateTime<Utc>) -> Self {
+        Self {
+            timestamp: Some(timestamp),
+            bars: BTreeMap::new(),
+        }
+    }
+
+    /// Adds the new bar of `symbol`.
+    pub fn with(mut self, symbol: impl Into<String>, data: MarketData) -> Self {
+        self.bars.insert(symbol.into(), data);
+        self
+    }
+}
+
+/// When the strategy of a symbol with a new bar is evaluated, given that the other symbols
+/// may not have updated in the same snapshot.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub enum Alignment {
+    /// Evaluate only on snapshots carrying a bar for every symbol.
+    Complete,
+    /// Evaluate once every symbol has updated at least once, reading the symbols missing
+    /// from the snapshot at their last known values.
+    #[default]
+    ForwardFill,
+    /// Like `ForwardFill`, but only while every symbol has updated within `secs` seconds
+    /// of the snapshot. Requires timestamped snapshots.
+    MaxAge { secs: u64 },
+}
+
+impl Alignment {
+    /// Returns the longest time a symbol may go without updating, if limited. Fails if it
+    /// can not be represented as a `TimeDelta`.
+    pub fn max_age(&self) -> TaResult<Option<TimeDelta>> {
+        let Alignment::MaxAge { secs } = *self else {
+            return Ok(None);
+        };
+        i64::try_from(secs)
+            .ok()
+            .and_then(TimeDelta::try_seconds)
+            .map(Some)
+            .ok_or_else(|| {
+                StrategyError::Configuration(format!("MaxAge of {secs}s is too long")).into()
+            })
+    }
+}
+
+/// Strategy over several symbols, emitting one action per symbol.
+///
+/// Each symbol has its own indicators, updated with its bars only, that the strategy trees
+/// of every symbol can reference through `Operand::Symbol`. The tree of a symbol is updated
+/// with the bars of that symbol and evaluated when the symbol has a new bar and the
+/// `alignment` policy allows it; otherwise the symbol holds.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MultiAssetStrategy {
+    /// Indicators of each symbol, keyed by symbol and indicator id.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub indicators: BTreeMap<String, IndicatorRegistry>,
+    /// Strategy tree of each traded symbol.
+    pub strategies: BTreeMap<String, StrategyNode>,
+    #[serde(default)]
+    pub alignment: Alignment,
+    /// Symbols that have updated so far, with the timestamp of their last snapshot.
+    #[serde(skip)]
+    last_seen: BTreeMap<String, Option<DateTime<Utc>>>,
+}
+
+impl MultiAssetStrategy {
+    pub fn new(alignment: Alignment) -> Self {
+        Self {
+            indicators: BTreeMap::new(),
+            strategies: BTreeMap::new(),
+            alignment,
+            last_seen: BTreeMap::new(),
+        }
+    }
+
+    /// Registers `indicator` under `id`, computed on the bars of `symbol`.
+    pub fn with_indicator(
+        mut self,
+        symbol: impl Into<String>,
+        id: impl Into<String>,
+        indicator: Indicator,
+    ) -> Self {
+        self.indicators
+            .entry(symbol.into())
+            .or_default()
+            .insert(id, indicator);
+        self
+    }
+
+    /// Sets the strategy tree that trades `symbol`.
+    pub fn with_strategy(mut self, symbol: impl Into<String>, strategy: StrategyNode) -> Self {
+        self.strategies.insert(symbol.into(), strategy);
+        self
+    }
+
+    /// Returns every symbol known to the strategy, traded or only referenced, in order.
+    pub fn symbols(&self) -> impl Iterator<Item = &str> {
+        let mut symbols: Vec<&str> = self
+            .indicators
+            .keys()
+            .chain(self.strategies.keys())
+            .map(String::as_str)
+            .collect();
+        symbols.sort_unstable();
+        symbols.dedup();
+        symbols.into_iter()
+    }
+
+    /// Updates the symbols in `snapshot` and returns the action of every traded symbol it
+    /// carries a bar for.
+    pub fn evaluate(&mut self, snapshot: &Snapshot) -> TaResult<BTreeMap<String, Action>> {
+        if let Some(symbol) = snapshot
+            .bars
+            .keys()
+            .find(|s| !self.indicators.contains_key(*s) && !self.strategies.contains_key(*s))
+        {
+            return Err(StrategyError::UnknownSymbol(symbol.clone()).into());
+        }
+        let context = Context {
+            timestamp: snapshot.timestamp,
+            ..Context::default()
+        };
+        for (symbol, data) in &snapshot.bars {
+            if let Some(indicators) = self.indicators.get_mut(symbol) {
+                indicators.update(data)?;
+            }
+            if let Some(strategy) = self.strategies.get_mut(symbol) {
+                strategy.update_with(data, &context)?;
+            }
+            self.last_seen.insert(symbol.clone(), snapshot.timestamp);
+        }
+
+        let aligned = self.aligned(snapshot)?;
+        let context = Context {
+            symbols: Some(&self.indicators),
+            ..context
+        };
+        let mut actions = BTreeMap::new();
+        for (symbol, strategy) in self.strategies.iter_mut() {
+            let Some(data) = snapshot.bars.get(symbol) else {
+                continue;
+            };
+            let action = if aligned {
+                strategy
+                    .evaluate_outcome(data, &context, None, false)?
+                    .action
+            } else {
+                Action::Hold
+            };
+            actions.insert(symbol.clone(), action);
+        }
+        Ok(actions)
+    }
+
+    /// Returns whether the alignment policy allows evaluating on `snapshot`.
+    fn aligned(&self, snapshot: &Snapshot) -> TaResult<bool> {
+        let mut symbols = self.symbols();
+        Ok(match self.alignment {
+            Alignment::Complete => symbols.all(|s| snapshot.bars.contains_key(s)),
+            Alignment::ForwardFill => symbols.all(|s| self.last_seen.contains_key(s)),
+            Alignment::MaxAge { .. } => {
+                let now = snapshot.timestamp.ok_or(StrategyError::MissingTimestamp)?;
+                let max_age = self.alignment.max_age()?.unwrap_or(TimeDelta::MAX);
+                symbols.all(
+                    |s| matches!(self.last_seen.get(s), Some(Some(seen)) if now - *seen <= max_age),
+                )
+            }
+        })
+    }
+
+    /// Validates every strategy tree, resolving `Operand::Symbol` references against the
+    /// indicators of the symbols.
+    pub fn validate(&self) -> TaResult<()> {
+        self.alignment.max_age()?;
+        for indicators in self.indicators.values() {
+            indicators.validate()?;
+        }
+        let context = Context {
+            symbols: Some(&self.indicators),
+            ..Context::default()
+        };
+        for strategy in self.strategies.values() {
+            strategy.validate_with(&context)?;
+        }
+        Ok(())
+    }
+}
+
+impl Reset for MultiAssetStrategy {
+    fn reset(&mut self) {
+        for indicators in self.indicators.values_mut() {
+            indicators.reset();
+        }
+        for strategy in self.strategies.values_mut() {
+            strategy.reset();
+        }
+        self.last_seen.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::TaError;
+    use crate::strategy::condition::Operator;
+    use crate::strategy::{Condition, Operand};
+    use crate::types::OutputType;
+    use chrono::TimeZone;
+
+    fn minute(minute: i64) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 1, 2, 9, 0, 0).unwrap() + TimeDelta::minutes(minute)
+    }
+
+    fn dxy(indicator: &str) -> Operand {
+        Operand::Symbol {
+            symbol: "DXY".to_string(),
+            indicator: indicator.to_string(),
+        }
+    }
+
+    /// Sells EURUSD below 1.05 while the DXY is trending up.
+    fn pairs(alignment: Alignment) -> TaResult<MultiAssetStrategy> {
+        Ok(MultiAssetStrategy::new(alignment)
+            .with_indicator("DXY", "fast", Indicator::sma(1)?)
+            .with_indicator("DXY", "slow", Indicator::sma(3)?)
+            .with_strategy(
+                "EURUSD",
+                StrategyNode::If {
+                    condition: Condition::And(vec![
+                        Condition::less_than(Indicator::sma(1)?, OutputType::Single(1.05)),
+                        Condition::Compare {
+                            left: dxy("fast"),
+                            right: dxy("slow"),
+                            operator: Operator::GreaterThan,
+                        },
+                    ]),
+                    then_branch: Box::new(StrategyNode::Action(Action::Sell)),
+                    else_branch: None,
+                },
+            ))
+    }
+
+    fn snapshot(t: i64, eurusd: Option<f64>, dxy: Option<f64>) -> Snapshot {
+        let mut snapshot = Snapshot::at(minute(t));
+        if let Some(price) = eurusd {
+            snapshot = snapshot.with("EURUSD", MarketData::Float(price));
+        }
+        if let Some(price) = dxy {
+            snapshot = snapshot.with("DXY", MarketData::Float(price));
+        }
+        snapshot
+    }
+
+    fn eurusd(strategy: &mut MultiAssetStrategy, snapshot: &Snapshot) -> TaResult<Option<Action>> {
+        Ok(strategy.evaluate(snapshot)?.remove("EURUSD"))
+    }
+
+    #[test]
+    fn test_cross_symbol_condition() -> TaResult<()> {
+        let mut strategy = pairs(Alignment::ForwardFill)?;
+        strategy.validate()?;
+        assert_eq!(
+            strategy.symbols().collect::<Vec<_>>(),
+            vec!["DXY", "EURUSD"]
+        );
+
+        // EURUSD alone cannot be evaluated before the DXY has a value.
+        assert_eq!(
+            eurusd(&mut strategy, &snapshot(0, Some(1.04), None))?,
+            Some(Action::Hold)
+        );
+        assert_eq!(
+            eurusd(&mut strategy, &snapshot(1, Some(1.04), Some(100.0)))?,
+            Some(Action::Hold)
+        );
+        // DXY rises, then only EURUSD updates and reads its last known value.
+        let actions = strategy.evaluate(&snapshot(2, None, Some(101.0)))?;
+        assert!(actions.is_empty());
+        assert_eq!(
+            eurusd(&mut strategy, &snapshot(3, Some(1.04), None))?,
+            Some(Action::Sell)
+        );
+        assert_eq!(
+            eurusd(&mut strategy, &snapshot(4, Some(1.06), None))?,
+            Some(Action::Hold)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_complete_alignment_requires_every_symbol() -> TaResult<()> {
+        let mut strategy = pairs(Alignment::Complete)?;
+        strategy.evaluate(&snapshot(0, Some(1.04), Some(100.0)))?;
+        strategy.evaluate(&snapshot(1, None, Some(101.0)))?;
+        assert_eq!(
+            eurusd(&mut strategy, &snapshot(2, Some(1.04), None))?,
+            Some(Action::Hold)
+        );
+        assert_eq!(
+            eurusd(&mut strategy, &snapshot(3, Some(1.04), Some(102.0)))?,
+            Some(Action::Sell)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_max_age_alignment_holds_on_stale_symbols() -> TaResult<()> {
+        let mut strategy = pairs(Alignment::MaxAge { secs: 300 })?;
+        strategy.evaluate(&snapshot(0, Some(1.04), Some(100.0)))?;
+        strategy.evaluate(&snapshot(1, None, Some(101.0)))?;
+        assert_eq!(
+            eurusd(&mut strategy, &snapshot(6, Some(1.04), None))?,
+            Some(Action::Sell)
+        );
+        assert_eq!(
+            eurusd(&mut strategy, &snapshot(7, Some(1.04), None))?,
+            Some(Action::Hold)
+        );
+
+        let untimed = Snapshot::new().with("EURUSD", MarketData::Float(1.04));
+        assert_eq!(
+            strategy.evaluate(&untimed),
+            Err(TaError::Strategy(StrategyError::MissingTimestamp))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_unknown_symbols() -> TaResult<()> {
+        let mut strategy = pairs(Alignment::ForwardFill)?;
+        assert_eq!(
+            strategy.evaluate(&Snapshot::new().with("GBPUSD", MarketData::Float(1.2))),
+            Err(TaError::Strategy(StrategyError::UnknownSymbol(
+                "GBPUSD".to_string()
+            )))
+        );
+
+        strategy.indicators.clear();
+        assert_eq!(
+            strategy.validate(),
+            Err(TaError::Strategy(StrategyError::UnknownSymbol(
+                "DXY".to_string()
+            )))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_multi_asset_serde_and_reset() -> TaResult<()> {
+        let mut strategy = pairs(Alignment::MaxAge { secs: 60 })?;
+        let json = serde_json::to_string(&strategy)?;
+        assert_eq!(serde_json::from_str::<MultiAssetStrategy>(&json)?, strategy);
+
+        strategy.evaluate(&snapshot(0, Some(1.04), Some(100.0)))?;
+        strategy.reset();
+        assert_eq!(strategy, pairs(Alignment::MaxAge { secs: 60 })?);
+
+        let json = json.replace(r#""secs":60"#, &format!(r#""secs":{}"#, u64::MAX));
+        let mut endless: MultiAssetStrategy = serde_json::from_str(&json)?;
+        assert!(endless.validate().is_err());
+        assert!(
+            endless
+                .evaluate(&snapshot(0, Some(1.04), Some(100.0)))
+                .is_err()
+        );
+        Ok(())
+    }
+}
@@ -1,13 +1,21 @@
 use crate::error::{TaError, TaResult};
 use crate::preprocessing::PreprocessingStep;
 use crate::strategy::error::StrategyError;
+use crate::strategy::context::Context;
+use crate::strategy::limit::RateLimit;
+use crate::strategy::machine::{MachineState, Transition, validate_states};
+use crate::strategy::registry::IndicatorRegistry;
+use crate::strategy::timeframe::Timeframe;
+use crate::strategy::trace::NodeTrace;
 use crate::strategy::{Action, Condition, MarketData};
 use crate::traits::{Period, Reset};
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
 
 /// Aggregation modes for `Sequence` nodes.
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub enum SequenceMode {
     /// Return the first action-producing node's result (default behavior).
     First,
@@ -17,10 +25,277 @@ pub enum SequenceMode {
     Any,
     /// Return an action only if all action-producing nodes agree; otherwise Hold.
     All,
-    /// Return the action with the highest occurrence; on tie defaults to Hold.
+    /// Return the action with the highest occurrence; ties are resolved by the
+    /// sequence's `TieBreak` policy.
     Majority,
     /// Return an action if at least the specified percentage of nodes agree; otherwise Hold.
+    /// When several actions reach the threshold the most frequent one wins and
+    /// remaining ties are resolved by the sequence's `TieBreak` policy.
     Percentage(u8), // 0-100 representing percentage threshold
+    /// Weighted vote over every child node, `Hold` included. Each child's action score
+    /// (`StrongBuy` = +2 ... `StrongSell` = -2) is multiplied by its weight and the action
+    /// nearest to the sum is emitted, so a weight of 1 counts a `Buy` as one `Buy`.
+    /// Requires one non-negative weight per child node, not all of them zero.
+    Weighted(Vec<f64>),
+    /// Scored vote over every child node, `Hold` included. The action scores are summed
+    /// and compared against the thresholds: at or above `buy_threshold` emits `Buy`, at or
+    /// below `sell_threshold` emits `Sell`, anything in between emits `Hold`.
+    Score {
+        buy_threshold: f64,
+        sell_threshold: f64,
+    },
+}
+
+/// Tie-breaking policies used by `SequenceMode::Majority` and `SequenceMode::Percentage`
+/// when several actions share the highest count.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum TieBreak {
+    /// Emit `Hold` whenever the vote is tied (default).
+    #[default]
+    Hold,
+    /// Emit the tied action with the highest conviction (`StrongBuy`/`StrongSell` over
+    /// `Buy`/`Sell`); falls back to `Hold` if the strongest tied actions disagree.
+    PreferStronger,
+    /// Emit the tied action produced first in the sequence order.
+    PreferFirst,
+}
+
+impl TieBreak {
+    fn is_default(&self) -> bool {
+        *self == TieBreak::default()
+    }
+
+    /// Resolves a tie between `candidates`, which must be given in first-seen order.
+    fn resolve(&self, candidates: &[Action]) -> Action {
+        match candidates {
+            [] => Action::Hold,
+            [single] => *single,
+            _ => match self {
+                TieBreak::Hold => Action::Hold,
+                TieBreak::PreferFirst => candidates[0],
+                TieBreak::PreferStronger => {
+                    let strongest = candidates.iter().map(Action::conviction).max().unwrap_or(0);
+                    let mut strong = candidates.iter().filter(|a| a.conviction() == strongest);
+                    match (strong.next(), strong.next()) {
+                        (Some(action), None) => *action,
+                        _ => Action::Hold,
+                    }
+                }
+            },
+        }
+    }
+}
+
+impl SequenceMode {
+    /// Checks the mode's parameters for a sequence of `nodes` child nodes.
+    pub fn validate(&self, nodes: usize) -> Result<(), StrategyError> {
+        match self {
+            SequenceMode::Percentage(percentage) if *percentage > 100 => {
+                Err(StrategyError::InvalidPercentage(*percentage))
+            }
+            SequenceMode::Weighted(weights) if weights.len() != nodes => {
+                Err(StrategyError::WeightsMismatch {
+                    weights: weights.len(),
+                    nodes,
+                })
+            }
+            SequenceMode::Weighted(weights)
+                if weights.iter().any(|w| !w.is_finite() || *w < 0.0) =>
+            {
+                Err(StrategyError::Configuration(format!(
+                    "Weighted weights must be finite and non-negative, got {weights:?}"
+                )))
+            }
+            SequenceMode::Weighted(weights) if weights.iter().sum::<f64>() <= 0.0 => Err(
+                StrategyError::Configuration("Weighted weights must not all be zero".to_string()),
+            ),
+            SequenceMode::Score {
+                buy_threshold,
+                sell_threshold,
+            } if buy_threshold.is_nan()
+                || sell_threshold.is_nan()
+                || buy_threshold <= sell_threshold =>
+            {
+                Err(StrategyError::Configuration(format!(
+                    "Score buy_threshold ({buy_threshold}) must be greater than sell_threshold ({sell_threshold})"
+                )))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Returns the sum of the action scores, each multiplied by its weight, and the largest
+/// magnitude that sum could reach.
+fn weighted_sum(votes: &[Action], weights: impl Iterator<Item = f64> + Clone) -> (f64, f64) {
+    let sum = votes
+        .iter()
+        .zip(weights.clone())
+        .map(|(action, weight)| action.score() as f64 * weight)
+        .sum();
+    (sum, 2.0 * weights.take(votes.len()).sum::<f64>())
+}
+
+/// Result of evaluating a node: the action, its confidence and, when recorded,
+/// the ids of the nodes and conditions that produced it and the evaluation trace.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Outcome {
+    pub action: Action,
+    pub confidence: f64,
+    pub nodes: Vec<String>,
+    pub conditions: Vec<String>,
+    pub trace: Option<NodeTrace>,
+}
+
+impl Outcome {
+    fn new(action: Action, confidence: f64) -> Self {
+        Self {
+            action,
+            confidence,
+            nodes: Vec::new(),
+            conditions: Vec::new(),
+            trace: None,
+        }
+    }
+
+    fn hold() -> Self {
+        Self::new(Action::Hold, 0.0)
+    }
+}
+
+/// Aggregates the outcomes of a `Sequence` node's children according to `mode`.
+///
+/// The provenance of the children supporting the chosen action is merged into the result.
+/// Confidence is taken from the chosen child in `First`/`Any`/`Last` modes, derived from the
+/// score in `Weighted`/`Score` modes, and otherwise is the share of non-Hold votes agreeing
+/// with the chosen action times their mean confidence.
+///
+/// Also returns the indices of the supporting children.
+fn aggregate(
+    mode: &SequenceMode,
+    tie_break: TieBreak,
+    mut outcomes: Vec<Outcome>,
+) -> (Outcome, Vec<usize>) {
+    let votes: Vec<Action> = outcomes.iter().map(|o| o.action).collect();
+    let actions: Vec<Action> = votes.iter().copied().filter(|a| *a != Action::Hold).collect();
+    let mut score = None;
+    let chosen = match mode {
+        SequenceMode::First | SequenceMode::Any => {
+            actions.first().copied().unwrap_or(Action::Hold)
+        }
+        SequenceMode::Last => actions.last().copied().unwrap_or(Action::Hold),
+        SequenceMode::All => match actions.first() {
+            Some(first) if actions.iter().all(|a| a == first) => *first,
+            _ => Action::Hold,
+        },
+        SequenceMode::Majority => most_frequent(&tally(&actions), tie_break),
+        SequenceMode::Percentage(percentage) => {
+            let total = actions.len();
+            let qualifying: Vec<(Action, usize)> = tally(&actions)
+                .into_iter()
+                .filter(|&(_, c)| c * 100 >= *percentage as usize * total)
+                .collect();
+            most_frequent(&qualifying, tie_break)
+        }
+        SequenceMode::Weighted(weights) => {
+            let (sum, range) = weighted_sum(&votes, weights.iter().copied());
+            score = Some((sum, range));
+            Action::from_score(sum)
+        }
+        SequenceMode::Score {
+            buy_threshold,
+            sell_threshold,
+        } => {
+            let (sum, range) = weighted_sum(&votes, std::iter::repeat(1.0));
+            score = Some((sum, range));
+            if sum >= *buy_threshold {
+                Action::Buy
+            } else if sum <= *sell_threshold {
+                Action::Sell
+            } else {
+                Action::Hold
+            }
+        }
+    };
+    if chosen == Action::Hold {
+        return (Outcome::hold(), Vec::new());
+    }
+
+    let indices = 0..outcomes.len();
+    let supporting: Vec<usize> = match mode {
+        SequenceMode::First | SequenceMode::Any => indices
+            .clone()
+            .find(|&i| outcomes[i].action == chosen)
+            .into_iter()
+            .collect(),
+        SequenceMode::Last => indices
+            .rev()
+            .find(|&i| outcomes[i].action == chosen)
+            .into_iter()
+            .collect(),
+        SequenceMode::Weighted(_) | SequenceMode::Score { .. } => indices
+            .filter(|&i| {
+                let action = outcomes[i].action;
+                action != Action::Hold && (action.score() > 0) == (chosen.score() > 0)
+            })
+            .collect(),
+        _ => indices.filter(|&i| outcomes[i].action == chosen).collect(),
+    };
+    let confidence = match (mode, score) {
+        (_, Some((sum, range))) if range > 0.0 => (sum.abs() / range).min(1.0),
+        (_, Some(_)) => 0.0,
+        (SequenceMode::First | SequenceMode::Any | SequenceMode::Last, _) => {
+            supporting.first().map_or(0.0, |&i| outcomes[i].confidence)
+        }
+        _ => {
+            let agreement = supporting.len() as f64 / actions.len().max(1) as f64;
+            let mean = supporting.iter().map(|&i| outcomes[i].confidence).sum::<f64>()
+                / supporting.len().max(1) as f64;
+            agreement * mean
+        }
+    };
+
+    let mut outcome = Outcome::new(chosen, confidence);
+    for &i in &supporting {
+        outcome.nodes.append(&mut outcomes[i].nodes);
+        outcome.conditions.append(&mut outcomes[i].conditions);
+    }
+    (outcome, supporting)
+}
+
+/// Counts occurrences of each action, preserving the order in which actions were first seen.
+fn tally(actions: &[Action]) -> Vec<(Action, usize)> {
+    let mut counts: Vec<(Action, usize)> = Vec::new();
+    for action in actions {
+        match counts.iter_mut().find(|(a, _)| a == action) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((*action, 1)),
+        }
+    }
+    counts
+}
+
+/// Returns the most frequent action among `counts`, resolving ties with `tie_break`.
+fn most_frequent(counts: &[(Action, usize)], tie_break: TieBreak) -> Action {
+    let Some(max) = counts.iter().map(|&(_, c)| c).max() else {
+        return Action::Hold;
+    };
+    let tied: Vec<Action> = counts
+        .iter()
+        .filter(|&&(_, c)| c == max)
+        .map(|&(a, _)| a)
+        .collect();
+    tie_break.resolve(&tied)
+}
+
+/// Moves the trace of a child `outcome` into `record`, marking the child as chosen if `chosen`.
+fn adopt(record: Option<&mut NodeTrace>, outcome: &mut Outcome, chosen: bool) {
+    if let (Some(record), Some(child)) = (record, outcome.trace.take()) {
+        if chosen {
+            record.chosen.push(child.id.clone());
+        }
+        record.children.push(child);
+    }
 }
 
 /// AST node for composable trading strategies.
@@ -51,12 +326,25 @@ pub enum StrategyNode {
         else_branch: Option<Box<StrategyNode>>,
     },
 
+    /// Bar based cooldown: after `action` emits a non-Hold action, it is not evaluated again
+    /// for the next `cooldown` bars. The cooldown counts down on every `update`, so it keeps
+    /// running while the node is not evaluated.
     Timeout {
         cooldown: usize,           // Cooldown period in candles
-        remaining: usize,          // Remaining cooldown candles
+        remaining: usize,          // Candles left until the action may fire again
         action: Box<StrategyNode>, // Action to execute after cooldown
     },
 
+    /// Time based limit on the actions emitted by `action`, driven by the bar timestamps.
+    /// Requires the evaluation context to carry a timestamp.
+    RateLimit {
+        limit: RateLimit,
+        action: Box<StrategyNode>,
+        /// Timestamps of the recent non-Hold actions still counting towards the limit.
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        fired: Vec<DateTime<Utc>>,
+    },
+
     /// Action node: produce a trading action (Buy, Sell, Hold).
     Action(Action),
 
@@ -64,6 +352,39 @@ pub enum StrategyNode {
     Sequence {
         mode: SequenceMode,
         nodes: Vec<StrategyNode>,
+        /// Tie-breaking policy for `Majority` and `Percentage` modes.
+        #[serde(default, skip_serializing_if = "TieBreak::is_default")]
+        tie_break: TieBreak,
+    },
+
+    /// Finite state machine: starts in the first state and, on every bar, takes the first
+    /// transition of the current state whose condition holds, or its timeout once it has
+    /// spent enough bars there. Emits the action of the transition taken, otherwise Hold.
+    StateMachine {
+        states: Vec<MachineState>,
+        /// Index of the current state.
+        #[serde(default)]
+        current: usize,
+        /// Number of bars evaluated since the current state was entered.
+        #[serde(default)]
+        bars_in_state: usize,
+    },
+
+    /// Owns indicators shared by the conditions of `node`, which reference them by id through
+    /// `Operand::Indicator`. Each shared indicator is updated once per bar, before `node`.
+    /// References resolve against the closest enclosing `Shared` node only.
+    Shared {
+        indicators: IndicatorRegistry,
+        node: Box<StrategyNode>,
+    },
+
+    /// Resamples the base bars into the named higher timeframes, whose indicators the
+    /// conditions of `node` reference through `Operand::Timeframe`. Every timeframe is
+    /// updated once per bar, before `node`. The node holds until every timeframe has closed
+    /// its first bucket.
+    MultiTimeframe {
+        timeframes: BTreeMap<String, Timeframe>,
+        node: Box<StrategyNode>,
     },
 }
 
@@ -75,10 +396,18 @@ impl Default for StrategyNode {
 
 impl StrategyNode {
     /// Update the `StrategyNode` with a new market data.
+    ///
+    /// Every indicator of the tree is updated exactly once.
     pub fn update(&mut self, data: &MarketData) -> TaResult<()> {
+        self.update_with(data, &Context::default())
+    }
+
+    /// Updates the node with new market data and the strategy's `context`, whose timestamp
+    /// assigns the bar to the time buckets of `MultiTimeframe` nodes.
+    pub fn update_with(&mut self, data: &MarketData, context: &Context) -> TaResult<()> {
         match self {
-            StrategyNode::Preprocess { then_branch, .. } => {
-                then_branch.update(data)?;
+            StrategyNode::Preprocess { step, then_branch } => {
+                then_branch.update_with(&step.apply(data), context)?;
                 Ok(())
             }
             StrategyNode::If {
@@ -87,17 +416,39 @@ impl StrategyNode {
                 else_branch,
             } => {
                 condition.update(data)?;
-                then_branch.update(data)?;
+                then_branch.update_with(data, context)?;
                 if let Some(else_node) = else_branch {
-                    else_node.update(data)?;
+                    else_node.update_with(data, context)?;
                 }
                 Ok(())
             }
-            StrategyNode::Timeout { action, .. } => action.update(data),
+            StrategyNode::Timeout {
+                action, remaining, ..
+            } => {
+                *remaining = remaining.saturating_sub(1);
+                action.update_with(data, context)
+            }
+            StrategyNode::RateLimit { action, .. } => action.update_with(data, context),
             StrategyNode::Action(..) => Ok(()),
+            StrategyNode::StateMachine { states, .. } => {
+                for transition in states.iter_mut().flat_map(|s| s.transitions.iter_mut()) {
+                    transition.condition.update(data)?;
+                }
+                Ok(())
+            }
+            StrategyNode::Shared { indicators, node } => {
+                indicators.update(data)?;
+                node.update_with(data, context)
+            }
+            StrategyNode::MultiTimeframe { timeframes, node } => {
+                for timeframe in timeframes.values_mut() {
+                    timeframe.update(data, context.timestamp)?;
+                }
+                node.update_with(data, context)
+            }
             StrategyNode::Sequence { nodes, .. } => {
                 for node in nodes {
-                    node.update(data)?;
+                    node.update_with(data, context)?;
                 }
                 Ok(())
             }
@@ -106,13 +457,64 @@ impl StrategyNode {
 
     /// Evaluate the strategy node against market data, returning a trading `Action`.
     pub fn evaluate(&mut self, data: &MarketData) -> TaResult<Action> {
-        // Before each evaluation, update the node with the latest market data.
-        self.update(data)?;
-        match self {
+        self.evaluate_with(data, &Context::default())
+    }
+
+    /// Evaluate the strategy node against market data and the strategy's `context`, which
+    /// exposes state such as the open position to the conditions of the tree.
+    pub fn evaluate_with(&mut self, data: &MarketData, context: &Context) -> TaResult<Action> {
+        self.update_with(data, context)?;
+        self.evaluate_outcome(data, context, None, false)
+            .map(|outcome| outcome.action)
+    }
+
+    /// Evaluates the node like `evaluate`, returning a trace of the evaluation instead of
+    /// only the action.
+    ///
+    /// The trace records for every evaluated node its action, the condition results with
+    /// their operand values, and the children that were chosen. The root node is identified
+    /// as `root.{kind}`. Evaluating with `explain` has the same side effects as `evaluate`.
+    pub fn explain(&mut self, data: &MarketData) -> TaResult<NodeTrace> {
+        self.explain_with(data, &Context::default())
+    }
+
+    /// Explains the evaluation of the node against market data and the strategy's `context`.
+    pub fn explain_with(&mut self, data: &MarketData, context: &Context) -> TaResult<NodeTrace> {
+        self.update_with(data, context)?;
+        let outcome = self.evaluate_outcome(data, context, Some("root"), true)?;
+        Ok(outcome
+            .trace
+            .unwrap_or_else(|| NodeTrace::new(format!("root.{}", self.kind()), self.kind())))
+    }
+
+    /// Evaluates the node like `evaluate`, also computing the confidence of the action.
+    /// Unlike `evaluate` the node is not updated first; callers update it once per bar.
+    ///
+    /// When `location` is provided this node is identified as `{location}.{kind}` and the ids
+    /// of the nodes and conditions that produced a non-Hold action are collected. When `trace`
+    /// is set a `NodeTrace` of the evaluation is attached to the outcome.
+    pub(crate) fn evaluate_outcome(
+        &mut self,
+        data: &MarketData,
+        context: &Context,
+        location: Option<&str>,
+        trace: bool,
+    ) -> TaResult<Outcome> {
+        let id = location.map(|location| format!("{location}.{}", self.kind()));
+        let at = |segment: &str| id.as_ref().map(|id| format!("{id}{segment}"));
+        let mut record =
+            trace.then(|| NodeTrace::new(id.clone().unwrap_or_default(), self.kind()));
+        let mut outcome = match self {
             StrategyNode::Preprocess { step, then_branch } => {
-                // Apply preprocessing step, then return Hold by default.
                 // Evaluate the then_branch after preprocessing.
-                then_branch.evaluate(&step.apply(data))
+                let mut outcome = then_branch.evaluate_outcome(
+                    &step.apply(data),
+                    context,
+                    at(".then_branch").as_deref(),
+                    trace,
+                )?;
+                adopt(record.as_mut(), &mut outcome, true);
+                outcome
             }
             StrategyNode::If {
                 condition,
@@ -120,79 +522,73 @@ impl StrategyNode {
                 else_branch,
             } => {
                 // Evaluate condition; on true, evaluate then_branch, else else_branch or Hold.
-                if condition.evaluate(data)? {
-                    then_branch.evaluate(data)
+                let mut fired = Vec::new();
+                let mut condition_trace = trace.then(Vec::new);
+                let held = condition.evaluate_recorded(
+                    data,
+                    context,
+                    at(".condition").as_deref(),
+                    &mut fired,
+                    condition_trace.as_mut(),
+                )?;
+                if let Some(record) = record.as_mut() {
+                    record.condition = condition_trace.and_then(|mut traces| traces.pop());
+                }
+                if held {
+                    let mut outcome =
+                        then_branch.evaluate_outcome(data, context, at(".then_branch").as_deref(), trace)?;
+                    fired.append(&mut outcome.conditions);
+                    outcome.conditions = fired;
+                    adopt(record.as_mut(), &mut outcome, true);
+                    outcome
                 } else if let Some(else_node) = else_branch {
-                    else_node.evaluate(data)
+                    let mut outcome =
+                        else_node.evaluate_outcome(data, context, at(".else_branch").as_deref(), trace)?;
+                    adopt(record.as_mut(), &mut outcome, true);
+                    outcome
                 } else {
-                    Ok(Action::Hold)
+                    Outcome::hold()
                 }
             }
-            StrategyNode::Action(action) => Ok(*action),
-            StrategyNode::Sequence { mode, nodes } => {
-                // Collect non-Hold actions from sub-nodes, respecting mode.
-                let mut actions = Vec::new();
-                for node in nodes {
-                    let res = node.evaluate(data)?;
-                    if res != Action::Hold {
-                        actions.push(res);
-                        if mode == &SequenceMode::First || mode == &SequenceMode::Any {
-                            break;
+            StrategyNode::Action(action) => {
+                Outcome::new(*action, action.conviction() as f64 / 2.0)
+            }
+            StrategyNode::Sequence {
+                mode,
+                nodes,
+                tie_break,
+            } => {
+                match mode {
+                    SequenceMode::Weighted(weights) if weights.len() != nodes.len() => {
+                        return Err(StrategyError::WeightsMismatch {
+                            weights: weights.len(),
+                            nodes: nodes.len(),
                         }
+                        .into());
                     }
+                    _ => {}
                 }
-                // Aggregate actions per mode
-                let chosen = match mode { 
-                    SequenceMode::First | SequenceMode::Any => {
-                        actions.into_iter().next().unwrap_or(Action::Hold)
-                    },
-                    SequenceMode::Last => {
-                        actions.into_iter().next_back().unwrap_or(Action::Hold)
-                    },
-                    SequenceMode::All => {
-                        if let Some(first) = actions.first() {
-                            if actions.iter().all(|a| a == first) {
-                                *first
-                            } else {
-                                Action::Hold
-                            }
-                        } else {
-                            Action::Hold
-                        }
-                    }
-                    SequenceMode::Majority => {
-                        use std::collections::HashMap;
-                        let mut counts: HashMap<Action, usize> = HashMap::new();
-                        for a in &actions {
-                            *counts.entry(*a).or_insert(0) += 1;
-                        }
-                        counts
-                            .into_iter()
-                            .max_by_key(|&(_, c)| c)
-                            .map(|(a, _)| a)
-                            .unwrap_or(Action::Hold)
+                // Collect the outcome of every sub-node, stopping early in First/Any modes.
+                let mut outcomes = Vec::with_capacity(nodes.len());
+                for (index, node) in nodes.iter_mut().enumerate() {
+                    let child = id.as_ref().map(|id| format!("{id}[{index}]"));
+                    let mut outcome = node.evaluate_outcome(data, context, child.as_deref(), trace)?;
+                    adopt(record.as_mut(), &mut outcome, false);
+                    let stop = outcome.action != Action::Hold
+                        && matches!(mode, SequenceMode::First | SequenceMode::Any);
+                    outcomes.push(outcome);
+                    if stop {
+                        break;
                     }
-                    SequenceMode::Percentage(percentage) => {
-                        use std::collections::HashMap;
-                        let mut counts: HashMap<Action, usize> = HashMap::new();
-                        for a in &actions {
-                            *counts.entry(*a).or_insert(0) += 1;
-                        }
-                        let total = actions.len();
-                        counts
-                            .into_iter()
-                            .filter_map(|(a, c)| {
-                                if c * 100 / total >= *percentage as usize {
-                                    Some(a)
-                                } else {
-                                    None
-                                }
-                            })
-                            .next()
-                            .unwrap_or(Action::Hold)
-                    }
-                };
-                Ok(chosen)
+                }
+                let (outcome, supporting) = aggregate(mode, *tie_break, outcomes);
+                if let Some(record) = record.as_mut() {
+                    record.chosen = supporting
+                        .into_iter()
+                        .map(|i| record.children[i].id.clone())
+                        .collect();
+                }
+                outcome
             }
             StrategyNode::Timeout {
                 cooldown,
@@ -200,17 +596,176 @@ impl StrategyNode {
                 action,
             } => {
                 if *remaining > 0 {
-                    *remaining -= 1; // Decrement cooldown
-                    Ok(Action::Hold) // Still in cooldown
+                    if let Some(record) = record.as_mut() {
+                        record.note = Some(format!("cooldown: {remaining} bars remaining"));
+                    }
+                    Outcome::hold() // Still in cooldown
                 } else {
                     // Execute action after cooldown
-                    let result = action.evaluate(data)?;
-                    if result != Action::Hold {
-                        *remaining = *cooldown; // Reset cooldown
+                    let mut outcome =
+                        action.evaluate_outcome(data, context, at(".action").as_deref(), trace)?;
+                    adopt(record.as_mut(), &mut outcome, true);
+                    if outcome.action != Action::Hold {
+                        // Counts the current candle, which is consumed by the next update.
+                        *remaining = *cooldown + 1;
+                    }
+                    outcome
+                }
+            }
+            StrategyNode::RateLimit {
+                limit,
+                action,
+                fired,
+            } => {
+                let now = context
+                    .timestamp
+                    .ok_or(StrategyError::MissingTimestamp)?;
+                limit.prune(fired, now)?;
+                if let Some(reason) = limit.blocked(fired, context.last_loss, now)? {
+                    if let Some(record) = record.as_mut() {
+                        record.note = Some(reason);
+                    }
+                    Outcome::hold()
+                } else {
+                    let mut outcome =
+                        action.evaluate_outcome(data, context, at(".action").as_deref(), trace)?;
+                    adopt(record.as_mut(), &mut outcome, true);
+                    if outcome.action != Action::Hold
+                        && !matches!(limit, RateLimit::AfterLoss { .. })
+                    {
+                        fired.push(now);
+                    }
+                    outcome
+                }
+            }
+            StrategyNode::StateMachine {
+                states,
+                current,
+                bars_in_state,
+            } => {
+                *bars_in_state += 1;
+                let Some(state) = states.get_mut(*current) else {
+                    return Err(StrategyError::Configuration(format!(
+                        "StateMachine has no state at index {current}"
+                    ))
+                    .into());
+                };
+                let mut fired = Vec::new();
+                let mut transition_traces = trace.then(Vec::new);
+                let mut taken = None;
+                for (index, transition) in state.transitions.iter_mut().enumerate() {
+                    let location =
+                        at(&format!(".states[{current}].transitions[{index}].condition"));
+                    if transition.condition.evaluate_recorded(
+                        data,
+                        context,
+                        location.as_deref(),
+                        &mut fired,
+                        transition_traces.as_mut(),
+                    )? {
+                        taken = Some((transition.to.clone(), transition.action, "transition"));
+                        break;
                     }
-                    Ok(result)
                 }
+                if taken.is_none() {
+                    fired.clear();
+                    taken = state
+                        .timeout
+                        .as_ref()
+                        .filter(|timeout| *bars_in_state >= timeout.bars)
+                        .map(|timeout| (timeout.to.clone(), timeout.action, "timeout"));
+                }
+                let from = state.name.clone();
+                let action = match taken {
+                    Some((to, action, reason)) => {
+                        *current = states
+                            .iter()
+                            .position(|s| s.name == to)
+                            .ok_or(StrategyError::UnknownState(to))?;
+                        *bars_in_state = 0;
+                        if let Some(record) = record.as_mut() {
+                            let to = &states[*current].name;
+                            record.note = Some(format!("{reason}: {from} -> {to}"));
+                        }
+                        action.unwrap_or(Action::Hold)
+                    }
+                    None => Action::Hold,
+                };
+                if let Some(record) = record.as_mut() {
+                    record.state = Some(states[*current].name.clone());
+                    record.transitions = transition_traces.unwrap_or_default();
+                }
+                let mut outcome = Outcome::new(action, action.conviction() as f64 / 2.0);
+                outcome.conditions = fired;
+                outcome
             }
+            StrategyNode::Shared { indicators, node } => {
+                let context = Context {
+                    indicators: Some(indicators),
+                    ..context.clone()
+                };
+                let mut outcome =
+                    node.evaluate_outcome(data, &context, at(".node").as_deref(), trace)?;
+                adopt(record.as_mut(), &mut outcome, true);
+                outcome
+            }
+            StrategyNode::MultiTimeframe { timeframes, node } => {
+                if let Some(name) = timeframes
+                    .iter()
+                    .find(|(_, timeframe)| timeframe.closed() == 0)
+                    .map(|(name, _)| name)
+                {
+                    if let Some(record) = record.as_mut() {
+                        record.note = Some(format!("warming up: timeframe {name}"));
+                    }
+                    Outcome::hold()
+                } else {
+                    let context = Context {
+                        timeframes: Some(timeframes),
+                        ..context.clone()
+                    };
+                    let mut outcome =
+                        node.evaluate_outcome(data, &context, at(".node").as_deref(), trace)?;
+                    adopt(record.as_mut(), &mut outcome, true);
+                    outcome
+                }
+            }
+        };
+        if outcome.action == Action::Hold {
+            outcome = Outcome::hold();
+        } else if let Some(id) = id {
+            outcome.nodes.insert(0, id);
+        }
+        if let Some(mut record) = record {
+            record.action = outcome.action;
+            record.confidence = outcome.confidence;
+            outcome.trace = Some(record);
+        }
+        Ok(outcome)
+    }
+
+    /// Returns the name of the node variant, as used in node ids.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            StrategyNode::Preprocess { .. } => "Preprocess",
+            StrategyNode::If { .. } => "If",
+            StrategyNode::Timeout { .. } => "Timeout",
+            StrategyNode::RateLimit { .. } => "RateLimit",
+            StrategyNode::Action(_) => "Action",
+            StrategyNode::Sequence { .. } => "Sequence",
+            StrategyNode::StateMachine { .. } => "StateMachine",
+            StrategyNode::Shared { .. } => "Shared",
+            StrategyNode::MultiTimeframe { .. } => "MultiTimeframe",
+        }
+    }
+
+    /// Returns the name of the current state of a `StateMachine` node, or `None` for other nodes.
+    pub fn current_state(&self) -> Option<&str> {
+        match self {
+            StrategyNode::StateMachine {
+                states, current, ..
+            } => states.get(*current).map(|s| s.name.as_str()),
+            _ => None,
         }
     }
 
@@ -241,44 +796,184 @@ impl StrategyNode {
             StrategyNode::Sequence { nodes, .. } => {
                 nodes.iter().filter_map(|n| n.max_period()).max()
             }
-            StrategyNode::Timeout { action, .. } => action.max_period(),
+            StrategyNode::Timeout { action, .. } | StrategyNode::RateLimit { action, .. } => {
+                action.max_period()
+            }
+            StrategyNode::StateMachine { states, .. } => states
+                .iter()
+                .flat_map(|s| s.transitions.iter())
+                .filter_map(|t| t.condition.max_period())
+                .max(),
+            StrategyNode::Shared { indicators, node } => {
+                indicators.max_period().max(node.max_period())
+            }
+            // Timeframe indicators warm up on closed buckets, see `evaluate_outcome`.
+            StrategyNode::MultiTimeframe { node, .. } => node.max_period(),
         }
     }
 
     /// Validates that every execution path in the strategy ends with an Action.
     /// Returns Ok(()) if valid, or Err(String) describing the first violation.
     pub fn validate(&self) -> Result<(), TaError> {
+        self.validate_with(&Context::default())
+    }
+
+    /// Validates the node, resolving indicator and timeframe references against `context`.
+    pub(crate) fn validate_with(&self, context: &Context) -> Result<(), TaError> {
         match self {
-            StrategyNode::Preprocess { then_branch, .. } => then_branch.validate(),
+            StrategyNode::Preprocess { then_branch, .. } => then_branch.validate_with(context),
             StrategyNode::Action(_) => Ok(()),
             StrategyNode::If {
                 then_branch,
                 else_branch,
                 condition,
             } => {
-                condition.validate()?;
+                condition.validate_with(context)?;
                 // Then branch must be valid
-                then_branch.validate()?;
+                then_branch.validate_with(context)?;
                 // Else branch must exist and be valid
                 if let Some(else_node) = else_branch {
-                    else_node.validate()?;
+                    else_node.validate_with(context)?;
                 }
                 Ok(())
             }
-            StrategyNode::Sequence { nodes, .. } => {
+            StrategyNode::Sequence { nodes, mode, .. } => {
                 if nodes.is_empty() {
                     return Err(TaError::from(StrategyError::EmptySequence));
                 }
+                mode.validate(nodes.len())?;
                 for node in nodes {
-                    node.validate()?;
+                    node.validate_with(context)?;
                 }
                 Ok(())
             }
             StrategyNode::Timeout { action, .. } => {
                 // Action must be valid
-                action.validate()?;
+                action.validate_with(context)?;
+                Ok(())
+            }
+            StrategyNode::RateLimit { limit, action, .. } => {
+                limit.validate()?;
+                action.validate_with(context)
+            }
+            StrategyNode::StateMachine {
+                states, current, ..
+            } => {
+                validate_states(states, *current)?;
+                for transition in states.iter().flat_map(|state| &state.transitions) {
+                    transition.condition.validate_with(context)?;
+                }
                 Ok(())
             }
+            StrategyNode::Shared {
+                indicators: shared,
+                node,
+            } => {
+                shared.validate()?;
+                node.validate_with(&Context {
+                    indicators: Some(shared),
+                    ..context.clone()
+                })
+            }
+            StrategyNode::MultiTimeframe { timeframes, node } => {
+                for timeframe in timeframes.values() {
+                    timeframe.validate()?;
+                }
+                node.validate_with(&Context {
+                    timeframes: Some(timeframes),
+                    ..context.clone()
+                })
+            }
+        }
+    }
+
+    /// Moves the indicators of every condition into a shared registry, so that structurally
+    /// equal indicators are stored and updated once, and wraps the tree in a `Shared` node.
+    ///
+    /// Indicators are compared including their internal state, so this is meant to be called
+    /// on a freshly built tree. `Preprocess` subtrees get a registry of their own as they see
+    /// preprocessed data, and existing `Shared` subtrees are kept as they are.
+    pub fn share_indicators(self) -> StrategyNode {
+        let mut indicators = IndicatorRegistry::new();
+        let node = self.into_shared(&mut indicators);
+        StrategyNode::Shared {
+            indicators,
+            node: Box::new(node),
+        }
+    }
+
+    fn into_shared(self, indicators: &mut IndicatorRegistry) -> StrategyNode {
+        match self {
+            StrategyNode::Preprocess { step, then_branch } => StrategyNode::Preprocess {
+                step,
+                then_branch: Box::new(then_branch.share_indicators()),
+            },
+            StrategyNode::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => StrategyNode::If {
+                condition: condition.into_shared(indicators),
+                then_branch: Box::new(then_branch.into_shared(indicators)),
+                else_branch: else_branch.map(|node| Box::new(node.into_shared(indicators))),
+            },
+            StrategyNode::Timeout {
+                cooldown,
+                remaining,
+                action,
+            } => StrategyNode::Timeout {
+                cooldown,
+                remaining,
+                action: Box::new(action.into_shared(indicators)),
+            },
+            StrategyNode::RateLimit {
+                limit,
+                action,
+                fired,
+            } => StrategyNode::RateLimit {
+                limit,
+                action: Box::new(action.into_shared(indicators)),
+                fired,
+            },
+            StrategyNode::Sequence {
+                mode,
+                nodes,
+                tie_break,
+            } => StrategyNode::Sequence {
+                mode,
+                nodes: nodes
+                    .into_iter()
+                    .map(|node| node.into_shared(indicators))
+                    .collect(),
+                tie_break,
+            },
+            StrategyNode::StateMachine {
+                states,
+                current,
+                bars_in_state,
+            } => StrategyNode::StateMachine {
+                states: states
+                    .into_iter()
+                    .map(|state| MachineState {
+                        transitions: state
+                            .transitions
+                            .into_iter()
+                            .map(|t| Transition {
+                                condition: t.condition.into_shared(indicators),
+                                ..t
+                            })
+                            .collect(),
+                        ..state
+                    })
+                    .collect(),
+                current,
+                bars_in_state,
+            },
+            StrategyNode::MultiTimeframe { timeframes, node } => StrategyNode::MultiTimeframe {
+                timeframes,
+                node: Box::new(node.into_shared(indicators)),
+            },
+            node @ (StrategyNode::Action(_) | StrategyNode::Shared { .. }) => node,
         }
     }
 }
@@ -320,6 +1015,31 @@ impl Reset for StrategyNode {
                 action.reset();
                 *remaining = 0; // Reset cooldown
             }
+            StrategyNode::RateLimit { action, fired, .. } => {
+                action.reset();
+                fired.clear();
+            }
+            StrategyNode::StateMachine {
+                states,
+                current,
+                bars_in_state,
+            } => {
+                for transition in states.iter_mut().flat_map(|s| s.transitions.iter_mut()) {
+                    transition.condition.reset();
+                }
+                *current = 0;
+                *bars_in_state = 0;
+            }
+            StrategyNode::Shared { indicators, node } => {
+                indicators.reset();
+                node.reset();
+            }
+            StrategyNode::MultiTimeframe { timeframes, node } => {
+                for timeframe in timeframes.values_mut() {
+                    timeframe.reset();
+                }
+                node.reset();
+            }
         }
     }
 }
@@ -383,6 +1103,7 @@ mod tests {
         let seq = StrategyNode::Sequence {
             mode: SequenceMode::All,
             nodes: vec![node1, node2],
+            tie_break: TieBreak::default(),
         };
         assert_eq!(seq.max_period(), Some(20));
         dbg!(&seq);
@@ -410,6 +1131,7 @@ mod tests {
                 StrategyNode::Action(Action::Buy),
                 StrategyNode::Action(Action::Sell),
             ],
+            tie_break: TieBreak::default(),
         };
         assert!(seq.validate().is_ok());
     }
@@ -432,6 +1154,7 @@ mod tests {
         let seq = StrategyNode::Sequence {
             mode: SequenceMode::Any,
             nodes: vec![],
+            tie_break: TieBreak::default(),
         };
         let err = seq.validate().unwrap_err();
         assert_eq!(err, TaError::from(StrategyError::EmptySequence));
@@ -472,6 +1195,7 @@ mod tests {
             Ok(StrategyNode::Sequence {
                 mode: crate::strategy::node::SequenceMode::First,
                 nodes: vec![long_entry, short_entry],
+                tie_break: TieBreak::default(),
             })
         }
         let mut strategy = multi_indicator_confluence_strategy()?;
@@ -641,6 +1365,7 @@ mod tests {
                 // Fallback action if no other conditions are met.
                 StrategyNode::Action(Action::Hold),
             ],
+            tie_break: TieBreak::default(),
         };
 
         // Validate that the strategy is well-formed (e.g., all paths lead to an action).
@@ -964,4 +1689,231 @@ mod tests {
 
         Ok(())
     }
+
+    fn voting_sequence(mode: SequenceMode, tie_break: TieBreak, actions: &[Action]) -> StrategyNode {
+        StrategyNode::Sequence {
+            mode,
+            nodes: actions.iter().copied().map(StrategyNode::Action).collect(),
+            tie_break,
+        }
+    }
+
+    #[test]
+    fn test_majority_tie_defaults_to_hold() -> TaResult<()> {
+        let data = MarketData::Float(100.0);
+        let mut node = voting_sequence(
+            SequenceMode::Majority,
+            TieBreak::default(),
+            &[Action::Buy, Action::Sell, Action::Sell, Action::Buy],
+        );
+        assert_eq!(node.evaluate(&data)?, Action::Hold);
+
+        let mut node = voting_sequence(
+            SequenceMode::Majority,
+            TieBreak::Hold,
+            &[Action::Buy, Action::Sell, Action::Buy],
+        );
+        assert_eq!(node.evaluate(&data)?, Action::Buy);
+        Ok(())
+    }
+
+    #[test]
+    fn test_majority_tie_break_policies() -> TaResult<()> {
+        let data = MarketData::Float(100.0);
+        let votes = [Action::Sell, Action::StrongBuy, Action::Sell, Action::StrongBuy];
+
+        let mut first = voting_sequence(SequenceMode::Majority, TieBreak::PreferFirst, &votes);
+        assert_eq!(first.evaluate(&data)?, Action::Sell);
+
+        let mut stronger =
+            voting_sequence(SequenceMode::Majority, TieBreak::PreferStronger, &votes);
+        assert_eq!(stronger.evaluate(&data)?, Action::StrongBuy);
+
+        // Equally strong but opposite actions cannot be separated.
+        let mut conflicting = voting_sequence(
+            SequenceMode::Majority,
+            TieBreak::PreferStronger,
+            &[Action::StrongSell, Action::StrongBuy],
+        );
+        assert_eq!(conflicting.evaluate(&data)?, Action::Hold);
+        Ok(())
+    }
+
+    #[test]
+    fn test_percentage_tie_break_policies() -> TaResult<()> {
+        let data = MarketData::Float(100.0);
+        let votes = [Action::Buy, Action::StrongSell, Action::Hold];
+
+        let mut hold = voting_sequence(SequenceMode::Percentage(50), TieBreak::Hold, &votes);
+        assert_eq!(hold.evaluate(&data)?, Action::Hold);
+
+        let mut first = voting_sequence(SequenceMode::Percentage(50), TieBreak::PreferFirst, &votes);
+        assert_eq!(first.evaluate(&data)?, Action::Buy);
+
+        let mut stronger =
+            voting_sequence(SequenceMode::Percentage(50), TieBreak::PreferStronger, &votes);
+        assert_eq!(stronger.evaluate(&data)?, Action::StrongSell);
+
+        // Only actions reaching the threshold qualify.
+        let mut strict = voting_sequence(
+            SequenceMode::Percentage(60),
+            TieBreak::PreferFirst,
+            &[Action::Buy, Action::Sell, Action::Sell],
+        );
+        assert_eq!(strict.evaluate(&data)?, Action::Sell);
+        Ok(())
+    }
+
+    #[test]
+    fn test_sequence_aggregation_is_reproducible() -> TaResult<()> {
+        let data = MarketData::Float(100.0);
+        let votes = [
+            Action::Buy,
+            Action::Sell,
+            Action::StrongBuy,
+            Action::StrongSell,
+            Action::Buy,
+            Action::Sell,
+        ];
+        let modes = [SequenceMode::Majority, SequenceMode::Percentage(30)];
+        let policies = [TieBreak::Hold, TieBreak::PreferStronger, TieBreak::PreferFirst];
+        for mode in modes {
+            for tie_break in policies {
+                let expected = voting_sequence(mode.clone(), tie_break, &votes).evaluate(&data)?;
+                for _ in 0..256 {
+                    let mut node = voting_sequence(mode.clone(), tie_break, &votes);
+                    assert_eq!(node.evaluate(&data)?, expected);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_serde_sequence_tie_break() -> TaResult<()> {
+        let node = voting_sequence(SequenceMode::Majority, TieBreak::default(), &[Action::Buy]);
+        let json = serde_json::to_string(&node)?;
+        assert_eq!(json, r#"{"Sequence":{"mode":"Majority","nodes":[{"Action":"Buy"}]}}"#);
+        assert_eq!(serde_json::from_str::<StrategyNode>(&json)?, node);
+
+        let node = voting_sequence(SequenceMode::Majority, TieBreak::PreferFirst, &[Action::Buy]);
+        let json = serde_json::to_string(&node)?;
+        assert!(json.contains(r#""tie_break":"PreferFirst""#));
+        assert_eq!(serde_json::from_str::<StrategyNode>(&json)?, node);
+        Ok(())
+    }
+
+    #[test]
+    fn test_weighted_sequence_mode() -> TaResult<()> {
+        let data = MarketData::Float(100.0);
+        let votes = [Action::Buy, Action::Hold, Action::Sell];
+
+        let mut node = voting_sequence(
+            SequenceMode::Weighted(vec![1.0, 1.0, 1.0]),
+            TieBreak::default(),
+            &votes,
+        );
+        // 1 + 0 - 1 = 0 -> Hold
+        assert_eq!(node.evaluate(&data)?, Action::Hold);
+
+        let mut node = voting_sequence(
+            SequenceMode::Weighted(vec![2.0, 0.0, 1.0]),
+            TieBreak::default(),
+            &votes,
+        );
+        // 2 - 1 = 1 -> Buy
+        assert_eq!(node.evaluate(&data)?, Action::Buy);
+
+        let mut node = voting_sequence(
+            SequenceMode::Weighted(vec![3.0, 1.0, 1.0]),
+            TieBreak::default(),
+            &votes,
+        );
+        // 3 - 1 = 2 -> StrongBuy
+        assert_eq!(node.evaluate(&data)?, Action::StrongBuy);
+
+        for weights in [
+            vec![1.0, -1.0, 1.0],
+            vec![0.0, 0.0, 0.0],
+            vec![1.0, f64::NAN, 1.0],
+        ] {
+            let node =
+                voting_sequence(SequenceMode::Weighted(weights), TieBreak::default(), &votes);
+            assert!(node.validate().is_err());
+        }
+
+        let mut node = voting_sequence(
+            SequenceMode::Weighted(vec![1.0, 1.0]),
+            TieBreak::default(),
+            &votes,
+        );
+        assert_eq!(
+            node.validate().unwrap_err(),
+            TaError::from(StrategyError::WeightsMismatch {
+                weights: 2,
+                nodes: 3
+            })
+        );
+        assert!(node.evaluate(&data).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_score_sequence_mode_counts_holds() -> TaResult<()> {
+        let data = MarketData::Float(100.0);
+        let mode = SequenceMode::Score {
+            buy_threshold: 2.0,
+            sell_threshold: -2.0,
+        };
+
+        // A single Buy among holds does not reach the threshold.
+        let mut node = voting_sequence(
+            mode.clone(),
+            TieBreak::default(),
+            &[
+                Action::Buy,
+                Action::Hold,
+                Action::Hold,
+                Action::Hold,
+                Action::Hold,
+            ],
+        );
+        assert_eq!(node.evaluate(&data)?, Action::Hold);
+
+        let mut node = voting_sequence(
+            mode.clone(),
+            TieBreak::default(),
+            &[Action::Buy, Action::Buy, Action::Hold],
+        );
+        assert_eq!(node.evaluate(&data)?, Action::Buy);
+
+        let mut node = voting_sequence(
+            mode.clone(),
+            TieBreak::default(),
+            &[Action::StrongSell, Action::StrongSell, Action::Buy],
+        );
+        // -2 - 2 + 1 = -3
+        assert_eq!(node.evaluate(&data)?, Action::Sell);
+
+        let nan = voting_sequence(
+            SequenceMode::Score {
+                buy_threshold: f64::NAN,
+                sell_threshold: 0.5,
+            },
+            TieBreak::default(),
+            &[Action::Buy],
+        );
+        assert!(nan.validate().is_err());
+
+        let invalid = voting_sequence(
+            SequenceMode::Score {
+                buy_threshold: -0.5,
+                sell_threshold: 0.5,
+            },
+            TieBreak::default(),
+            &[Action::Buy],
+        );
+        assert!(invalid.validate().is_err());
+        Ok(())
+    }
 }
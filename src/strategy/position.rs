@@ -0,0 +1,130 @@
+use serde::{Deserialize, Serialize};
+
+#[cfg(not(feature = "js"))]
+use crate::Indicator;
+use crate::error::{TaError, TaResult};
+#[cfg(feature = "js")]
+use crate::indicators::indicator::Indicator;
+use crate::strategy::wrapper::IndicatorState;
+use crate::strategy::{Action, MarketData};
+use crate::traits::Reset;
+use crate::types::OutputType;
+
+/// Direction of the open position.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Side {
+    /// No position is open.
+    #[default]
+    Flat,
+    Long,
+    Short,
+}
+
+impl Side {
+    /// Returns the side a non-Hold action opens, or `Flat` for `Hold`.
+    pub fn of(action: Action) -> Side {
+        match action.score() {
+            s if s > 0 => Side::Long,
+            s if s < 0 => Side::Short,
+            _ => Side::Flat,
+        }
+    }
+
+    /// Returns the action that closes a position on this side.
+    pub fn exit_action(&self) -> Action {
+        match self {
+            Side::Flat => Action::Hold,
+            Side::Long => Action::Sell,
+            Side::Short => Action::Buy,
+        }
+    }
+}
+
+/// State of the position tracked by a `ManagedStrategy`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub struct Position {
+    pub side: Side,
+    /// Price at which the position was opened.
+    pub entry_price: f64,
+    /// Number of bars evaluated since the position was opened.
+    pub bars_in_trade: usize,
+    /// Price at which the stop loss triggers.
+    pub stop_price: Option<f64>,
+    /// Price at which the take profit triggers.
+    pub target_price: Option<f64>,
+    /// Most favourable price reached since entry.
+    pub best_price: f64,
+    /// Stop distance captured at entry, used to trail the stop behind `best_price`.
+    pub trail_distance: Option<f64>,
+}
+
+impl Position {
+    pub fn is_flat(&self) -> bool {
+        self.side == Side::Flat
+    }
+}
+
+/// Distance between the entry price and a stop loss or take profit.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum Stop {
+    /// Absolute price distance.
+    Fixed(f64),
+    /// Percentage of the entry price (`1.0` = 1%).
+    Percent(f64),
+    /// Multiple of the Average True Range at entry.
+    Atr {
+        atr: Box<IndicatorState>,
+        multiplier: f64,
+    },
+}
+
+impl Stop {
+    /// Creates an ATR based stop using an ATR of the given period.
+    pub fn atr(period: usize, multiplier: f64) -> TaResult<Self> {
+        Ok(Stop::Atr {
+            atr: Box::new(Indicator::atr(period)?.into()),
+            multiplier,
+        })
+    }
+
+    /// Updates the ATR of an ATR based stop with the latest market data.
+    pub fn update(&mut self, data: &MarketData) -> TaResult<()> {
+        match self {
+            Stop::Atr { atr, .. } => atr.update(data),
+            Stop::Fixed(_) | Stop::Percent(_) => Ok(()),
+        }
+    }
+
+    /// Returns the price distance of the stop for a position opened at `entry_price`.
+    pub fn distance(&self, entry_price: f64) -> TaResult<f64> {
+        match self {
+            Stop::Fixed(distance) => Ok(*distance),
+            Stop::Percent(percent) => Ok(entry_price * percent / 100.0),
+            Stop::Atr { atr, multiplier } => match atr.prev()? {
+                OutputType::Single(value) => Ok(value * multiplier),
+                other => Err(TaError::IncorrectOutputType {
+                    expected: "Single".to_string(),
+                    actual: format!("{other:?}"),
+                }),
+            },
+        }
+    }
+}
+
+impl Reset for Stop {
+    fn reset(&mut self) {
+        if let Stop::Atr { atr, .. } = self {
+            atr.reset();
+        }
+    }
+}
+
+/// Reason a `ManagedStrategy` closed its position.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Exit {
+    StopLoss,
+    TrailingStop,
+    TakeProfit,
+    /// The strategy emitted an action in the opposite direction.
+    Signal,
+}
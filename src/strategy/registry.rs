@@ -0,0 +1,253 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+#[cfg(not(feature = "js"))]
+use crate::Indicator;
+#[cfg(feature = "js")]
+use crate::indicators::indicator::Indicator;
+use crate::{
+    error::TaResult,
+    strategy::{MarketData, StrategyError, wrapper::IndicatorState},
+    traits::{IndicatorTrait, Period, Reset},
+    types::OutputType,
+};
+
+/// Table of indicators shared by the conditions of a `StrategyNode::Shared` subtree.
+///
+/// Conditions reference the indicators by id through `Operand::Indicator`, so an indicator
+/// used by several conditions is stored and updated only once. Serializes as a map from id
+/// to indicator.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(transparent)]
+pub struct IndicatorRegistry {
+    indicators: BTreeMap<String, IndicatorState>,
+}
+
+impl IndicatorRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Inserts `indicator` under `id`, returning the indicator previously stored under it.
+    pub fn insert(&mut self, id: impl Into<String>, indicator: Indicator) -> Option<Indicator> {
+        self.indicators
+            .insert(id.into(), indicator.into())
+            .map(Indicator::from)
+    }
+
+    /// Returns the id of an indicator structurally equal to `indicator`, registering it
+    /// under a new id derived from its name and period if there is none.
+    pub fn register(&mut self, indicator: Indicator) -> String {
+        if let Some((id, _)) = self
+            .indicators
+            .iter()
+            .find(|(_, state)| state.indicator == indicator)
+        {
+            return id.clone();
+        }
+        let base = format!("{}({})", indicator.name(), indicator.period());
+        let mut id = base.clone();
+        let mut suffix = 1;
+        while self.indicators.contains_key(&id) {
+            suffix += 1;
+            id = format!("{base}#{suffix}");
+        }
+        self.indicators.insert(id.clone(), indicator.into());
+        id
+    }
+
+    pub fn get(&self, id: &str) -> Option<&IndicatorState> {
+        self.indicators.get(id)
+    }
+
+    /// Returns the latest output of the indicator registered under `id`.
+    pub fn prev(&self, id: &str) -> TaResult<OutputType> {
+        self.indicators
+            .get(id)
+            .ok_or_else(|| StrategyError::UnknownIndicator(id.to_string()))?
+            .prev()
+    }
+
+    /// Returns the registered ids in order.
+    pub fn ids(&self) -> impl Iterator<Item = &str> {
+        self.indicators.keys().map(String::as_str)
+    }
+
+    pub fn len(&self) -> usize {
+        self.indicators.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.indicators.is_empty()
+    }
+
+    /// Updates every registered indicator once with the latest market data.
+    pub fn update(&mut self, data: &MarketData) -> TaResult<()> {
+        for indicator in self.indicators.values_mut() {
+            indicator.update(data)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the maximum period of the registered indicators, or `None` if there are none.
+    pub fn max_period(&self) -> Option<usize> {
+        self.indicators.values().map(|i| i.period()).max()
+    }
+
+    /// Returns an error if any registered indicator has a period of zero.
+    pub fn validate(&self) -> TaResult<()> {
+        if self.indicators.values().any(|i| i.period() == 0) {
+            return Err(StrategyError::InvalidIndicatorPeriod { period: 0 }.into());
+        }
+        Ok(())
+    }
+}
+
+impl Reset for IndicatorRegistry {
+    fn reset(&mut self) {
+        for indicator in self.indicators.values_mut() {
+            indicator.reset();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::strategy::node::{SequenceMode, TieBreak};
+    use crate::strategy::{Action, Condition, Operand, StrategyNode};
+    use crate::traits::Next;
+
+    fn confluence() -> TaResult<StrategyNode> {
+        let oversold = |level: f64| -> TaResult<Condition> {
+            Ok(Condition::less_than(
+                Indicator::rsi(3)?,
+                OutputType::Single(level),
+            ))
+        };
+        Ok(StrategyNode::Sequence {
+            mode: SequenceMode::Majority,
+            nodes: vec![
+                StrategyNode::If {
+                    condition: Condition::And(vec![
+                        oversold(40.0)?,
+                        Condition::indicator(
+                            Indicator::sma(2)?,
+                            Indicator::sma(4)?,
+                            crate::strategy::condition::Operator::GreaterThan,
+                        ),
+                    ]),
+                    then_branch: Box::new(StrategyNode::Action(Action::Buy)),
+                    else_branch: Some(Box::new(StrategyNode::Action(Action::Hold))),
+                },
+                StrategyNode::If {
+                    condition: Condition::Or(vec![oversold(30.0)?, oversold(20.0)?]),
+                    then_branch: Box::new(StrategyNode::Action(Action::Buy)),
+                    else_branch: Some(Box::new(StrategyNode::Action(Action::Sell))),
+                },
+                StrategyNode::If {
+                    condition: Condition::Not(Box::new(oversold(60.0)?)),
+                    then_branch: Box::new(StrategyNode::Action(Action::Sell)),
+                    else_branch: None,
+                },
+            ],
+            tie_break: TieBreak::default(),
+        })
+    }
+
+    fn prices() -> Vec<f64> {
+        (0..60)
+            .map(|i| 100.0 + (i as f64 * 0.7).sin() * 10.0 + (i % 7) as f64)
+            .collect()
+    }
+
+    #[test]
+    fn test_share_indicators_deduplicates() -> TaResult<()> {
+        let shared = confluence()?.share_indicators();
+        let StrategyNode::Shared { indicators, .. } = &shared else {
+            unreachable!()
+        };
+        assert_eq!(indicators.len(), 3);
+        shared.validate()?;
+        assert_eq!(shared.period(), confluence()?.period());
+
+        let json = serde_json::to_string(&shared)?;
+        assert_eq!(json.matches("\"Rsi\"").count(), 1);
+        let restored: StrategyNode = serde_json::from_str(&json)?;
+        assert_eq!(restored, shared);
+        Ok(())
+    }
+
+    #[test]
+    fn test_shared_tree_matches_owned_tree() -> TaResult<()> {
+        let mut owned = confluence()?;
+        let mut shared = confluence()?.share_indicators();
+        for price in prices() {
+            let data = MarketData::Float(price);
+            assert_eq!(shared.evaluate(&data)?, owned.evaluate(&data)?);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_indicators_update_once_per_bar() -> TaResult<()> {
+        let mut owned = confluence()?;
+        let mut shared = confluence()?.share_indicators();
+        let mut expected = Indicator::rsi(3)?;
+        for price in prices() {
+            let data = MarketData::Float(price);
+            owned.evaluate(&data)?;
+            shared.evaluate(&data)?;
+            expected.next(&data)?;
+        }
+
+        let StrategyNode::Shared { indicators, .. } = &shared else {
+            unreachable!()
+        };
+        let rsi = indicators
+            .ids()
+            .filter_map(|id| indicators.get(id))
+            .find(|i| matches!(i.indicator, Indicator::Rsi(_)));
+        assert_eq!(rsi.map(|i| &i.indicator), Some(&expected));
+
+        let StrategyNode::Sequence { nodes, .. } = &owned else {
+            unreachable!()
+        };
+        let StrategyNode::If {
+            condition: Condition::Or(conditions),
+            ..
+        } = &nodes[1]
+        else {
+            unreachable!()
+        };
+        let Condition::Value { indicator, .. } = &conditions[0] else {
+            unreachable!()
+        };
+        assert_eq!(indicator.indicator, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_unknown_shared_indicator() -> TaResult<()> {
+        let condition = Condition::Compare {
+            left: Operand::Indicator("missing".to_string()),
+            right: Operand::Value(OutputType::Single(1.0)),
+            operator: crate::strategy::condition::Operator::GreaterThan,
+        };
+        let mut node = StrategyNode::Shared {
+            indicators: IndicatorRegistry::new(),
+            node: Box::new(StrategyNode::If {
+                condition,
+                then_branch: Box::new(StrategyNode::Action(Action::Buy)),
+                else_branch: None,
+            }),
+        };
+        let error = || {
+            crate::error::TaError::Strategy(StrategyError::UnknownIndicator("missing".to_string()))
+        };
+        assert_eq!(node.validate(), Err(error()));
+        assert_eq!(node.evaluate(&MarketData::Float(1.0)), Err(error()));
+        Ok(())
+    }
+}
@@ -0,0 +1,754 @@
+//! JSON Schema of the serialized strategy types.
+//!
+//! The schema is written by hand next to the serde representation it describes, because most
+//! indicators implement `Serialize` manually and only persist their parameters. Every type has
+//! a definition under `$defs`, so editors can validate either a whole `StrategyFile` or a single
+//! `StrategyNode`, `Condition`, `Indicator` or `Operator`.
+
+use serde_json::{Map, Value, json};
+
+use crate::indicators::indicator::Indicator;
+use crate::strategy::condition::{Condition, Operator};
+use crate::strategy::file::{STRATEGY_FILE_VERSION, StrategyFile};
+use crate::strategy::node::StrategyNode;
+
+/// JSON Schema dialect of the generated schemas.
+pub const JSON_SCHEMA_DIALECT: &str = "https://json-schema.org/draft/2020-12/schema";
+
+impl StrategyFile {
+    /// Returns the JSON Schema of a versioned strategy file.
+    pub fn json_schema() -> Value {
+        json_schema("StrategyFile")
+    }
+}
+
+impl StrategyNode {
+    /// Returns the JSON Schema of a serialized strategy tree.
+    pub fn json_schema() -> Value {
+        json_schema("StrategyNode")
+    }
+}
+
+impl Condition {
+    /// Returns the JSON Schema of a serialized condition.
+    pub fn json_schema() -> Value {
+        json_schema("Condition")
+    }
+}
+
+impl Indicator {
+    /// Returns the JSON Schema of a serialized indicator. `Custom` indicators cannot be
+    /// deserialized and are not part of the schema.
+    pub fn json_schema() -> Value {
+        json_schema("Indicator")
+    }
+}
+
+impl Operator {
+    /// Returns the JSON Schema of a serialized comparison operator.
+    pub fn json_schema() -> Value {
+        json_schema("Operator")
+    }
+}
+
+/// Builds a schema document whose root references the definition `root`, with every
+/// definition attached under `$defs`.
+fn json_schema(root: &str) -> Value {
+    json!({
+        "$schema": JSON_SCHEMA_DIALECT,
+        "title": root,
+        "$ref": reference_path(root),
+        "$defs": definitions(),
+    })
+}
+
+fn definitions() -> Map<String, Value> {
+    let definitions = [
+        ("StrategyFile", strategy_file()),
+        ("StrategyNode", strategy_node()),
+        ("SequenceMode", sequence_mode()),
+        (
+            "TieBreak",
+            unit_enum(&["Hold", "PreferStronger", "PreferFirst"]),
+        ),
+        (
+            "PreprocessingStep",
+            unit_enum(&["WaveletDenoise", "Normalize"]),
+        ),
+        ("RateLimit", rate_limit()),
+        ("MachineState", machine_state()),
+        ("Timeframe", timeframe()),
+        ("IndicatorRegistry", map_of(reference("Indicator"))),
+        ("Condition", condition()),
+        ("Operand", operand()),
+        ("Operator", operator()),
+        ("OutputType", output_type()),
+        (
+            "Action",
+            unit_enum(&["StrongBuy", "Buy", "Hold", "Sell", "StrongSell"]),
+        ),
+        ("Side", unit_enum(&["Flat", "Long", "Short"])),
+        (
+            "Session",
+            unit_enum(&["Sydney", "Tokyo", "London", "NewYork"]),
+        ),
+        ("Indicator", indicator()),
+    ];
+    definitions
+        .into_iter()
+        .map(|(name, schema)| (name.to_string(), schema))
+        .collect()
+}
+
+fn strategy_file() -> Value {
+    json!({
+        "description": "Versioned strategy document.",
+        "type": "object",
+        "properties": {
+            "version": { "const": STRATEGY_FILE_VERSION },
+            "strategy": reference("StrategyNode"),
+        },
+        "required": ["version", "strategy"],
+        "additionalProperties": false,
+    })
+}
+
+fn strategy_node() -> Value {
+    let node = || reference("StrategyNode");
+    one_of([
+        variant("Action", reference("Action")),
+        variant(
+            "If",
+            object(
+                json!({
+                    "condition": reference("Condition"),
+                    "then_branch": node(),
+                    "else_branch": nullable(node()),
+                }),
+                &["condition", "then_branch"],
+            ),
+        ),
+        variant(
+            "Sequence",
+            object(
+                json!({
+                    "mode": reference("SequenceMode"),
+                    "nodes": array_of(node()),
+                    "tie_break": reference("TieBreak"),
+                }),
+                &["mode", "nodes"],
+            ),
+        ),
+        variant(
+            "Timeout",
+            object(
+                json!({
+                    "cooldown": count(0),
+                    "remaining": count(0),
+                    "action": node(),
+                }),
+                &["cooldown", "remaining", "action"],
+            ),
+        ),
+        variant(
+            "RateLimit",
+            object(
+                json!({
+                    "limit": reference("RateLimit"),
+                    "action": node(),
+                    "fired": array_of(json!({ "type": "string", "format": "date-time" })),
+                }),
+                &["limit", "action"],
+            ),
+        ),
+        variant(
+            "Preprocess",
+            object(
+                json!({
+                    "step": reference("PreprocessingStep"),
+                    "then_branch": node(),
+                }),
+                &["step", "then_branch"],
+            ),
+        ),
+        variant(
+            "StateMachine",
+            object(
+                json!({
+                    "states": array_of(reference("MachineState")),
+                    "current": count(0),
+                    "bars_in_state": count(0),
+                }),
+                &["states"],
+            ),
+        ),
+        variant(
+            "Shared",
+            object(
+                json!({
+                    "indicators": reference("IndicatorRegistry"),
+                    "node": node(),
+                }),
+                &["indicators", "node"],
+            ),
+        ),
+        variant(
+            "MultiTimeframe",
+            object(
+                json!({
+                    "timeframes": map_of(reference("Timeframe")),
+                    "node": node(),
+                }),
+                &["timeframes", "node"],
+            ),
+        ),
+    ])
+}
+
+fn sequence_mode() -> Value {
+    one_of([
+        unit_enum(&["First", "Last", "Any", "All", "Majority"]),
+        variant(
+            "Percentage",
+            json!({ "type": "integer", "minimum": 0, "maximum": 100 }),
+        ),
+        variant("Weighted", array_of(json!({ "type": "number" }))),
+        variant(
+            "Score",
+            object(
+                json!({
+                    "buy_threshold": { "type": "number" },
+                    "sell_threshold": { "type": "number" },
+                }),
+                &["buy_threshold", "sell_threshold"],
+            ),
+        ),
+    ])
+}
+
+fn rate_limit() -> Value {
+    one_of([
+        variant(
+            "Signals",
+            object(
+                json!({ "max": count(1), "window_secs": count(0) }),
+                &["max", "window_secs"],
+            ),
+        ),
+        variant("Cooldown", object(json!({ "secs": count(0) }), &["secs"])),
+        variant("AfterLoss", object(json!({ "secs": count(0) }), &["secs"])),
+    ])
+}
+
+fn machine_state() -> Value {
+    let transition = object(
+        json!({
+            "condition": reference("Condition"),
+            "to": { "type": "string" },
+            "action": reference("Action"),
+        }),
+        &["condition", "to"],
+    );
+    let timeout = object(
+        json!({
+            "bars": count(1),
+            "to": { "type": "string" },
+            "action": reference("Action"),
+        }),
+        &["bars", "to"],
+    );
+    object(
+        json!({
+            "name": { "type": "string" },
+            "transitions": array_of(transition),
+            "timeout": timeout,
+        }),
+        &["name"],
+    )
+}
+
+fn timeframe() -> Value {
+    object(
+        json!({
+            "resample": one_of([
+                variant("Seconds", count(1)),
+                variant("Bars", count(1)),
+            ]),
+            "indicators": reference("IndicatorRegistry"),
+            "forming": {
+                "description": "Bucket still forming when the strategy was saved.",
+                "type": "object",
+            },
+        }),
+        &["resample", "indicators"],
+    )
+}
+
+fn condition() -> Value {
+    let operator = || reference("Operator");
+    let value = || reference("OutputType");
+    let indicator = || reference("Indicator");
+    let time = || json!({ "type": "string", "pattern": "^\\d{2}:\\d{2}:\\d{2}(\\.\\d+)?$" });
+    one_of([
+        variant(
+            "ValueOnly",
+            object(
+                json!({ "left": value(), "right": value(), "operator": operator() }),
+                &["left", "right", "operator"],
+            ),
+        ),
+        variant(
+            "Value",
+            object(
+                json!({ "indicator": indicator(), "value": value(), "operator": operator() }),
+                &["indicator", "value", "operator"],
+            ),
+        ),
+        variant(
+            "ValueInversed",
+            object(
+                json!({ "value": value(), "indicator": indicator(), "operator": operator() }),
+                &["value", "indicator", "operator"],
+            ),
+        ),
+        variant(
+            "Indicator",
+            object(
+                json!({ "left": indicator(), "right": indicator(), "operator": operator() }),
+                &["left", "right", "operator"],
+            ),
+        ),
+        variant(
+            "Compare",
+            object(
+                json!({
+                    "left": reference("Operand"),
+                    "right": reference("Operand"),
+                    "operator": operator(),
+                }),
+                &["left", "right", "operator"],
+            ),
+        ),
+        variant("And", array_of(reference("Condition"))),
+        variant("Or", array_of(reference("Condition"))),
+        variant("Not", reference("Condition")),
+        variant("Position", reference("Side")),
+        variant(
+            "BarsInTrade",
+            object(
+                json!({ "operator": operator(), "bars": count(0) }),
+                &["operator", "bars"],
+            ),
+        ),
+        variant(
+            "TimeOfDay",
+            object(
+                json!({
+                    "start": time(),
+                    "end": time(),
+                    "tz_offset": {
+                        "description": "Offset of the local time from UTC, in minutes.",
+                        "type": "integer",
+                        "minimum": -1439,
+                        "maximum": 1439,
+                    },
+                }),
+                &["start", "end"],
+            ),
+        ),
+        variant(
+            "DayOfWeek",
+            array_of(unit_enum(&[
+                "Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun",
+            ])),
+        ),
+        variant("Session", reference("Session")),
+    ])
+}
+
+fn operand() -> Value {
+    let id = || json!({ "type": "string" });
+    one_of([
+        variant("Indicator", id()),
+        variant(
+            "Timeframe",
+            object(
+                json!({
+                    "timeframe": id(),
+                    "indicator": id(),
+                    "forming": { "type": "boolean" },
+                }),
+                &["timeframe", "indicator"],
+            ),
+        ),
+        variant(
+            "Symbol",
+            object(
+                json!({ "symbol": id(), "indicator": id() }),
+                &["symbol", "indicator"],
+            ),
+        ),
+        variant("Value", reference("OutputType")),
+    ])
+}
+
+fn operator() -> Value {
+    unit_enum(&[
+        "GreaterThan",
+        "LessThan",
+        "Equals",
+        "GreaterThanOrEqual",
+        "LessThanOrEqual",
+        "CrossOver",
+        "CrossUnder",
+    ])
+}
+
+fn output_type() -> Value {
+    one_of([
+        unit_enum(&["Open", "High", "Low", "Close", "Volume"]),
+        variant("Single", json!({ "type": "number" })),
+        variant("Array", array_of(json!({ "type": "number" }))),
+        variant("Custom", array_of(reference("OutputType"))),
+        variant("Static", unit_enum(&["True", "False"])),
+    ])
+}
+
+fn indicator() -> Value {
+    let period = || count(1);
+    let multiplier = || json!({ "type": "number" });
+    let ema = object(json!({ "period": period() }), &["period"]);
+    one_of([
+        tagged("None", json!({}), &[]),
+        tagged(
+            "Alligator",
+            json!({
+                "jaw_period": period(),
+                "jaw_shift": period(),
+                "teeth_period": period(),
+                "teeth_shift": period(),
+                "lips_period": period(),
+                "lips_shift": period(),
+            }),
+            &[
+                "jaw_period",
+                "jaw_shift",
+                "teeth_period",
+                "teeth_shift",
+                "lips_period",
+                "lips_shift",
+            ],
+        ),
+        tagged(
+            "Ao",
+            json!({ "short_period": period(), "long_period": period() }),
+            &["short_period", "long_period"],
+        ),
+        tagged("Atr", json!({ "period": period() }), &["period"]),
+        tagged(
+            "Bb",
+            json!({ "period": period(), "multiplier": multiplier() }),
+            &["period", "multiplier"],
+        ),
+        tagged("Ema", json!({ "period": period() }), &["period"]),
+        tagged(
+            "Kc",
+            json!({ "period": period(), "multiplier": multiplier() }),
+            &["period", "multiplier"],
+        ),
+        tagged(
+            "Macd",
+            json!({ "fast_ema": period(), "slow_ema": period(), "signal_ema": period() }),
+            &["fast_ema", "slow_ema", "signal_ema"],
+        ),
+        tagged("Mae", json!({ "period": period() }), &["period"]),
+        tagged("Obv", json!({}), &[]),
+        tagged(
+            "Rsi",
+            json!({ "period": period(), "up_ema": ema, "down_ema": ema }),
+            &["period"],
+        ),
+        tagged("Sd", json!({ "period": period() }), &["period"]),
+        tagged("Sma", json!({ "period": period() }), &["period"]),
+        tagged("Smma", json!({ "period": period() }), &["period"]),
+        tagged(
+            "Stoch",
+            json!({ "period": period(), "smoothing_period": period() }),
+            &["period", "smoothing_period"],
+        ),
+        tagged(
+            "SuperTrend",
+            json!({ "multiplier": multiplier(), "period": period() }),
+            &["multiplier", "period"],
+        ),
+        tagged("Tr", json!({ "prev_close": { "type": "number" } }), &[]),
+        tagged("WilliamsR", json!({ "period": period() }), &["period"]),
+    ])
+}
+
+fn reference_path(name: &str) -> String {
+    format!("#/$defs/{name}")
+}
+
+fn reference(name: &str) -> Value {
+    json!({ "$ref": reference_path(name) })
+}
+
+fn count(minimum: usize) -> Value {
+    json!({ "type": "integer", "minimum": minimum })
+}
+
+fn nullable(schema: Value) -> Value {
+    json!({ "anyOf": [schema, { "type": "null" }] })
+}
+
+fn array_of(items: Value) -> Value {
+    json!({ "type": "array", "items": items })
+}
+
+fn map_of(values: Value) -> Value {
+    json!({ "type": "object", "additionalProperties": values })
+}
+
+fn one_of<const N: usize>(schemas: [Value; N]) -> Value {
+    json!({ "oneOf": Vec::from(schemas) })
+}
+
+fn unit_enum(variants: &[&str]) -> Value {
+    json!({ "type": "string", "enum": variants })
+}
+
+fn object(properties: Value, required: &[&str]) -> Value {
+    json!({
+        "type": "object",
+        "properties": properties,
+        "required": required,
+        "additionalProperties": false,
+    })
+}
+
+/// Externally tagged enum variant, serialized as `{ "<name>": <content> }`.
+fn variant(name: &str, content: Value) -> Value {
+    let mut properties = Map::new();
+    properties.insert(name.to_string(), content);
+    object(Value::Object(properties), &[name])
+}
+
+/// Internally tagged `Indicator` variant, serialized as `{ "type": "<name>", ... }`.
+fn tagged(name: &str, mut properties: Value, required: &[&str]) -> Value {
+    properties["type"] = json!({ "const": name });
+    let mut required = required.to_vec();
+    required.insert(0, "type");
+    object(properties, &required)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::TaResult;
+    use crate::strategy::node::{SequenceMode, TieBreak};
+    use crate::strategy::registry::IndicatorRegistry;
+    use crate::strategy::timeframe::Resample;
+    use crate::strategy::{Action, MachineState, Operand, RateLimit, Side, Timeframe};
+    use crate::types::OutputType;
+
+    /// Checks `instance` against the subset of JSON Schema used by this module, returning the
+    /// path of the first mismatch.
+    fn check(schema: &Value, instance: &Value, root: &Value, path: &str) -> Result<(), String> {
+        let fail = || Err(format!("{path}: {instance} does not match {schema}"));
+        if let Some(target) = schema.get("$ref").and_then(Value::as_str) {
+            let name = target.trim_start_matches("#/$defs/");
+            return check(&root["$defs"][name], instance, root, path);
+        }
+        if let Some(schemas) = schema.get("oneOf").and_then(Value::as_array) {
+            let matches = schemas
+                .iter()
+                .filter(|schema| check(schema, instance, root, path).is_ok())
+                .count();
+            return if matches == 1 { Ok(()) } else { fail() };
+        }
+        if let Some(schemas) = schema.get("anyOf").and_then(Value::as_array) {
+            return match schemas
+                .iter()
+                .any(|schema| check(schema, instance, root, path).is_ok())
+            {
+                true => Ok(()),
+                false => fail(),
+            };
+        }
+        if schema.get("const").is_some_and(|value| value != instance) {
+            return fail();
+        }
+        let values = schema.get("enum").and_then(Value::as_array);
+        if values.is_some_and(|values| !values.contains(instance)) {
+            return fail();
+        }
+        let typed = match schema.get("type").and_then(Value::as_str) {
+            None => true,
+            Some("object") => instance.is_object(),
+            Some("array") => instance.is_array(),
+            Some("string") => instance.is_string(),
+            Some("number") => instance.is_number(),
+            Some("integer") => instance.is_i64() || instance.is_u64(),
+            Some("boolean") => instance.is_boolean(),
+            Some("null") => instance.is_null(),
+            Some(other) => panic!("unsupported type {other}"),
+        };
+        if !typed {
+            return fail();
+        }
+        let bound = |key: &str| schema.get(key).and_then(Value::as_f64);
+        let value = instance.as_f64();
+        if bound("minimum").is_some_and(|minimum| value.is_some_and(|value| value < minimum))
+            || bound("maximum").is_some_and(|maximum| value.is_some_and(|value| value > maximum))
+        {
+            return fail();
+        }
+        if let Some(items) = schema.get("items") {
+            for (i, item) in instance.as_array().unwrap().iter().enumerate() {
+                check(items, item, root, &format!("{path}[{i}]"))?;
+            }
+        }
+        if let Some(object) = instance.as_object() {
+            for required in schema["required"].as_array().into_iter().flatten() {
+                if !object.contains_key(required.as_str().unwrap()) {
+                    return Err(format!("{path}: missing {required}"));
+                }
+            }
+            for (key, value) in object {
+                let path = format!("{path}.{key}");
+                match (&schema["properties"][key], &schema["additionalProperties"]) {
+                    (Value::Null, Value::Bool(false)) => return Err(format!("{path}: unexpected")),
+                    (Value::Null, Value::Null) => {}
+                    (Value::Null, additional) => check(additional, value, root, &path)?,
+                    (property, _) => check(property, value, root, &path)?,
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn assert_valid(schema: &Value, instance: &impl serde::Serialize) -> TaResult<()> {
+        let instance = serde_json::to_value(instance)?;
+        check(schema, &instance, schema, "$").unwrap_or_else(|error| panic!("{error}"));
+        Ok(())
+    }
+
+    fn assert_invalid(schema: &Value, instance: Value) {
+        assert!(check(schema, &instance, schema, "$").is_err(), "{instance}");
+    }
+
+    fn indicators() -> TaResult<Vec<Indicator>> {
+        Ok(vec![
+            Indicator::none(),
+            Indicator::alligator(13, 8, 8, 5, 5, 3)?,
+            Indicator::ao(5, 34)?,
+            Indicator::atr(14)?,
+            Indicator::bb(20, 2.0)?,
+            Indicator::ema(9)?,
+            Indicator::kc(20, 1.5)?,
+            Indicator::macd(12, 26, 9)?,
+            Indicator::mae(10)?,
+            Indicator::obv(),
+            Indicator::rsi(14)?,
+            Indicator::sd(20)?,
+            Indicator::sma(50)?,
+            Indicator::smma(7)?,
+            Indicator::stoch(14, 3)?,
+            Indicator::super_trend(3.0, 10)?,
+            Indicator::tr(),
+            Indicator::williams_r(14)?,
+        ])
+    }
+
+    #[test]
+    fn test_schema_accepts_serialized_strategies() -> TaResult<()> {
+        let mut shared = IndicatorRegistry::new();
+        for (i, indicator) in indicators()?.into_iter().enumerate() {
+            assert_valid(&Indicator::json_schema(), &indicator)?;
+            shared.insert(format!("i{i}"), indicator);
+        }
+        let condition = Condition::And(vec![
+            Condition::greater_than(Indicator::rsi(14)?, OutputType::Array(vec![1.0, 2.0])),
+            Condition::Not(Box::new(Condition::Compare {
+                left: Operand::Indicator("i0".to_string()),
+                right: Operand::Value(OutputType::Close),
+                operator: Operator::CrossOver(None),
+            })),
+            Condition::Position(Side::Long),
+        ]);
+        assert_valid(&Condition::json_schema(), &condition)?;
+        let strategy = StrategyNode::Shared {
+            indicators: shared,
+            node: Box::new(StrategyNode::MultiTimeframe {
+                timeframes: [(
+                    "1h".to_string(),
+                    Timeframe::new(Resample::Seconds(3600))
+                        .with_indicator("trend", Indicator::ema(20)?),
+                )]
+                .into(),
+                node: Box::new(StrategyNode::Sequence {
+                    mode: SequenceMode::Weighted(vec![1.0, 2.0, 0.5]),
+                    tie_break: TieBreak::PreferStronger,
+                    nodes: vec![
+                        StrategyNode::If {
+                            condition,
+                            then_branch: Box::new(StrategyNode::Action(Action::Buy)),
+                            else_branch: None,
+                        },
+                        StrategyNode::RateLimit {
+                            limit: RateLimit::Cooldown { secs: 60 },
+                            action: Box::new(StrategyNode::Action(Action::Sell)),
+                            fired: Vec::new(),
+                        },
+                        StrategyNode::StateMachine {
+                            states: vec![MachineState::new("idle").with_timeout(
+                                3,
+                                "idle",
+                                Some(Action::Hold),
+                            )],
+                            current: 0,
+                            bars_in_state: 0,
+                        },
+                    ],
+                }),
+            }),
+        };
+        assert_valid(&StrategyNode::json_schema(), &strategy)?;
+        assert_valid(&StrategyFile::json_schema(), &StrategyFile::new(strategy))?;
+        assert_valid(&Operator::json_schema(), &Operator::CrossUnder(None))?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_schema_rejects_invalid_documents() {
+        let schema = StrategyNode::json_schema();
+        assert_invalid(&schema, json!({ "Action": "Buyy" }));
+        assert_invalid(&schema, json!({ "Sequence": { "mode": "First" } }));
+        assert_invalid(
+            &schema,
+            json!({ "If": {
+                "condition": { "Value": {
+                    "indicator": { "type": "Rsi", "period": 0 },
+                    "value": { "Single": 30.0 },
+                    "operator": "LessThan",
+                } },
+                "then_branch": { "Action": "Buy" },
+            } }),
+        );
+        assert_invalid(
+            &Indicator::json_schema(),
+            json!({ "type": "Bb", "period": 20 }),
+        );
+        assert_invalid(
+            &Indicator::json_schema(),
+            json!({ "type": "Sma", "length": 20 }),
+        );
+        assert_invalid(&StrategyFile::json_schema(), json!({ "Action": "Buy" }));
+        assert_invalid(
+            &schema,
+            json!({ "Sequence": { "mode": { "Percentage": 101 }, "nodes": [] } }),
+        );
+        assert_eq!(StrategyNode::json_schema()["$schema"], JSON_SCHEMA_DIALECT);
+    }
+}
@@ -0,0 +1,178 @@
+use chrono::NaiveTime;
+use serde::{Deserialize, Serialize};
+
+/// Major forex market sessions, used by `Condition::Session`.
+///
+/// Session hours are fixed in UTC and do not follow daylight saving time changes.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Session {
+    /// 22:00 - 07:00 UTC.
+    Sydney,
+    /// 00:00 - 09:00 UTC.
+    Tokyo,
+    /// 08:00 - 17:00 UTC.
+    London,
+    /// 13:00 - 22:00 UTC.
+    NewYork,
+}
+
+impl Session {
+    /// Returns the start (inclusive) and end (exclusive) of the session in UTC.
+    pub fn window(&self) -> (NaiveTime, NaiveTime) {
+        let (start, end) = match self {
+            Session::Sydney => (22, 7),
+            Session::Tokyo => (0, 9),
+            Session::London => (8, 17),
+            Session::NewYork => (13, 22),
+        };
+        (hour(start), hour(end))
+    }
+
+    /// Returns whether `time`, in UTC, falls within the session.
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        let (start, end) = self.window();
+        in_window(time, start, end)
+    }
+}
+
+fn hour(hour: u32) -> NaiveTime {
+    NaiveTime::from_hms_opt(hour, 0, 0).unwrap_or_default()
+}
+
+/// Returns whether `time` lies in `[start, end)`, wrapping around midnight when `start > end`.
+/// A window with `start == end` covers the whole day.
+pub(crate) fn in_window(time: NaiveTime, start: NaiveTime, end: NaiveTime) -> bool {
+    if start <= end {
+        start == end || (start <= time && time < end)
+    } else {
+        time >= start || time < end
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::{TaError, TaResult};
+    use crate::strategy::strat::Strategy;
+    use crate::strategy::{Action, Condition, Context, MarketData, StrategyError, StrategyNode};
+    use chrono::{DateTime, TimeZone, Utc, Weekday};
+
+    fn at(day: u32, hour: u32, minute: u32) -> DateTime<Utc> {
+        // 2024-01-01 is a Monday.
+        Utc.with_ymd_and_hms(2024, 1, day, hour, minute, 0).unwrap()
+    }
+
+    fn time(hour: u32, minute: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(hour, minute, 0).unwrap()
+    }
+
+    fn holds(condition: &mut Condition, timestamp: DateTime<Utc>) -> TaResult<bool> {
+        condition.evaluate_with(&MarketData::Float(1.0), &Context::at(timestamp))
+    }
+
+    #[test]
+    fn test_in_window_wraps_midnight() {
+        assert!(in_window(time(9, 0), time(9, 0), time(17, 0)));
+        assert!(!in_window(time(17, 0), time(9, 0), time(17, 0)));
+        assert!(in_window(time(23, 0), time(22, 0), time(7, 0)));
+        assert!(in_window(time(3, 0), time(22, 0), time(7, 0)));
+        assert!(!in_window(time(12, 0), time(22, 0), time(7, 0)));
+        assert!(in_window(time(12, 0), time(0, 0), time(0, 0)));
+    }
+
+    #[test]
+    fn test_time_of_day_with_offset() -> TaResult<()> {
+        // 09:30 - 16:00 at UTC-5.
+        let mut condition = Condition::TimeOfDay {
+            start: time(9, 30),
+            end: time(16, 0),
+            tz_offset: -300,
+        };
+        assert!(holds(&mut condition, at(2, 14, 30))?);
+        assert!(!holds(&mut condition, at(2, 14, 29))?);
+        assert!(holds(&mut condition, at(2, 20, 59))?);
+        assert!(!holds(&mut condition, at(2, 21, 0))?);
+
+        for tz_offset in [1440, i32::MAX, i32::MIN] {
+            let mut condition = Condition::TimeOfDay {
+                start: time(9, 30),
+                end: time(16, 0),
+                tz_offset,
+            };
+            assert!(condition.validate().is_err());
+            assert!(holds(&mut condition, at(2, 14, 30)).is_err());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_day_of_week_and_session() -> TaResult<()> {
+        let mut weekdays = Condition::Not(Box::new(Condition::DayOfWeek(vec![
+            Weekday::Sat,
+            Weekday::Sun,
+        ])));
+        assert!(holds(&mut weekdays, at(5, 12, 0))?);
+        assert!(!holds(&mut weekdays, at(6, 12, 0))?);
+        assert!(!holds(&mut weekdays, at(7, 12, 0))?);
+
+        let mut london = Condition::Session(Session::London);
+        assert!(holds(&mut london, at(2, 8, 0))?);
+        assert!(!holds(&mut london, at(2, 17, 0))?);
+        let mut sydney = Condition::Session(Session::Sydney);
+        assert!(holds(&mut sydney, at(2, 23, 0))?);
+        assert!(holds(&mut sydney, at(2, 6, 59))?);
+        assert!(!holds(&mut sydney, at(2, 7, 0))?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_time_condition_requires_timestamp() {
+        let mut condition = Condition::Session(Session::Tokyo);
+        assert_eq!(
+            condition.evaluate(&MarketData::Float(1.0)),
+            Err(TaError::Strategy(StrategyError::MissingTimestamp))
+        );
+    }
+
+    #[test]
+    fn test_strategy_filters_by_time() -> TaResult<()> {
+        let node = StrategyNode::If {
+            condition: Condition::And(vec![
+                Condition::Session(Session::NewYork),
+                Condition::Not(Box::new(Condition::DayOfWeek(vec![Weekday::Fri]))),
+            ]),
+            then_branch: Box::new(StrategyNode::Action(Action::Buy)),
+            else_branch: None,
+        };
+        let json = serde_json::to_string(&node)?;
+        assert_eq!(serde_json::from_str::<StrategyNode>(&json)?, node);
+
+        let mut strategy = Strategy::new(node);
+        let data = MarketData::Float(1.0);
+        assert_eq!(
+            strategy.evaluate_at(&data, at(2, 14, 0))?,
+            Some(Action::Buy)
+        );
+        assert_eq!(
+            strategy.evaluate_at(&data, at(2, 23, 0))?,
+            Some(Action::Hold)
+        );
+        assert_eq!(
+            strategy.evaluate_at(&data, at(5, 14, 0))?,
+            Some(Action::Hold)
+        );
+        let signal = strategy
+            .evaluate_signal(&data, Some(at(3, 15, 0)))?
+            .unwrap();
+        assert_eq!(signal.action, Action::Buy);
+
+        let trace = strategy.explain(&data, Some(at(3, 16, 0)))?.unwrap();
+        assert_eq!(trace.action, Action::Buy);
+        assert!(trace.condition.is_some());
+        assert_eq!(
+            strategy.explain(&data, None),
+            Err(TaError::Strategy(StrategyError::MissingTimestamp))
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,179 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::strategy::Action;
+
+/// Suggested lifetime of the position opened by a signal.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Expiry {
+    /// Expire after the given number of bars.
+    Bars(usize),
+    /// Expire after the given number of seconds.
+    Seconds(u64),
+}
+
+/// A trading action together with the context explaining why and how strongly it fired.
+///
+/// Node ids describe the location of a node in the strategy tree, starting at `root`, for
+/// example `root.Sequence[1].If.then_branch.Action`. Condition ids follow the same scheme,
+/// for example `root.Sequence[1].If.condition.And[0]`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Signal {
+    /// The action emitted by the strategy.
+    pub action: Action,
+    /// Confidence of the action between `0.0` and `1.0`. `Hold` always has a confidence of `0.0`.
+    pub confidence: f64,
+    /// Timestamp of the bar that triggered the signal, if provided.
+    pub timestamp: Option<DateTime<Utc>>,
+    /// Index of the bar that triggered the signal, counted from the first bar seen by the strategy.
+    pub index: usize,
+    /// Ids of the nodes that produced the action, from the root down to the action leaves.
+    pub nodes: Vec<String>,
+    /// Ids of the conditions that held on the path to the action.
+    pub conditions: Vec<String>,
+    /// Suggested expiry of the position opened by this signal.
+    pub expiry: Option<Expiry>,
+    /// Suggested size of the position opened by this signal.
+    pub position_size: Option<f64>,
+}
+
+impl Signal {
+    /// Returns `true` if the signal asks to enter or exit a position, i.e. is not `Hold`.
+    pub fn is_actionable(&self) -> bool {
+        self.action != Action::Hold
+    }
+}
+
+impl From<Signal> for Action {
+    fn from(signal: Signal) -> Self {
+        signal.action
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "js")]
+    use crate::indicators::indicator::Indicator;
+    #[cfg(not(feature = "js"))]
+    use crate::Indicator;
+
+    use super::*;
+    use crate::error::TaResult;
+    use crate::strategy::node::{SequenceMode, TieBreak};
+    use crate::strategy::strat::Strategy;
+    use crate::strategy::{Condition, MarketData, StrategyNode};
+    use crate::types::OutputType;
+    use chrono::TimeZone;
+
+    fn confluence() -> TaResult<StrategyNode> {
+        Ok(StrategyNode::Sequence {
+            mode: SequenceMode::Majority,
+            nodes: vec![
+                StrategyNode::If {
+                    condition: Condition::And(vec![
+                        Condition::greater_than(Indicator::sma(1)?, OutputType::Single(10.0)),
+                        Condition::less_than(Indicator::sma(1)?, OutputType::Single(100.0)),
+                    ]),
+                    then_branch: Box::new(StrategyNode::Action(Action::StrongBuy)),
+                    else_branch: Some(Box::new(StrategyNode::Action(Action::Sell))),
+                },
+                StrategyNode::Action(Action::StrongBuy),
+                StrategyNode::Action(Action::Hold),
+            ],
+            tie_break: TieBreak::default(),
+        })
+    }
+
+    #[test]
+    fn test_evaluate_signal_provenance() -> TaResult<()> {
+        let timestamp = Utc.with_ymd_and_hms(2024, 1, 2, 3, 4, 5).unwrap();
+        let mut strategy = Strategy::new(confluence()?)
+            .with_expiry(Expiry::Seconds(60))
+            .with_position_size(10.0);
+
+        // Warm-up bar.
+        assert_eq!(strategy.evaluate_signal(&MarketData::Float(50.0), None)?, None);
+
+        let signal = strategy
+            .evaluate_signal(&MarketData::Float(50.0), Some(timestamp))?
+            .unwrap();
+        assert_eq!(signal.action, Action::StrongBuy);
+        assert_eq!(signal.confidence, 1.0);
+        assert_eq!(signal.index, 1);
+        assert_eq!(signal.timestamp, Some(timestamp));
+        assert_eq!(signal.expiry, Some(Expiry::Seconds(60)));
+        assert_eq!(signal.position_size, Some(10.0));
+        assert_eq!(
+            signal.nodes,
+            vec![
+                "root.Sequence",
+                "root.Sequence[0].If",
+                "root.Sequence[0].If.then_branch.Action",
+                "root.Sequence[1].Action",
+            ]
+        );
+        assert_eq!(
+            signal.conditions,
+            vec![
+                "root.Sequence[0].If.condition.And[0]",
+                "root.Sequence[0].If.condition.And[1]",
+                "root.Sequence[0].If.condition",
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_evaluate_signal_matches_evaluate() -> TaResult<()> {
+        let mut with_signal = Strategy::new(confluence()?);
+        let mut plain = Strategy::new(confluence()?);
+        for price in [50.0, 5.0, 50.0, 500.0, 20.0] {
+            let data = MarketData::Float(price);
+            let signal = with_signal.evaluate_signal(&data, None)?;
+            assert_eq!(signal.map(Action::from), plain.evaluate(&data)?);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_hold_signal_has_no_provenance() -> TaResult<()> {
+        let mut strategy = Strategy::new(confluence()?);
+        strategy.evaluate_signal(&MarketData::Float(5.0), None)?;
+        // The If node emits Sell, outvoted by StrongBuy: the tie resolves to Hold.
+        let signal = strategy
+            .evaluate_signal(&MarketData::Float(5.0), None)?
+            .unwrap();
+        assert_eq!(signal.action, Action::Hold);
+        assert_eq!(signal.confidence, 0.0);
+        assert!(!signal.is_actionable());
+        assert!(signal.nodes.is_empty());
+        assert!(signal.conditions.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_serde_signal() -> TaResult<()> {
+        let mut strategy = Strategy::new(StrategyNode::Action(Action::Buy));
+        let signal = strategy
+            .evaluate_signal(&MarketData::Float(1.0), None)?
+            .unwrap();
+        assert_eq!(signal.confidence, 0.5);
+        let json = serde_json::to_string(&signal)?;
+        assert_eq!(serde_json::from_str::<Signal>(&json)?, signal);
+
+        let strategy = Strategy::new(confluence()?)
+            .with_expiry(Expiry::Bars(3))
+            .with_position_size(2.5);
+        let json = serde_json::to_string(&strategy)?;
+        let read: Strategy = serde_json::from_str(&json)?;
+        assert_eq!(read.nodes, strategy.nodes);
+        assert_eq!(read.expiry, Some(Expiry::Bars(3)));
+        assert_eq!(read.position_size, Some(2.5));
+
+        // Strategies saved as a bare tree load without signal settings.
+        let bare: Strategy = serde_json::from_str(&serde_json::to_string(&strategy.nodes)?)?;
+        assert_eq!(bare.nodes, strategy.nodes);
+        assert_eq!((bare.expiry, bare.position_size), (None, None));
+        Ok(())
+    }
+}
@@ -0,0 +1,599 @@
+use crate::{
+    strategy::{
+        Action, Condition, StrategyNode,
+        condition::Operator,
+        machine::{MachineState, Transition},
+        node::SequenceMode,
+    },
+    traits::Period,
+};
+
+impl StrategyNode {
+    /// Rewrites redundant parts of the tree while keeping the actions it emits, its runtime
+    /// state and its `max_period`.
+    ///
+    /// Nested `And`s and `Or`s are flattened, duplicate sub-conditions and single element
+    /// `And`s and `Or`s are removed and double negations are dropped. `Sequence` nodes with a
+    /// single child are replaced by the child, unless a `Weighted` or `Score` mode or a
+    /// percentage above 100 may turn its action into `Hold`. `If` nodes whose branches are
+    /// identical are replaced by the branch, a missing `else_branch` counting as `Hold`.
+    ///
+    /// Conditions and nodes whose evaluation updates state, such as `CrossOver` operators or
+    /// `Timeout` nodes, are only ever moved, never merged or dropped, as evaluating them less
+    /// often would change their results. Conditions are not dropped either when that would
+    /// lower `max_period`.
+    pub fn simplify(self) -> StrategyNode {
+        let period = self.max_period();
+        let mut simplifier = Simplifier {
+            period,
+            // Every occurrence of the longest period but one may be dropped.
+            spare: period.map_or(0, |period| self.occurrences(period).saturating_sub(1)),
+        };
+        simplifier.node(self)
+    }
+
+    /// Counts the indicators of the tree whose period is `period`, as seen by `max_period`.
+    fn occurrences(&self, period: usize) -> usize {
+        match self {
+            StrategyNode::Action(_) => 0,
+            StrategyNode::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                condition.occurrences(period)
+                    + then_branch.occurrences(period)
+                    + else_branch
+                        .as_ref()
+                        .map_or(0, |node| node.occurrences(period))
+            }
+            StrategyNode::Sequence { nodes, .. } => {
+                nodes.iter().map(|node| node.occurrences(period)).sum()
+            }
+            StrategyNode::Preprocess {
+                then_branch: node, ..
+            }
+            | StrategyNode::Timeout { action: node, .. }
+            | StrategyNode::RateLimit { action: node, .. }
+            | StrategyNode::MultiTimeframe { node, .. } => node.occurrences(period),
+            StrategyNode::StateMachine { states, .. } => states
+                .iter()
+                .flat_map(|state| &state.transitions)
+                .map(|transition| transition.condition.occurrences(period))
+                .sum(),
+            StrategyNode::Shared { indicators, node } => {
+                let shared = indicators
+                    .ids()
+                    .filter_map(|id| indicators.get(id))
+                    .filter(|indicator| indicator.period() == period)
+                    .count();
+                shared + node.occurrences(period)
+            }
+        }
+    }
+
+    /// Returns whether evaluating the node leaves no state behind, so that evaluating a copy
+    /// of it on different bars gives the same actions.
+    fn is_stateless(&self) -> bool {
+        match self {
+            StrategyNode::Action(_) => true,
+            StrategyNode::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                condition.is_stateless()
+                    && then_branch.is_stateless()
+                    && else_branch.as_ref().is_none_or(|node| node.is_stateless())
+            }
+            StrategyNode::Sequence { nodes, .. } => nodes.iter().all(StrategyNode::is_stateless),
+            StrategyNode::Preprocess { then_branch, .. } => then_branch.is_stateless(),
+            _ => false,
+        }
+    }
+}
+
+impl Condition {
+    fn occurrences(&self, period: usize) -> usize {
+        match self {
+            Condition::Value { indicator, .. } | Condition::ValueInversed { indicator, .. } => {
+                usize::from(indicator.period() == period)
+            }
+            Condition::Indicator { left, right, .. } => {
+                usize::from(left.period() == period) + usize::from(right.period() == period)
+            }
+            Condition::And(conditions) | Condition::Or(conditions) => conditions
+                .iter()
+                .map(|condition| condition.occurrences(period))
+                .sum(),
+            Condition::Not(condition) => condition.occurrences(period),
+            _ => 0,
+        }
+    }
+
+    /// Returns whether the condition has no crossover operator, the only condition state that
+    /// is updated on evaluation rather than on `update`.
+    fn is_stateless(&self) -> bool {
+        let stateless = |operator: &Operator| {
+            !matches!(operator, Operator::CrossOver(_) | Operator::CrossUnder(_))
+        };
+        match self {
+            Condition::ValueOnly { operator, .. }
+            | Condition::Value { operator, .. }
+            | Condition::ValueInversed { operator, .. }
+            | Condition::Indicator { operator, .. }
+            | Condition::Compare { operator, .. }
+            | Condition::BarsInTrade { operator, .. } => stateless(operator),
+            Condition::And(conditions) | Condition::Or(conditions) => {
+                conditions.iter().all(Condition::is_stateless)
+            }
+            Condition::Not(condition) => condition.is_stateless(),
+            Condition::Position(_)
+            | Condition::TimeOfDay { .. }
+            | Condition::DayOfWeek(_)
+            | Condition::Session(_) => true,
+        }
+    }
+}
+
+struct Simplifier {
+    /// `max_period` of the tree being simplified.
+    period: Option<usize>,
+    /// Number of indicators with that period that may still be dropped.
+    spare: usize,
+}
+
+impl Simplifier {
+    fn occurrences(&self, condition: &Condition) -> usize {
+        self.period
+            .map_or(0, |period| condition.occurrences(period))
+    }
+
+    fn node(&mut self, node: StrategyNode) -> StrategyNode {
+        match node {
+            StrategyNode::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                let condition = self.condition(condition);
+                let then_branch = self.node(*then_branch);
+                let else_branch = else_branch.map(|node| self.node(*node));
+                let same = match &else_branch {
+                    Some(else_branch) => *else_branch == then_branch,
+                    None => then_branch == StrategyNode::Action(Action::Hold),
+                };
+                let dropped = self.occurrences(&condition);
+                if same && then_branch.is_stateless() && dropped <= self.spare {
+                    self.spare -= dropped;
+                    if let Some(period) = self.period {
+                        // The identical else branch is dropped too.
+                        self.spare -= else_branch.map_or(0, |node| node.occurrences(period));
+                    }
+                    return then_branch;
+                }
+                StrategyNode::If {
+                    condition,
+                    then_branch: Box::new(then_branch),
+                    else_branch: else_branch.map(Box::new),
+                }
+            }
+            StrategyNode::Sequence {
+                mode,
+                nodes,
+                tie_break,
+            } => {
+                let mut nodes: Vec<StrategyNode> =
+                    nodes.into_iter().map(|node| self.node(node)).collect();
+                let transparent = match mode {
+                    SequenceMode::Weighted(_) | SequenceMode::Score { .. } => false,
+                    SequenceMode::Percentage(percentage) => percentage <= 100,
+                    _ => true,
+                };
+                if transparent && nodes.len() == 1 {
+                    return nodes.remove(0);
+                }
+                StrategyNode::Sequence {
+                    mode,
+                    nodes,
+                    tie_break,
+                }
+            }
+            StrategyNode::Preprocess { step, then_branch } => StrategyNode::Preprocess {
+                step,
+                then_branch: Box::new(self.node(*then_branch)),
+            },
+            StrategyNode::Timeout {
+                cooldown,
+                remaining,
+                action,
+            } => StrategyNode::Timeout {
+                cooldown,
+                remaining,
+                action: Box::new(self.node(*action)),
+            },
+            StrategyNode::RateLimit {
+                limit,
+                action,
+                fired,
+            } => StrategyNode::RateLimit {
+                limit,
+                action: Box::new(self.node(*action)),
+                fired,
+            },
+            StrategyNode::StateMachine {
+                states,
+                current,
+                bars_in_state,
+            } => StrategyNode::StateMachine {
+                states: states
+                    .into_iter()
+                    .map(|state| MachineState {
+                        transitions: state
+                            .transitions
+                            .into_iter()
+                            .map(|t| Transition {
+                                condition: self.condition(t.condition),
+                                ..t
+                            })
+                            .collect(),
+                        ..state
+                    })
+                    .collect(),
+                current,
+                bars_in_state,
+            },
+            StrategyNode::Shared { indicators, node } => StrategyNode::Shared {
+                indicators,
+                node: Box::new(self.node(*node)),
+            },
+            StrategyNode::MultiTimeframe { timeframes, node } => StrategyNode::MultiTimeframe {
+                timeframes,
+                node: Box::new(self.node(*node)),
+            },
+            action @ StrategyNode::Action(_) => action,
+        }
+    }
+
+    fn condition(&mut self, condition: Condition) -> Condition {
+        match condition {
+            Condition::And(conditions) => self.junction(conditions, true),
+            Condition::Or(conditions) => self.junction(conditions, false),
+            Condition::Not(condition) => match self.condition(*condition) {
+                Condition::Not(condition) => *condition,
+                condition => Condition::Not(Box::new(condition)),
+            },
+            leaf => leaf,
+        }
+    }
+
+    /// Simplifies the members of an `And` (`conjunction`) or `Or`, flattening nested
+    /// junctions of the same kind and dropping stateless duplicates.
+    fn junction(&mut self, conditions: Vec<Condition>, conjunction: bool) -> Condition {
+        let mut members: Vec<Condition> = Vec::with_capacity(conditions.len());
+        for condition in conditions {
+            let flattened = match self.condition(condition) {
+                Condition::And(nested) if conjunction => nested,
+                Condition::Or(nested) if !conjunction => nested,
+                condition => vec![condition],
+            };
+            for condition in flattened {
+                if condition.is_stateless() && members.contains(&condition) {
+                    self.spare -= self.occurrences(&condition);
+                } else {
+                    members.push(condition);
+                }
+            }
+        }
+        match (members.len(), conjunction) {
+            (1, _) => members.remove(0),
+            (_, true) => Condition::And(members),
+            (_, false) => Condition::Or(members),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(not(feature = "js"))]
+    use crate::Indicator;
+    #[cfg(feature = "js")]
+    use crate::indicators::indicator::Indicator;
+
+    use super::*;
+    use crate::error::TaResult;
+    use crate::strategy::MarketData;
+    use crate::strategy::node::TieBreak;
+    use crate::types::OutputType;
+
+    /// Xorshift generator, enough to build reproducible random trees and prices.
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn below(&mut self, n: usize) -> usize {
+            (self.next() % n as u64) as usize
+        }
+
+        fn unit(&mut self) -> f64 {
+            (self.next() >> 11) as f64 / (1u64 << 53) as f64
+        }
+    }
+
+    fn action(rng: &mut Rng) -> Action {
+        [
+            Action::StrongBuy,
+            Action::Buy,
+            Action::Hold,
+            Action::Sell,
+            Action::StrongSell,
+        ][rng.below(5)]
+    }
+
+    fn condition(rng: &mut Rng, depth: usize) -> TaResult<Condition> {
+        let leaf = depth == 0 || rng.below(3) == 0;
+        Ok(match if leaf { rng.below(4) } else { 4 + rng.below(4) } {
+            0 => {
+                let operator = [
+                    Operator::GreaterThan,
+                    Operator::LessThan,
+                    Operator::CrossOver(None),
+                    Operator::CrossUnder(None),
+                ][rng.below(4)]
+                .clone();
+                let indicator = match rng.below(3) {
+                    0 => Indicator::sma(1 + rng.below(5))?,
+                    1 => Indicator::ema(1 + rng.below(5))?,
+                    _ => Indicator::rsi(2 + rng.below(4))?,
+                };
+                let level = if matches!(indicator, Indicator::Rsi(_)) {
+                    50.0
+                } else {
+                    100.0
+                };
+                Condition::value(indicator, OutputType::Single(level), operator)
+            }
+            1 => Condition::indicator(
+                Indicator::sma(1 + rng.below(3))?,
+                Indicator::ema(2 + rng.below(6))?,
+                Operator::GreaterThan,
+            ),
+            2 => Condition::ValueOnly {
+                left: OutputType::Close,
+                right: OutputType::Single(100.0),
+                operator: Operator::GreaterThanOrEqual,
+            },
+            3 => Condition::Not(Box::new(Condition::Not(Box::new(condition(rng, 0)?)))),
+            4 | 5 => {
+                let first = condition(rng, depth - 1)?;
+                let mut members = vec![first.clone()];
+                for _ in 0..rng.below(3) {
+                    members.push(condition(rng, depth - 1)?);
+                }
+                // Duplicates and nested junctions of the same kind.
+                if rng.below(2) == 0 {
+                    members.push(first);
+                }
+                if rng.below(2) == 0 {
+                    members = vec![Condition::And(members)];
+                }
+                match rng.below(2) {
+                    0 => Condition::And(members),
+                    _ => Condition::Or(members),
+                }
+            }
+            _ => Condition::Not(Box::new(condition(rng, depth - 1)?)),
+        })
+    }
+
+    fn node(rng: &mut Rng, depth: usize) -> TaResult<StrategyNode> {
+        let leaf = depth == 0 || rng.below(4) == 0;
+        Ok(match if leaf { 0 } else { 1 + rng.below(5) } {
+            0 => StrategyNode::Action(action(rng)),
+            1 | 2 => {
+                let then_branch = node(rng, depth - 1)?;
+                let else_branch = match rng.below(3) {
+                    0 => None,
+                    1 => Some(Box::new(then_branch.clone())),
+                    _ => Some(Box::new(node(rng, depth - 1)?)),
+                };
+                StrategyNode::If {
+                    condition: condition(rng, 2)?,
+                    then_branch: Box::new(then_branch),
+                    else_branch,
+                }
+            }
+            3 => {
+                let mode = [
+                    SequenceMode::First,
+                    SequenceMode::Last,
+                    SequenceMode::Any,
+                    SequenceMode::All,
+                    SequenceMode::Majority,
+                    SequenceMode::Percentage(60),
+                    SequenceMode::Score {
+                        buy_threshold: 1.5,
+                        sell_threshold: -1.5,
+                    },
+                ][rng.below(7)]
+                .clone();
+                StrategyNode::Sequence {
+                    mode,
+                    nodes: (0..1 + rng.below(3))
+                        .map(|_| node(rng, depth - 1))
+                        .collect::<TaResult<_>>()?,
+                    tie_break: TieBreak::PreferStronger,
+                }
+            }
+            4 => StrategyNode::Timeout {
+                cooldown: 1 + rng.below(3),
+                remaining: 0,
+                action: Box::new(node(rng, depth - 1)?),
+            },
+            _ => {
+                let then_branch = node(rng, depth - 1)?;
+                StrategyNode::If {
+                    condition: condition(rng, 1)?,
+                    then_branch: Box::new(then_branch.clone()),
+                    else_branch: Some(Box::new(then_branch)),
+                }
+            }
+        })
+    }
+
+    fn size(node: &StrategyNode) -> usize {
+        fn conditions(condition: &Condition) -> usize {
+            1 + match condition {
+                Condition::And(members) | Condition::Or(members) => {
+                    members.iter().map(conditions).sum()
+                }
+                Condition::Not(condition) => conditions(condition),
+                _ => 0,
+            }
+        }
+        1 + match node {
+            StrategyNode::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => conditions(condition) + size(then_branch) + else_branch.as_deref().map_or(0, size),
+            StrategyNode::Sequence { nodes, .. } => nodes.iter().map(size).sum(),
+            StrategyNode::Timeout { action, .. } => size(action),
+            _ => 0,
+        }
+    }
+
+    fn prices(rng: &mut Rng, bars: usize) -> Vec<MarketData> {
+        let mut price = 100.0;
+        (0..bars)
+            .map(|_| {
+                price += rng.unit() * 4.0 - 2.0;
+                MarketData::Float(price)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_simplify_rewrites() -> TaResult<()> {
+        let rsi = || Condition::less_than(Indicator::rsi(14).unwrap(), OutputType::Single(30.0));
+        let sma = || Condition::greater_than(Indicator::sma(5).unwrap(), OutputType::Close);
+        let node = StrategyNode::Sequence {
+            mode: SequenceMode::All,
+            nodes: vec![StrategyNode::If {
+                condition: Condition::And(vec![
+                    Condition::And(vec![rsi(), sma()]),
+                    Condition::Not(Box::new(Condition::Not(Box::new(rsi())))),
+                ]),
+                then_branch: Box::new(StrategyNode::If {
+                    condition: Condition::Or(vec![sma()]),
+                    then_branch: Box::new(StrategyNode::Action(Action::Buy)),
+                    else_branch: Some(Box::new(StrategyNode::Action(Action::Buy))),
+                }),
+                else_branch: Some(Box::new(StrategyNode::Action(Action::Hold))),
+            }],
+            tie_break: TieBreak::Hold,
+        };
+        let simplified = node.clone().simplify();
+        assert_eq!(
+            simplified,
+            StrategyNode::If {
+                condition: Condition::And(vec![rsi(), sma()]),
+                then_branch: Box::new(StrategyNode::Action(Action::Buy)),
+                else_branch: Some(Box::new(StrategyNode::Action(Action::Hold))),
+            }
+        );
+        assert_eq!(simplified.max_period(), node.max_period());
+        Ok(())
+    }
+
+    #[test]
+    fn test_simplify_keeps_period_and_state() -> TaResult<()> {
+        // Dropping the condition would lower `max_period` from 20 to 5.
+        let node = StrategyNode::If {
+            condition: Condition::greater_than(Indicator::sma(20)?, OutputType::Close),
+            then_branch: Box::new(StrategyNode::If {
+                condition: Condition::greater_than(Indicator::sma(5)?, OutputType::Close),
+                then_branch: Box::new(StrategyNode::Action(Action::Buy)),
+                else_branch: None,
+            }),
+            else_branch: Some(Box::new(StrategyNode::If {
+                condition: Condition::greater_than(Indicator::sma(5)?, OutputType::Close),
+                then_branch: Box::new(StrategyNode::Action(Action::Buy)),
+                else_branch: None,
+            })),
+        };
+        assert_eq!(node.clone().simplify(), node);
+
+        // Identical stateful branches are kept, as each counts down on its own bars.
+        let timeout = StrategyNode::Timeout {
+            cooldown: 2,
+            remaining: 1,
+            action: Box::new(StrategyNode::Action(Action::Sell)),
+        };
+        let node = StrategyNode::If {
+            condition: Condition::ValueOnly {
+                left: OutputType::Close,
+                right: OutputType::Single(1.0),
+                operator: Operator::GreaterThan,
+            },
+            then_branch: Box::new(timeout.clone()),
+            else_branch: Some(Box::new(timeout)),
+        };
+        assert_eq!(node.clone().simplify(), node);
+
+        // Duplicate crossovers are kept, as each remembers its own previous value.
+        let cross = || Condition::cross_over(Indicator::sma(3).unwrap(), OutputType::Single(1.0));
+        let condition = Condition::And(vec![cross(), cross()]);
+        assert_eq!(
+            StrategyNode::If {
+                condition: condition.clone(),
+                then_branch: Box::new(StrategyNode::Action(Action::Buy)),
+                else_branch: None,
+            }
+            .simplify(),
+            StrategyNode::If {
+                condition,
+                then_branch: Box::new(StrategyNode::Action(Action::Buy)),
+                else_branch: None,
+            }
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_simplify_equivalent_on_random_data() -> TaResult<()> {
+        let mut rng = Rng(0x9e37_79b9_7f4a_7c15);
+        let mut shrunk = 0;
+        for _ in 0..300 {
+            let mut original = node(&mut rng, 4)?;
+            let data = prices(&mut rng, 120);
+            // Simplify part way through the run as well, to cover trees carrying state.
+            let split = rng.below(data.len());
+            for bar in &data[..split] {
+                original.evaluate(bar)?;
+            }
+            let mut simplified = original.clone().simplify();
+            assert_eq!(simplified.max_period(), original.max_period());
+            assert!(size(&simplified) <= size(&original));
+            if size(&simplified) < size(&original) {
+                shrunk += 1;
+            }
+            for (index, bar) in data[split..].iter().enumerate() {
+                assert_eq!(
+                    simplified.evaluate(bar)?,
+                    original.evaluate(bar)?,
+                    "bar {} of {original:?}\nsimplified to {simplified:?}",
+                    split + index
+                );
+            }
+        }
+        assert!(shrunk > 100, "only {shrunk} trees were simplified");
+        Ok(())
+    }
+}
@@ -1,10 +1,12 @@
 use std::ops::Deref;
 
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 use crate::{
     error::TaResult,
-    strategy::{Action, MarketData, StrategyNode},
+    strategy::context::Context,
+    strategy::{Action, Expiry, MarketData, NodeTrace, Signal, StrategyNode},
     traits::{Period, Reset},
 };
 
@@ -18,6 +20,11 @@ pub enum State {
 pub struct Strategy {
     pub nodes: StrategyNode,
     pub state: State,
+    /// Suggested expiry attached to every signal produced by `evaluate_signal`.
+    pub expiry: Option<Expiry>,
+    /// Suggested position size attached to every signal produced by `evaluate_signal`.
+    pub position_size: Option<f64>,
+    index: usize,
 }
 
 impl Deref for Strategy {
@@ -30,10 +37,7 @@ impl Deref for Strategy {
 
 impl Default for Strategy {
     fn default() -> Self {
-        Self {
-            nodes: StrategyNode::default(),
-            state: State::Progress(0),
-        }
+        Self::new(StrategyNode::default())
     }
 }
 
@@ -42,21 +46,114 @@ impl Strategy {
         Self {
             nodes,
             state: State::Progress(0),
+            expiry: None,
+            position_size: None,
+            index: 0,
         }
     }
 
+    /// Sets the suggested expiry attached to the produced signals.
+    pub fn with_expiry(mut self, expiry: Expiry) -> Self {
+        self.expiry = Some(expiry);
+        self
+    }
+
+    /// Sets the suggested position size attached to the produced signals.
+    pub fn with_position_size(mut self, position_size: f64) -> Self {
+        self.position_size = Some(position_size);
+        self
+    }
+
     pub fn evaluate(&mut self, data: &MarketData) -> TaResult<Option<Action>> {
+        self.evaluate_in(data, &Context::default())
+    }
+
+    /// Evaluates the strategy like `evaluate` for a bar with the given timestamp, which
+    /// time based conditions are checked against.
+    pub fn evaluate_at(
+        &mut self,
+        data: &MarketData,
+        timestamp: DateTime<Utc>,
+    ) -> TaResult<Option<Action>> {
+        self.evaluate_in(data, &Context::at(timestamp))
+    }
+
+    fn evaluate_in(&mut self, data: &MarketData, context: &Context) -> TaResult<Option<Action>> {
         self.next();
         match self.state {
             State::Progress(_) => {
-                self.nodes.update(data)?;
+                self.nodes.update_with(data, context)?;
                 Ok(None)
             }
-            State::Ready => self.nodes.evaluate(data).map(Some),
+            State::Ready => self.nodes.evaluate_with(data, context).map(Some),
+        }
+    }
+
+    /// Evaluates the strategy like `evaluate`, returning a `Signal` that carries the action
+    /// together with its confidence and the nodes and conditions that produced it.
+    ///
+    /// `timestamp` is the time of the bar in `data`; time based conditions are checked against
+    /// it and it is copied into the signal as is.
+    pub fn evaluate_signal(
+        &mut self,
+        data: &MarketData,
+        timestamp: Option<DateTime<Utc>>,
+    ) -> TaResult<Option<Signal>> {
+        let index = self.index;
+        let context = Context {
+            timestamp,
+            ..Context::default()
+        };
+        self.next();
+        match self.state {
+            State::Progress(_) => {
+                self.nodes.update_with(data, &context)?;
+                Ok(None)
+            }
+            State::Ready => {
+                self.nodes.update_with(data, &context)?;
+                let outcome = self
+                    .nodes
+                    .evaluate_outcome(data, &context, Some("root"), false)?;
+                Ok(Some(Signal {
+                    action: outcome.action,
+                    confidence: outcome.confidence,
+                    timestamp,
+                    index,
+                    nodes: outcome.nodes,
+                    conditions: outcome.conditions,
+                    expiry: self.expiry,
+                    position_size: self.position_size,
+                }))
+            }
+        }
+    }
+
+    /// Evaluates the strategy like `evaluate`, returning a trace of the evaluation.
+    ///
+    /// `timestamp` is the time of the bar in `data`, as for `evaluate_signal`. Returns `None`
+    /// while the strategy is still warming up.
+    pub fn explain(
+        &mut self,
+        data: &MarketData,
+        timestamp: Option<DateTime<Utc>>,
+    ) -> TaResult<Option<NodeTrace>> {
+        let context = Context {
+            timestamp,
+            ..Context::default()
+        };
+        self.next();
+        match self.state {
+            State::Progress(_) => {
+                self.nodes.update_with(data, &context)?;
+                Ok(None)
+            }
+            State::Ready => self.nodes.explain_with(data, &context).map(Some),
         }
     }
 
     fn next(&mut self) {
+        self.index += 1;
         if let State::Progress(index) = self.state {
             if index < self.nodes.period() {
                 self.state = State::Progress(index + 1);
@@ -70,6 +167,7 @@ impl Strategy {
 impl Reset for Strategy {
     fn reset(&mut self) {
         self.state = State::Progress(0);
+        self.index = 0;
         self.nodes.reset();
     }
 }
@@ -80,13 +178,30 @@ impl Period for Strategy {
     }
 }
 
+/// Serialized form of a `Strategy`: the tree, with the signal settings next to its variant.
+/// Strategies saved before the settings existed are bare trees and load without them.
+#[derive(Serialize, Deserialize)]
+struct StrategyDocument {
+    #[serde(flatten)]
+    nodes: StrategyNode,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    expiry: Option<Expiry>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    position_size: Option<f64>,
+}
+
 /// Custom implementation of the Serialize and Deserialize traits for Strategy
 impl Serialize for Strategy {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
     {
-        self.nodes.serialize(serializer)
+        StrategyDocument {
+            nodes: self.nodes.clone(),
+            expiry: self.expiry,
+            position_size: self.position_size,
+        }
+        .serialize(serializer)
     }
 }
 
@@ -95,7 +210,11 @@ impl<'de> Deserialize<'de> for Strategy {
     where
         D: Deserializer<'de>,
     {
-        let nodes = StrategyNode::deserialize(deserializer)?;
-        Ok(Self::new(nodes))
+        let document = StrategyDocument::deserialize(deserializer)?;
+        Ok(Self {
+            expiry: document.expiry,
+            position_size: document.position_size,
+            ..Self::new(document.nodes)
+        })
     }
 }
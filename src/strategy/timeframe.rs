@@ -0,0 +1,405 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+#[cfg(not(feature = "js"))]
+use crate::Indicator;
+use crate::helper_types::Bar;
+#[cfg(feature = "js")]
+use crate::indicators::indicator::Indicator;
+use crate::{
+    error::TaResult,
+    strategy::{MarketData, StrategyError, registry::IndicatorRegistry},
+    traits::{Candle, Reset},
+    types::OutputType,
+};
+
+/// How the base bars are grouped into the bars of a higher timeframe.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resample {
+    /// Buckets of this many seconds aligned to the Unix epoch. Bar timestamps are close
+    /// times, so a bar belongs to the bucket containing the instant just before it closed,
+    /// and a bar closing on a boundary closes its bucket. Otherwise a bucket closes when the
+    /// first bar of a later bucket arrives. Updating requires a timestamp.
+    Seconds(u64),
+    /// Buckets of this many consecutive base bars. A bucket closes with its last bar.
+    Bars(usize),
+}
+
+/// The higher timeframe bar being built from the base bars seen so far.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Bucket {
+    /// Index of the time bucket, unused for `Resample::Bars`.
+    key: i64,
+    bars: usize,
+    open: f64,
+    high: f64,
+    low: f64,
+    close: f64,
+    volume: f64,
+}
+
+impl Bucket {
+    fn new(key: i64, data: &MarketData) -> Self {
+        Self {
+            key,
+            bars: 1,
+            open: data.open(),
+            high: data.high(),
+            low: data.low(),
+            close: data.close(),
+            volume: data.volume(),
+        }
+    }
+
+    fn merge(&mut self, data: &MarketData) {
+        self.bars += 1;
+        self.high = self.high.max(data.high());
+        self.low = self.low.min(data.low());
+        self.close = data.close();
+        self.volume += data.volume();
+    }
+
+    fn bar(&self) -> Bar {
+        Bar::new()
+            .set_open(self.open)
+            .set_high(self.high)
+            .set_low(self.low)
+            .set_close(self.close)
+            .set_price(self.close)
+            .set_volume(self.volume)
+    }
+}
+
+/// A higher timeframe resampled from the base bars, with the indicators computed on it.
+///
+/// The indicators are only updated when a bucket closes, so their values never depend on
+/// base bars of a bucket that is still forming. `peek` exposes the forming bucket explicitly.
+///
+/// The buckets, like the indicator outputs, are not serialized: a restored timeframe starts a
+/// new bucket and warms up again on its next closed bucket.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Timeframe {
+    pub resample: Resample,
+    pub indicators: IndicatorRegistry,
+    #[serde(skip)]
+    forming: Option<Bucket>,
+    /// Number of buckets closed so far.
+    #[serde(skip)]
+    closed: usize,
+}
+
+impl Timeframe {
+    pub fn new(resample: Resample) -> Self {
+        Self {
+            resample,
+            indicators: IndicatorRegistry::new(),
+            forming: None,
+            closed: 0,
+        }
+    }
+
+    /// Registers `indicator` under `id`, computed on the bars of this timeframe.
+    pub fn with_indicator(mut self, id: impl Into<String>, indicator: Indicator) -> Self {
+        self.indicators.insert(id, indicator);
+        self
+    }
+
+    /// Returns the number of buckets closed so far.
+    pub fn closed(&self) -> usize {
+        self.closed
+    }
+
+    /// Returns the bucket that is still forming, if any.
+    pub fn forming(&self) -> Option<Bar> {
+        self.forming.as_ref().map(Bucket::bar)
+    }
+
+    /// Adds a base bar with the given timestamp, updating the indicators with every bucket
+    /// it closes.
+    pub fn update(&mut self, data: &MarketData, timestamp: Option<DateTime<Utc>>) -> TaResult<()> {
+        match self.resample {
+            Resample::Seconds(secs) => {
+                let timestamp = timestamp.ok_or(StrategyError::MissingTimestamp)?;
+                let millis = i64::try_from(secs.max(1))
+                    .ok()
+                    .and_then(|secs| secs.checked_mul(1000))
+                    .unwrap_or(i64::MAX);
+                let time = timestamp.timestamp_millis();
+                let key = (time - 1).div_euclid(millis);
+                match self.forming.as_mut() {
+                    Some(bucket) if bucket.key == key => bucket.merge(data),
+                    _ => {
+                        self.close()?;
+                        self.forming = Some(Bucket::new(key, data));
+                    }
+                }
+                if time.rem_euclid(millis) == 0 {
+                    self.close()?;
+                }
+            }
+            Resample::Bars(bars) => {
+                let bucket = match self.forming.as_mut() {
+                    Some(bucket) => {
+                        bucket.merge(data);
+                        bucket
+                    }
+                    None => self.forming.insert(Bucket::new(0, data)),
+                };
+                if bucket.bars >= bars {
+                    self.close()?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn close(&mut self) -> TaResult<()> {
+        if let Some(bucket) = self.forming.take() {
+            self.indicators.update(&MarketData::Bar(bucket.bar()))?;
+            self.closed += 1;
+        }
+        Ok(())
+    }
+
+    /// Returns the output of the indicator registered under `id` as of the last closed bucket.
+    pub fn prev(&self, id: &str) -> TaResult<OutputType> {
+        self.indicators.prev(id)
+    }
+
+    /// Returns the output the indicator registered under `id` would have if the forming
+    /// bucket closed now, or its last output if no bucket is forming. The indicator itself
+    /// is left untouched.
+    pub fn peek(&self, id: &str) -> TaResult<OutputType> {
+        let Some(bucket) = self.forming.as_ref() else {
+            return self.prev(id);
+        };
+        let mut indicator = self
+            .indicators
+            .get(id)
+            .ok_or_else(|| StrategyError::UnknownIndicator(id.to_string()))?
+            .clone();
+        indicator.update(&MarketData::Bar(bucket.bar()))?;
+        indicator.prev()
+    }
+
+    /// Returns an error if the bucket size is zero or any indicator has a period of zero.
+    pub fn validate(&self) -> TaResult<()> {
+        if matches!(self.resample, Resample::Seconds(0) | Resample::Bars(0)) {
+            return Err(StrategyError::Configuration(format!(
+                "Invalid timeframe bucket size: {:?}",
+                self.resample
+            ))
+            .into());
+        }
+        self.indicators.validate()
+    }
+}
+
+impl Reset for Timeframe {
+    fn reset(&mut self) {
+        self.indicators.reset();
+        self.forming = None;
+        self.closed = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+    use crate::error::TaError;
+    use crate::strategy::condition::Operator;
+    use crate::strategy::{Action, Condition, Context, Operand, StrategyNode};
+    use chrono::{TimeDelta, TimeZone};
+
+    fn minute(minute: i64) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 1, 2, 9, 0, 0).unwrap() + TimeDelta::minutes(minute)
+    }
+
+    fn bar(open: f64, high: f64, low: f64, close: f64) -> MarketData {
+        MarketData::Bar(
+            Bar::new()
+                .set_open(open)
+                .set_high(high)
+                .set_low(low)
+                .set_close(close)
+                .set_price(close)
+                .set_volume(1.0),
+        )
+    }
+
+    fn close(timeframe: &Timeframe) -> Option<f64> {
+        match timeframe.prev("close") {
+            Ok(OutputType::Single(value)) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Buys on the base timeframe above 100 while the 15 minute close is above 100.
+    fn trend_filter(forming: bool) -> TaResult<StrategyNode> {
+        let timeframe =
+            Timeframe::new(Resample::Seconds(900)).with_indicator("close", Indicator::sma(1)?);
+        Ok(StrategyNode::MultiTimeframe {
+            timeframes: BTreeMap::from([("15m".to_string(), timeframe)]),
+            node: Box::new(StrategyNode::If {
+                condition: Condition::And(vec![
+                    Condition::Compare {
+                        left: Operand::Timeframe {
+                            timeframe: "15m".to_string(),
+                            indicator: "close".to_string(),
+                            forming,
+                        },
+                        right: Operand::Value(OutputType::Single(100.0)),
+                        operator: Operator::GreaterThan,
+                    },
+                    Condition::greater_than(Indicator::sma(1)?, OutputType::Single(100.0)),
+                ]),
+                then_branch: Box::new(StrategyNode::Action(Action::Buy)),
+                else_branch: None,
+            }),
+        })
+    }
+
+    fn run(node: &mut StrategyNode, bars: &[(i64, f64)]) -> TaResult<Vec<Action>> {
+        bars.iter()
+            .map(|(t, price)| {
+                node.evaluate_with(&MarketData::Float(*price), &Context::at(minute(*t)))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_resample_by_bar_count() -> TaResult<()> {
+        let mut timeframe =
+            Timeframe::new(Resample::Bars(3)).with_indicator("close", Indicator::sma(1)?);
+        timeframe.update(&bar(10.0, 12.0, 9.0, 11.0), None)?;
+        timeframe.update(&bar(11.0, 15.0, 10.0, 14.0), None)?;
+        let forming = timeframe.forming().unwrap();
+        assert_eq!(
+            (forming.open, forming.high, forming.low, forming.close),
+            (10.0, 15.0, 9.0, 14.0)
+        );
+        assert_eq!(forming.volume, 2.0);
+        assert_eq!(timeframe.closed(), 0);
+        assert_eq!(timeframe.peek("close")?, OutputType::Single(14.0));
+        assert!(timeframe.prev("close").is_err());
+
+        timeframe.update(&bar(14.0, 14.0, 8.0, 9.0), None)?;
+        assert_eq!(timeframe.closed(), 1);
+        assert_eq!(timeframe.forming(), None);
+        assert_eq!(close(&timeframe), Some(9.0));
+        Ok(())
+    }
+
+    #[test]
+    fn test_resample_by_time_bucket() -> TaResult<()> {
+        let mut timeframe =
+            Timeframe::new(Resample::Seconds(900)).with_indicator("close", Indicator::sma(1)?);
+        for (t, price) in [(1, 101.0), (8, 105.0)] {
+            timeframe.update(&MarketData::Float(price), Some(minute(t)))?;
+        }
+        assert_eq!(timeframe.closed(), 0);
+        // The bar closing at 09:15 completes the 09:00 bucket.
+        timeframe.update(&MarketData::Float(99.0), Some(minute(15)))?;
+        assert_eq!(timeframe.closed(), 1);
+        assert_eq!(close(&timeframe), Some(99.0));
+        assert_eq!(timeframe.forming(), None);
+
+        // A gap skips the empty buckets; the next bar closes the forming one.
+        timeframe.update(&MarketData::Float(103.0), Some(minute(50)))?;
+        assert_eq!(timeframe.forming().map(|bar| bar.open), Some(103.0));
+        timeframe.update(&MarketData::Float(98.0), Some(minute(70)))?;
+        assert_eq!(timeframe.closed(), 2);
+        assert_eq!(close(&timeframe), Some(103.0));
+
+        assert_eq!(
+            timeframe.update(&MarketData::Float(1.0), None),
+            Err(TaError::Strategy(StrategyError::MissingTimestamp))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_higher_timeframe_has_no_lookahead() -> TaResult<()> {
+        let mut node = trend_filter(false)?;
+        node.validate()?;
+        // 09:00 bucket closes at 99, 09:15 bucket closes at 104.
+        let bars = [
+            (1, 101.0),
+            (15, 99.0),
+            (16, 104.0),
+            (30, 104.0),
+            (31, 102.0),
+        ];
+        use Action::{Buy, Hold};
+        assert_eq!(run(&mut node, &bars)?, vec![Hold, Hold, Hold, Buy, Buy]);
+
+        // Reading the forming bucket follows the current bucket's close instead.
+        let mut node = trend_filter(true)?;
+        assert_eq!(run(&mut node, &bars)?, vec![Hold, Hold, Buy, Buy, Buy]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_multi_timeframe_warm_up_trace() -> TaResult<()> {
+        let mut node = trend_filter(false)?;
+        let trace = node.explain_with(&MarketData::Float(101.0), &Context::at(minute(1)))?;
+        assert_eq!(trace.id, "root.MultiTimeframe");
+        assert_eq!(trace.note.as_deref(), Some("warming up: timeframe 15m"));
+
+        node.explain_with(&MarketData::Float(101.0), &Context::at(minute(15)))?;
+        let trace = node.explain_with(&MarketData::Float(101.0), &Context::at(minute(16)))?;
+        assert_eq!(trace.action, Action::Buy);
+        let filter = trace
+            .find("root.MultiTimeframe.node.If")
+            .and_then(|node| node.condition.as_ref())
+            .and_then(|condition| condition.find("root.MultiTimeframe.node.If.condition.And[0]"));
+        assert_eq!(
+            filter.and_then(|condition| condition.left.clone()),
+            Some(OutputType::Single(101.0))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_multi_timeframe_validation() -> TaResult<()> {
+        let StrategyNode::MultiTimeframe { timeframes, node } = trend_filter(false)? else {
+            unreachable!()
+        };
+        let renamed = StrategyNode::MultiTimeframe {
+            timeframes: BTreeMap::from([("1h".to_string(), timeframes["15m"].clone())]),
+            node: node.clone(),
+        };
+        assert_eq!(
+            renamed.validate(),
+            Err(TaError::Strategy(StrategyError::UnknownTimeframe(
+                "15m".to_string()
+            )))
+        );
+
+        let empty = StrategyNode::MultiTimeframe {
+            timeframes: BTreeMap::from([("15m".to_string(), Timeframe::new(Resample::Bars(0)))]),
+            node,
+        };
+        assert!(empty.validate().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_multi_timeframe_serde_and_reset() -> TaResult<()> {
+        let mut node = trend_filter(false)?;
+        run(&mut node, &[(1, 101.0), (15, 104.0), (16, 104.0)])?;
+
+        let json = serde_json::to_string(&node)?;
+        let mut restored: StrategyNode = serde_json::from_str(&json)?;
+        assert_eq!(restored.kind(), "MultiTimeframe");
+        // Buckets and indicator outputs are not serialized, so the restored node waits for
+        // a bucket.
+        assert_eq!(run(&mut restored, &[(17, 104.0)])?, vec![Action::Hold]);
+
+        node.reset();
+        assert_eq!(node, trend_filter(false)?);
+        Ok(())
+    }
+}
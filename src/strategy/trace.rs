@@ -0,0 +1,223 @@
+use serde::{Deserialize, Serialize};
+
+use crate::strategy::Action;
+use crate::strategy::condition::Operator;
+use crate::types::OutputType;
+
+/// Trace of a single node evaluation, produced by `StrategyNode::explain`.
+///
+/// Node and condition ids follow the same scheme as `Signal`, for example
+/// `root.Sequence[1].If.then_branch.Action` and `root.Sequence[1].If.condition.And[0]`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct NodeTrace {
+    /// Location of the node in the strategy tree.
+    pub id: String,
+    /// Name of the node variant, e.g. `If` or `Sequence`.
+    pub kind: String,
+    /// Action produced by the node.
+    pub action: Action,
+    /// Confidence of the action between `0.0` and `1.0`.
+    pub confidence: f64,
+    /// Trace of the condition evaluated by an `If` node.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub condition: Option<ConditionTrace>,
+    /// Traces of the child nodes that were evaluated, in evaluation order.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<NodeTrace>,
+    /// Ids of the children whose result was used to produce the action.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub chosen: Vec<String>,
+    /// State of a `StateMachine` node after the evaluation.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub state: Option<String>,
+    /// Traces of the transition conditions checked by a `StateMachine` node.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub transitions: Vec<ConditionTrace>,
+    /// Additional information about the evaluation, e.g. a running cooldown.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+}
+
+/// Trace of a single condition evaluation.
+///
+/// Comparisons record the operator and both operands: indicator operands hold the
+/// indicator's latest output and value operands hold the resolved constant or price.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ConditionTrace {
+    /// Location of the condition in the strategy tree.
+    pub id: String,
+    /// Name of the condition variant, e.g. `Value` or `And`.
+    pub kind: String,
+    /// Whether the condition held.
+    pub result: bool,
+    /// Operator of a comparison condition.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub operator: Option<Operator>,
+    /// Left operand of a comparison condition.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub left: Option<OutputType>,
+    /// Right operand of a comparison condition.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub right: Option<OutputType>,
+    /// Traces of the sub-conditions that were evaluated, in evaluation order.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<ConditionTrace>,
+}
+
+impl NodeTrace {
+    pub(crate) fn new(id: String, kind: &str) -> Self {
+        Self {
+            id,
+            kind: kind.to_string(),
+            action: Action::Hold,
+            confidence: 0.0,
+            condition: None,
+            children: Vec::new(),
+            chosen: Vec::new(),
+            state: None,
+            transitions: Vec::new(),
+            note: None,
+        }
+    }
+
+    /// Returns the trace of the node with the given id, searching this trace and its children.
+    pub fn find(&self, id: &str) -> Option<&NodeTrace> {
+        if self.id == id {
+            return Some(self);
+        }
+        self.children.iter().find_map(|child| child.find(id))
+    }
+}
+
+impl ConditionTrace {
+    /// Returns the trace of the condition with the given id, searching this trace and its children.
+    pub fn find(&self, id: &str) -> Option<&ConditionTrace> {
+        if self.id == id {
+            return Some(self);
+        }
+        self.children.iter().find_map(|child| child.find(id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(not(feature = "js"))]
+    use crate::Indicator;
+    #[cfg(feature = "js")]
+    use crate::indicators::indicator::Indicator;
+
+    use super::*;
+    use crate::error::TaResult;
+    use crate::strategy::node::{SequenceMode, TieBreak};
+    use crate::strategy::{Condition, MarketData, StrategyNode};
+
+    fn strategy() -> TaResult<StrategyNode> {
+        Ok(StrategyNode::Sequence {
+            mode: SequenceMode::Majority,
+            nodes: vec![
+                StrategyNode::If {
+                    condition: Condition::And(vec![
+                        Condition::greater_than(Indicator::sma(1)?, OutputType::Single(10.0)),
+                        Condition::Not(Box::new(Condition::less_than(
+                            Indicator::sma(1)?,
+                            OutputType::Single(20.0),
+                        ))),
+                    ]),
+                    then_branch: Box::new(StrategyNode::Action(Action::Buy)),
+                    else_branch: Some(Box::new(StrategyNode::Action(Action::Sell))),
+                },
+                StrategyNode::Action(Action::Buy),
+                StrategyNode::Timeout {
+                    cooldown: 5,
+                    remaining: 2,
+                    action: Box::new(StrategyNode::Action(Action::Sell)),
+                },
+            ],
+            tie_break: TieBreak::default(),
+        })
+    }
+
+    #[test]
+    fn test_explain_records_conditions_and_choices() -> TaResult<()> {
+        let mut node = strategy()?;
+        let trace = node.explain(&MarketData::Float(30.0))?;
+        assert_eq!(trace.id, "root.Sequence");
+        assert_eq!(trace.kind, "Sequence");
+        assert_eq!(trace.action, Action::Buy);
+        assert_eq!(trace.children.len(), 3);
+        assert_eq!(
+            trace.chosen,
+            vec!["root.Sequence[0].If", "root.Sequence[1].Action"]
+        );
+
+        let branch = trace.find("root.Sequence[0].If").unwrap();
+        assert_eq!(
+            branch.chosen,
+            vec!["root.Sequence[0].If.then_branch.Action"]
+        );
+        let condition = branch.condition.as_ref().unwrap();
+        assert_eq!(condition.id, "root.Sequence[0].If.condition");
+        assert_eq!(condition.kind, "And");
+        assert!(condition.result);
+
+        let value = condition
+            .find("root.Sequence[0].If.condition.And[0]")
+            .unwrap();
+        assert_eq!(value.kind, "Value");
+        assert_eq!(value.operator, Some(Operator::GreaterThan));
+        assert_eq!(value.left, Some(OutputType::Single(30.0)));
+        assert_eq!(value.right, Some(OutputType::Single(10.0)));
+
+        let negated = condition
+            .find("root.Sequence[0].If.condition.And[1].Not")
+            .unwrap();
+        assert!(!negated.result);
+        assert_eq!(negated.left, Some(OutputType::Single(30.0)));
+        assert_eq!(negated.right, Some(OutputType::Single(20.0)));
+
+        let timeout = trace.find("root.Sequence[2].Timeout").unwrap();
+        assert_eq!(timeout.action, Action::Hold);
+        assert!(timeout.children.is_empty());
+        assert_eq!(timeout.note.as_deref(), Some("cooldown: 1 bars remaining"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_explain_matches_evaluate() -> TaResult<()> {
+        let mut explained = strategy()?;
+        let mut evaluated = strategy()?;
+        for price in [5.0, 15.0, 30.0, 12.0, 40.0, 8.0] {
+            let data = MarketData::Float(price);
+            assert_eq!(explained.explain(&data)?.action, evaluated.evaluate(&data)?);
+        }
+        assert_eq!(explained, evaluated);
+        Ok(())
+    }
+
+    #[test]
+    fn test_explain_false_condition_without_else() -> TaResult<()> {
+        let mut node = StrategyNode::If {
+            condition: Condition::greater_than(Indicator::sma(1)?, OutputType::Single(10.0)),
+            then_branch: Box::new(StrategyNode::Action(Action::Buy)),
+            else_branch: None,
+        };
+        let trace = node.explain(&MarketData::Float(5.0))?;
+        assert_eq!(trace.action, Action::Hold);
+        assert!(trace.children.is_empty());
+        assert!(trace.chosen.is_empty());
+        let condition = trace.condition.unwrap();
+        assert!(!condition.result);
+        assert_eq!(condition.left, Some(OutputType::Single(5.0)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_trace_json_roundtrip() -> TaResult<()> {
+        let mut node = strategy()?;
+        let trace = node.explain(&MarketData::Float(15.0))?;
+        let json = serde_json::to_string(&trace)?;
+        let restored: NodeTrace = serde_json::from_str(&json)?;
+        assert_eq!(trace, restored);
+        Ok(())
+    }
+}
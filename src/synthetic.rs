@@ -0,0 +1,411 @@
+//! Seedable generators of synthetic candle series, for property tests and for stress testing
+//! strategies on controlled market conditions before touching real data.
+//!
+//! Prices follow a stochastic process on the log price, so they always stay positive. Every
+//! bar is simulated as a short path from its open to its close, and its high and low are the
+//! extremes of that path, which keeps `low <= open, close <= high` on every bar.
+
+use chipa_ta_utils::TaUtilsError;
+use serde::{Deserialize, Serialize};
+
+use crate::{error::TaResult, helper_types::Bar, random::Rng};
+
+/// Drift and volatility of the log price, per bar.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct Regime {
+    pub drift: f64,
+    pub volatility: f64,
+}
+
+impl Regime {
+    pub fn new(drift: f64, volatility: f64) -> Self {
+        Self { drift, volatility }
+    }
+}
+
+/// Stochastic process followed by the price.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum Process {
+    /// Geometric Brownian motion: the log price has a constant drift and volatility.
+    Gbm(Regime),
+    /// Ornstein-Uhlenbeck process on the log price, pulled back towards `ln(mean)` by the
+    /// fraction `speed` of the distance every bar.
+    MeanReverting {
+        mean: f64,
+        speed: f64,
+        volatility: f64,
+    },
+    /// Geometric Brownian motion whose drift and volatility switch between regimes. At the
+    /// start of every bar the process moves to another, uniformly drawn, regime with
+    /// probability `switch`.
+    RegimeSwitching { regimes: Vec<Regime>, switch: f64 },
+}
+
+impl Default for Process {
+    fn default() -> Self {
+        Process::Gbm(Regime::new(0.0, 0.01))
+    }
+}
+
+/// Sudden moves of the price inside a bar.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct Jumps {
+    /// Probability of a jump in every bar.
+    pub probability: f64,
+    /// Mean of the jump of the log price.
+    pub mean: f64,
+    /// Standard deviation of the jump of the log price.
+    pub std_dev: f64,
+}
+
+/// Differences between the open of a bar and the close of the previous one.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct Gaps {
+    /// Probability that a bar opens with a gap.
+    pub probability: f64,
+    /// Standard deviation of the gap of the log price.
+    pub std_dev: f64,
+}
+
+/// Generator of synthetic candle series.
+///
+/// The same generator and seed always produce the same series.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct Synthetic {
+    pub process: Process,
+    /// Open of the first bar.
+    pub start: f64,
+    pub jumps: Option<Jumps>,
+    pub gaps: Option<Gaps>,
+    /// Number of steps of the path simulated inside every bar.
+    pub steps: usize,
+    /// Typical volume of a bar. Volume grows with the range of the bar.
+    pub volume: f64,
+    pub seed: u64,
+}
+
+impl Default for Synthetic {
+    fn default() -> Self {
+        Self {
+            process: Process::default(),
+            start: 100.0,
+            jumps: None,
+            gaps: None,
+            steps: 8,
+            volume: 1000.0,
+            seed: 0,
+        }
+    }
+}
+
+impl Synthetic {
+    pub fn new(process: Process) -> Self {
+        Self {
+            process,
+            ..Self::default()
+        }
+    }
+
+    /// Geometric Brownian motion with the given drift and volatility per bar.
+    pub fn gbm(drift: f64, volatility: f64) -> Self {
+        Self::new(Process::Gbm(Regime::new(drift, volatility)))
+    }
+
+    /// Log price reverting towards `ln(mean)`, starting at `mean`.
+    pub fn mean_reverting(mean: f64, speed: f64, volatility: f64) -> Self {
+        Self::new(Process::MeanReverting {
+            mean,
+            speed,
+            volatility,
+        })
+        .with_start(mean)
+    }
+
+    pub fn regime_switching(regimes: Vec<Regime>, switch: f64) -> Self {
+        Self::new(Process::RegimeSwitching { regimes, switch })
+    }
+
+    pub fn with_start(mut self, start: f64) -> Self {
+        self.start = start;
+        self
+    }
+
+    pub fn with_jumps(mut self, probability: f64, mean: f64, std_dev: f64) -> Self {
+        self.jumps = Some(Jumps {
+            probability,
+            mean,
+            std_dev,
+        });
+        self
+    }
+
+    pub fn with_gaps(mut self, probability: f64, std_dev: f64) -> Self {
+        self.gaps = Some(Gaps {
+            probability,
+            std_dev,
+        });
+        self
+    }
+
+    pub fn with_steps(mut self, steps: usize) -> Self {
+        self.steps = steps;
+        self
+    }
+
+    pub fn with_volume(mut self, volume: f64) -> Self {
+        self.volume = volume;
+        self
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    pub fn validate(&self) -> TaResult<()> {
+        let invalid = |message: String| Err(TaUtilsError::InvalidParameter(message).into());
+        let probability = |p: f64| (0.0..=1.0).contains(&p);
+        let scale = |value: f64| value.is_finite() && value >= 0.0;
+        let regime = |regime: &Regime| regime.drift.is_finite() && scale(regime.volatility);
+        let valid = match &self.process {
+            Process::Gbm(r) => regime(r),
+            Process::MeanReverting {
+                mean,
+                speed,
+                volatility,
+            } => mean.is_finite() && *mean > 0.0 && probability(*speed) && scale(*volatility),
+            Process::RegimeSwitching { regimes, switch } => {
+                !regimes.is_empty() && regimes.iter().all(regime) && probability(*switch)
+            }
+        };
+        if !valid {
+            return invalid(format!("synthetic process {:?}", self.process));
+        }
+        if !(self.start.is_finite() && self.start > 0.0) {
+            return invalid(format!("synthetic start price {}", self.start));
+        }
+        if self.steps == 0 || !scale(self.volume) {
+            return invalid(format!(
+                "{} steps and volume {} per bar",
+                self.steps, self.volume
+            ));
+        }
+        if let Some(jumps) = self
+            .jumps
+            .filter(|j| !(probability(j.probability) && j.mean.is_finite() && scale(j.std_dev)))
+        {
+            return invalid(format!("synthetic jumps {jumps:?}"));
+        }
+        if let Some(gaps) = self
+            .gaps
+            .filter(|g| !(probability(g.probability) && scale(g.std_dev)))
+        {
+            return invalid(format!("synthetic gaps {gaps:?}"));
+        }
+        Ok(())
+    }
+
+    /// Generates `len` bars.
+    pub fn generate(&self, len: usize) -> TaResult<Vec<Bar>> {
+        Ok(self.generate_with_regimes(len)?.0)
+    }
+
+    /// Generates `len` bars together with the index of the regime every bar was generated
+    /// in. Processes without regimes report regime `0` for every bar.
+    pub fn generate_with_regimes(&self, len: usize) -> TaResult<(Vec<Bar>, Vec<usize>)> {
+        self.validate()?;
+        let mut rng = Rng::new(self.seed);
+        let mut bars = Vec::with_capacity(len);
+        let mut regimes = Vec::with_capacity(len);
+        let mut log_price = self.start.ln();
+        let mut regime = 0;
+        let switching = match &self.process {
+            Process::RegimeSwitching { regimes, switch } if regimes.len() > 1 => {
+                Some((regimes.len(), *switch))
+            }
+            _ => None,
+        };
+        let dt = 1.0 / self.steps as f64;
+        for index in 0..len {
+            if let Some((count, _)) = switching.filter(|(_, switch)| rng.chance(*switch)) {
+                regime = (regime + 1 + rng.below(count - 1)) % count;
+            }
+            let gap = self
+                .gaps
+                .filter(|gaps| index > 0 && rng.chance(gaps.probability));
+            if let Some(gaps) = gap {
+                log_price += rng.normal() * gaps.std_dev;
+            }
+            let jump = self.jumps.and_then(|jumps| {
+                rng.chance(jumps.probability).then(|| {
+                    (
+                        rng.below(self.steps),
+                        jumps.mean + rng.normal() * jumps.std_dev,
+                    )
+                })
+            });
+
+            let open = log_price;
+            let (mut high, mut low) = (open, open);
+            for step in 0..self.steps {
+                log_price += self.step(log_price, regime, dt, &mut rng);
+                if let Some((_, size)) = jump.filter(|(at, _)| *at == step) {
+                    log_price += size;
+                }
+                high = high.max(log_price);
+                low = low.min(log_price);
+            }
+            let range = high - low;
+            let volume = self.volume * (0.3 * rng.normal()).exp() * (1.0 + 10.0 * range);
+            bars.push(
+                Bar::new()
+                    .set_open(open.exp())
+                    .set_high(high.exp())
+                    .set_low(low.exp())
+                    .set_close(log_price.exp())
+                    .set_volume(volume),
+            );
+            regimes.push(regime);
+        }
+        Ok((bars, regimes))
+    }
+
+    /// Change of the log price over one step of `dt` bars.
+    fn step(&self, log_price: f64, regime: usize, dt: f64, rng: &mut Rng) -> f64 {
+        let noise = rng.normal() * dt.sqrt();
+        let gbm =
+            |r: &Regime| (r.drift - r.volatility * r.volatility / 2.0) * dt + r.volatility * noise;
+        match &self.process {
+            Process::Gbm(r) => gbm(r),
+            Process::MeanReverting {
+                mean,
+                speed,
+                volatility,
+            } => speed * (mean.ln() - log_price) * dt + volatility * noise,
+            Process::RegimeSwitching { regimes, .. } => gbm(&regimes[regime]),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Candle;
+
+    fn log_returns(bars: &[Bar]) -> Vec<f64> {
+        bars.windows(2)
+            .map(|pair| (pair[1].close() / pair[0].close()).ln())
+            .collect()
+    }
+
+    fn std_dev(values: &[f64]) -> f64 {
+        let mean = values.iter().sum::<f64>() / values.len() as f64;
+        (values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / values.len() as f64).sqrt()
+    }
+
+    #[test]
+    fn test_consistent_ohlcv() -> TaResult<()> {
+        let generators = [
+            Synthetic::gbm(0.0005, 0.02),
+            Synthetic::mean_reverting(50.0, 0.1, 0.02),
+            Synthetic::regime_switching(
+                vec![Regime::new(0.002, 0.01), Regime::new(-0.003, 0.04)],
+                0.05,
+            ),
+            Synthetic::gbm(0.0, 0.01)
+                .with_jumps(0.05, -0.05, 0.02)
+                .with_gaps(0.1, 0.03),
+        ];
+        for generator in generators {
+            let bars = generator.with_seed(4).generate(500)?;
+            assert_eq!(bars.len(), 500);
+            for bar in &bars {
+                assert!(bar.low() > 0.0);
+                assert!(bar.low() <= bar.open().min(bar.close()));
+                assert!(bar.high() >= bar.open().max(bar.close()));
+                assert!(bar.volume() > 0.0);
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_seeded() -> TaResult<()> {
+        let generator = Synthetic::gbm(0.0, 0.02).with_jumps(0.1, 0.0, 0.05);
+        let first = generator.clone().with_seed(1).generate(100)?;
+        assert_eq!(generator.clone().with_seed(1).generate(100)?, first);
+        assert_ne!(generator.with_seed(2).generate(100)?, first);
+        Ok(())
+    }
+
+    #[test]
+    fn test_processes() -> TaResult<()> {
+        let gbm = Synthetic::gbm(0.0, 0.02).with_seed(3).generate(5000)?;
+        assert!((std_dev(&log_returns(&gbm)) - 0.02).abs() < 0.002);
+        // Without gaps every bar opens at the previous close.
+        assert!(
+            gbm.windows(2)
+                .all(|pair| { (pair[1].open() - pair[0].close()).abs() < 1e-9 * pair[0].close() })
+        );
+
+        let reverting = Synthetic::mean_reverting(50.0, 0.2, 0.02)
+            .with_start(80.0)
+            .with_seed(3)
+            .generate(2000)?;
+        let tail = &reverting[1000..];
+        let mean = tail.iter().map(Candle::close).sum::<f64>() / tail.len() as f64;
+        assert!((mean - 50.0).abs() < 2.5);
+
+        let (bars, regimes) = Synthetic::regime_switching(
+            vec![Regime::new(0.0, 0.005), Regime::new(0.0, 0.05)],
+            0.02,
+        )
+        .with_seed(3)
+        .generate_with_regimes(4000)?;
+        let returns = log_returns(&bars);
+        let calm: Vec<f64> = (1..bars.len())
+            .filter(|&i| regimes[i] == 0)
+            .map(|i| returns[i - 1])
+            .collect();
+        let wild: Vec<f64> = (1..bars.len())
+            .filter(|&i| regimes[i] == 1)
+            .map(|i| returns[i - 1])
+            .collect();
+        assert!(!calm.is_empty() && !wild.is_empty());
+        assert!(std_dev(&wild) > 5.0 * std_dev(&calm));
+        Ok(())
+    }
+
+    #[test]
+    fn test_gaps_and_invalid_parameters() -> TaResult<()> {
+        let bars = Synthetic::gbm(0.0, 0.01)
+            .with_gaps(1.0, 0.05)
+            .with_seed(8)
+            .generate(50)?;
+        assert!(
+            bars.windows(2)
+                .all(|pair| pair[1].open() != pair[0].close())
+        );
+
+        assert!(Synthetic::gbm(0.0, -0.1).generate(10).is_err());
+        assert!(
+            Synthetic::mean_reverting(0.0, 0.1, 0.01)
+                .generate(10)
+                .is_err()
+        );
+        assert!(
+            Synthetic::regime_switching(Vec::new(), 0.1)
+                .generate(10)
+                .is_err()
+        );
+        assert!(Synthetic::default().with_steps(0).generate(10).is_err());
+        assert!(
+            Synthetic::default()
+                .with_jumps(1.5, 0.0, 0.1)
+                .generate(10)
+                .is_err()
+        );
+        Ok(())
+    }
+}